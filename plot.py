@@ -16,6 +16,11 @@ def plot(filename, test, mapkind):
         plot_grow(filename, mapkind)
     elif test == "probe":
         plot_probe(filename, mapkind)
+    elif test == "bulk":
+        plot_bulk(filename, mapkind)
+
+# mean, p50, p95, p99, p99.9, max and stddev per series, with the default percentiles.
+SERIES_COLUMNS = 7
 
 def blank_csv_data():
     csv_data = {}
@@ -46,10 +51,11 @@ def read_csv(filename):
             data["a_95"][(load_factor, meta_bits)] = float(row[5])
             data["a_99"][(load_factor, meta_bits)] = float(row[6])
 
-            data["b_mean"][(load_factor, meta_bits)] = float(row[7])
-            data["b_50"][(load_factor, meta_bits)] = float(row[8])
-            data["b_95"][(load_factor, meta_bits)] = float(row[9])
-            data["b_99"][(load_factor, meta_bits)] = float(row[10])
+            b = 3 + SERIES_COLUMNS
+            data["b_mean"][(load_factor, meta_bits)] = float(row[b])
+            data["b_50"][(load_factor, meta_bits)] = float(row[b + 1])
+            data["b_95"][(load_factor, meta_bits)] = float(row[b + 2])
+            data["b_99"][(load_factor, meta_bits)] = float(row[b + 3])
 
     return data
 
@@ -118,6 +124,41 @@ def plot_churn(filename, mapkind):
 def plot_probe(filename, mapkind):
     make_plots(filename, "probe", mapkind, "present", "absent")
 
+def plot_bulk(filename, mapkind):
+    make_plots(filename, "bulk", mapkind, "probes", "writes")
+
+# the load factor at which the meta-bits summaries are drawn.
+SUMMARY_LOAD = 0.9
+
+# mean probes for present and absent keys against meta bits, at SUMMARY_LOAD or the
+# highest load below it which every meta-bits setting reached.
+def plot_meta_bits_summary(filename, mapkind):
+    data = read_csv(filename)
+
+    loads_by_bits = {}
+    for (load_factor, meta_bits) in data["a_mean"]:
+        loads_by_bits.setdefault(meta_bits, set()).add(load_factor)
+
+    common_loads = set.intersection(*loads_by_bits.values())
+    candidates = [l for l in common_loads if l <= SUMMARY_LOAD + 1e-9]
+    if not candidates:
+        return
+    load_factor = max(candidates)
+
+    meta_bit_counts = sorted(loads_by_bits)
+    present = [data["a_mean"][(load_factor, b)] for b in meta_bit_counts]
+    absent = [data["b_mean"][(load_factor, b)] for b in meta_bit_counts]
+
+    fig, ax = plt.subplots(figsize=(8, 5))
+    ax.set_title(f"{mapkind}: mean probes at load factor {load_factor:.2f}")
+    ax.set(xlabel="meta bits", ylabel="probes")
+    ax.plot(meta_bit_counts, present, marker="o", label="present")
+    ax.plot(meta_bit_counts, absent, marker="o", label="absent")
+    ax.set_xticks(meta_bit_counts)
+    ax.legend()
+    plt.savefig(f"plot/{mapkind}_meta_bits")
+    plt.close(fig)
+
 
 if not(os.path.exists('plot')):
     os.mkdir('plot')
@@ -127,3 +168,5 @@ for file in os.listdir("out"):
     if filename.endswith(".csv"):
         (test, mapkind) = parse_filename(filename)
         plot(filename, test, mapkind)
+        if test == "probe":
+            plot_meta_bits_summary(filename, mapkind)
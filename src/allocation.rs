@@ -0,0 +1,282 @@
+use crate::meta_map::{operation, touch_cache_line, touch_line, Accesses};
+use crate::MapOptions;
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut, Index, IndexMut, Range, RangeFrom, RangeFull, RangeTo};
+use std::slice::SliceIndex;
+
+const HUGEPAGE_SIZE: usize = 2 << 20;
+
+const CACHE_LINE_BYTES: usize = 64;
+
+// allocate a bucket array of `len` copies of `value`.
+//
+// its pages are `options.page_size` bytes. with `options.hugepages`, the allocation is
+// advised to be backed by transparent hugepages before it is first touched, so that
+// TLB effects can be controlled for at large capacities.
+pub fn bucket_array<T: Clone>(len: usize, value: T, options: MapOptions) -> BucketArray<T> {
+    let mut buckets = Vec::with_capacity(len);
+    if options.hugepages {
+        advise_hugepages(&mut buckets);
+    }
+
+    buckets.resize(len, value);
+    let bytes = len * std::mem::size_of::<T>();
+    BucketArray {
+        line_stamps: vec![Cell::new(0); bytes.div_ceil(CACHE_LINE_BYTES)],
+        page_stamps: vec![Cell::new(0); bytes.div_ceil(options.page_size)],
+        dirty_stamps: vec![Cell::new(0); bytes.div_ceil(options.page_size)],
+        last_page: Cell::new((0, 0)),
+        page_size: options.page_size,
+        buckets,
+    }
+}
+
+// buckets laid out back to back, each taking the size of `T`, from the start of a cache
+// line and of a page. indexing notes the lines and pages holding the buckets indexed as
+// touched, and mutable indexing notes their pages as dirtied, for `count_accesses`.
+// iterating through the slice doesn't, as only rebuilds scan whole arrays.
+pub struct BucketArray<T> {
+    buckets: Vec<T>,
+    // the last operation to touch each cache line.
+    line_stamps: Vec<Cell<u64>>,
+    // the last operation to read and to write each page.
+    page_stamps: Vec<Cell<u64>>,
+    dirty_stamps: Vec<Cell<u64>>,
+    // the operation which last read a page, and the page.
+    last_page: Cell<(u64, usize)>,
+    page_size: usize,
+}
+
+impl<T> BucketArray<T> {
+    fn touch(&self, buckets: Range<usize>) {
+        let size = std::mem::size_of::<T>();
+        if buckets.is_empty() || size == 0 {
+            return;
+        }
+
+        let first = buckets.start * size / CACHE_LINE_BYTES;
+        let last = (buckets.end * size - 1) / CACHE_LINE_BYTES;
+        for stamp in &self.line_stamps[first..=last] {
+            touch_cache_line(stamp, |accesses| accesses.bucket_lines += 1);
+        }
+
+        let operation = operation();
+        let first = buckets.start * size / self.page_size;
+        let last = (buckets.end * size - 1) / self.page_size;
+        for page in first..=last {
+            let (last_operation, last_page) = self.last_page.replace((operation, page));
+            let tally: fn(&mut Accesses) =
+                if last_operation == operation && page.abs_diff(last_page) == 1 {
+                    |accesses| accesses.sequential_page_reads += 1
+                } else {
+                    |accesses| accesses.random_page_reads += 1
+                };
+            touch_line(&self.page_stamps[page], tally);
+        }
+    }
+
+    fn dirty(&self, buckets: Range<usize>) {
+        let size = std::mem::size_of::<T>();
+        if buckets.is_empty() || size == 0 {
+            return;
+        }
+
+        let first = buckets.start * size / self.page_size;
+        let last = (buckets.end * size - 1) / self.page_size;
+        for stamp in &self.dirty_stamps[first..=last] {
+            touch_line(stamp, |accesses| accesses.dirty_pages += 1);
+        }
+    }
+}
+
+// the buckets an index selects, out of `len`.
+pub trait Span {
+    fn span(&self, len: usize) -> Range<usize>;
+}
+
+impl Span for usize {
+    fn span(&self, _len: usize) -> Range<usize> {
+        *self..*self + 1
+    }
+}
+
+impl Span for Range<usize> {
+    fn span(&self, _len: usize) -> Range<usize> {
+        self.clone()
+    }
+}
+
+impl Span for RangeFrom<usize> {
+    fn span(&self, len: usize) -> Range<usize> {
+        self.start..len
+    }
+}
+
+impl Span for RangeTo<usize> {
+    fn span(&self, _len: usize) -> Range<usize> {
+        0..self.end
+    }
+}
+
+impl Span for RangeFull {
+    fn span(&self, len: usize) -> Range<usize> {
+        0..len
+    }
+}
+
+impl<T, I: SliceIndex<[T]> + Span> Index<I> for BucketArray<T> {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &I::Output {
+        self.touch(index.span(self.buckets.len()));
+        &self.buckets[index]
+    }
+}
+
+impl<T, I: SliceIndex<[T]> + Span> IndexMut<I> for BucketArray<T> {
+    fn index_mut(&mut self, index: I) -> &mut I::Output {
+        let span = index.span(self.buckets.len());
+        self.touch(span.clone());
+        self.dirty(span);
+        &mut self.buckets[index]
+    }
+}
+
+impl<T> Deref for BucketArray<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.buckets
+    }
+}
+
+impl<T> DerefMut for BucketArray<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.buckets
+    }
+}
+
+// only whole hugepages inside the allocation can be backed by one, so the advice is
+// limited to the aligned part of it.
+#[cfg(target_os = "linux")]
+fn advise_hugepages<T>(buckets: &mut Vec<T>) {
+    let start = buckets.as_mut_ptr() as usize;
+    let end = start + buckets.capacity() * std::mem::size_of::<T>();
+    let aligned_start = start.next_multiple_of(HUGEPAGE_SIZE);
+    let aligned_end = end - end % HUGEPAGE_SIZE;
+    if aligned_end <= aligned_start {
+        return;
+    }
+
+    // SAFETY: the range lies within the vector's allocation, and MADV_HUGEPAGE
+    // does not change its contents.
+    let result = unsafe {
+        libc::madvise(
+            aligned_start as *mut libc::c_void,
+            aligned_end - aligned_start,
+            libc::MADV_HUGEPAGE,
+        )
+    };
+    if result != 0 {
+        eprintln!(
+            "madvise(MADV_HUGEPAGE) failed: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_hugepages<T>(_buckets: &mut Vec<T>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta_map::{count_accesses, RECENT_OPERATIONS};
+
+    #[test]
+    fn lines_touched_are_distinct() {
+        // 8 buckets of 8 bytes per line.
+        let mut buckets = bucket_array(64, 0u64, MapOptions::seeded(1));
+        let ((), lines) = count_accesses(|| {
+            buckets[0] = 1;
+            assert_eq!(buckets[7], 0);
+        });
+        assert_eq!(lines.bucket_lines, 1);
+        assert_eq!(lines.meta_lines, 0);
+
+        let (sum, lines) = count_accesses(|| buckets[7] + buckets[8..20].iter().sum::<u64>());
+        assert_eq!(sum, 0);
+        assert_eq!(lines.bucket_lines, 3);
+
+        assert_eq!(buckets.iter().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn pages_dirtied_are_distinct() {
+        // 512 buckets of 8 bytes per 4 KiB page.
+        let mut buckets = bucket_array(2048, 0u64, MapOptions::seeded(1));
+        let ((), accesses) = count_accesses(|| {
+            buckets[0] = 1;
+            buckets[511] = 1;
+            assert_eq!(buckets[1024], 0);
+        });
+        assert_eq!(accesses.dirty_pages, 1);
+
+        let ((), accesses) = count_accesses(|| {
+            buckets[510..514].fill(2);
+            buckets[2047] = 2;
+        });
+        assert_eq!(accesses.dirty_pages, 3);
+    }
+
+    #[test]
+    fn pages_follow_the_page_size() {
+        // 64 buckets of 8 bytes per 512 byte page.
+        let options = MapOptions {
+            page_size: 512,
+            ..MapOptions::seeded(1)
+        };
+        let mut buckets = bucket_array(2048, 0u64, options);
+        let ((), accesses) = count_accesses(|| {
+            buckets[0] = 1;
+            buckets[64] = 1;
+            buckets[511] = 1;
+        });
+        assert_eq!(accesses.dirty_pages, 3);
+    }
+
+    #[test]
+    fn page_reads_after_a_neighbour_are_sequential() {
+        let buckets = bucket_array(4096, 0u64, MapOptions::seeded(1));
+        let ((), accesses) = count_accesses(|| {
+            // pages 0, 1, 1, 2, then 5 and 4.
+            for bucket in [0, 600, 700, 1100, 2600, 2100] {
+                assert_eq!(buckets[bucket], 0);
+            }
+        });
+        assert_eq!(accesses.random_page_reads, 2);
+        assert_eq!(accesses.sequential_page_reads, 3);
+
+        // every operation starts with a random read.
+        let ((), accesses) = count_accesses(|| assert_eq!(buckets[2100], 0));
+        assert_eq!(accesses.random_page_reads, 1);
+    }
+
+    #[test]
+    fn lines_touched_lately_are_recent() {
+        let buckets = bucket_array(64, 0u64, MapOptions::seeded(1));
+        let ((), accesses) = count_accesses(|| assert_eq!(buckets[0], 0));
+        assert_eq!(accesses.recent_lines, 0);
+
+        // the first line was touched by the last operation, the second never.
+        let ((), accesses) = count_accesses(|| assert_eq!(buckets[0] + buckets[8], 0));
+        assert_eq!(accesses.bucket_lines, 2);
+        assert_eq!(accesses.recent_lines, 1);
+
+        for _ in 0..RECENT_OPERATIONS {
+            count_accesses(|| assert_eq!(buckets[16], 0));
+        }
+        let ((), accesses) = count_accesses(|| assert_eq!(buckets[0] + buckets[16], 0));
+        assert_eq!(accesses.recent_lines, 1);
+    }
+}
@@ -0,0 +1,427 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, MetaMap, Metadata, PslHint};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+// what a search learned about a bucket.
+enum Seen {
+    Empty,
+    Key,
+    // another key, with this home bucket.
+    Other(usize),
+}
+
+// the outcome of searching for a key.
+struct Search {
+    found: Option<usize>,
+    // the key belongs just before this bucket, between the keys with homes up to its own
+    // and those with later homes.
+    gap: usize,
+    // an empty bucket next to the gap, if the search saw one.
+    free: Option<usize>,
+    probes: usize,
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements bidirectional linear probing: every cluster is kept ordered by home bucket,
+// and a key may sit either side of its home. an insert makes room at the key's place in
+// the order by shifting keys towards whichever empty bucket is nearer, keeping clusters
+// centered on their homes. a removal shifts the keys displaced over the hole back.
+//
+// the metadata holds every key's signed displacement as a PSL, zigzag encoded, so that
+// when it is exact a key's home is known without reading its bucket.
+pub struct Bidirectional {
+    hasher: CountingHasher,
+    buckets: BucketArray<Option<u64>>,
+    meta: MetaMap,
+    len: usize,
+}
+
+// displacements 0, -1, 1, -2, 2, ... are stored as PSLs 1, 2, 3, 4, 5, ...
+fn encode(displacement: isize) -> usize {
+    1 + if displacement >= 0 {
+        2 * displacement as usize
+    } else {
+        2 * displacement.unsigned_abs() - 1
+    }
+}
+
+fn decode(psl: usize) -> isize {
+    let zigzag = psl - 1;
+    if zigzag.is_multiple_of(2) {
+        (zigzag / 2) as isize
+    } else {
+        -(zigzag.div_ceil(2) as isize)
+    }
+}
+
+impl Bidirectional {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        Bidirectional {
+            hasher: options.hasher(0),
+            buckets: bucket_array(capacity, None, options),
+            meta: MetaMap::new(capacity, meta_bits),
+            len: 0,
+        }
+    }
+
+    fn bucket_for(&self, key: u64) -> usize {
+        (self.hasher.hash_one(key) % (self.buckets.len() as u64)) as usize
+    }
+
+    // the signed distance from `from` to `to`, the short way around the table.
+    fn offset(&self, from: usize, to: usize) -> isize {
+        let len = self.buckets.len() as isize;
+        let distance = (to as isize - from as isize).rem_euclid(len);
+        if distance > len / 2 {
+            distance - len
+        } else {
+            distance
+        }
+    }
+
+    fn step(&self, bucket: usize, by: isize) -> usize {
+        (bucket as isize + by).rem_euclid(self.buckets.len() as isize) as usize
+    }
+
+    // the home of the key in `bucket`, from its metadata when exact.
+    fn metadata_home(&self, bucket: usize) -> Option<usize> {
+        match self.meta.hint_psl(bucket) {
+            Some(PslHint::Exact(psl)) => Some(self.step(bucket, -decode(psl))),
+            _ => None,
+        }
+    }
+
+    // look at a bucket while searching for `key`, whose home is `home`. the bucket is
+    // only read when its metadata can't tell that it's empty or has another home.
+    fn inspect(&self, bucket: usize, key: u64, home: usize, probes: &mut usize) -> Seen {
+        if self.meta.hint_empty(bucket) {
+            check_hint(|| self.buckets[bucket].is_none(), bucket);
+            explain!("bucket {bucket}: metadata says empty");
+            return Seen::Empty;
+        }
+
+        let known_home = self.metadata_home(bucket);
+        if let Some(other_home) = known_home.filter(|&other_home| other_home != home) {
+            check_hint(|| self.buckets[bucket] != Some(key), bucket);
+            explain!("bucket {bucket}: metadata says its key's home is {other_home}");
+            return Seen::Other(other_home);
+        }
+
+        *probes += 1;
+        match self.buckets[bucket] {
+            None => {
+                explain!("bucket {bucket}: read, empty");
+                Seen::Empty
+            }
+            Some(k) if k == key => {
+                explain!("bucket {bucket}: read, holds the key");
+                Seen::Key
+            }
+            Some(k) => {
+                let other_home = known_home.unwrap_or_else(|| self.bucket_for(k));
+                explain!("bucket {bucket}: read, holds {k} with home {other_home}");
+                Seen::Other(other_home)
+            }
+        }
+    }
+
+    // the home of the key in `bucket`, or None if it's empty.
+    fn home_at(&self, bucket: usize, probes: &mut usize) -> Option<usize> {
+        if self.meta.hint_empty(bucket) {
+            check_hint(|| self.buckets[bucket].is_none(), bucket);
+            return None;
+        }
+        if let Some(home) = self.metadata_home(bucket) {
+            return Some(home);
+        }
+
+        *probes += 1;
+        self.buckets[bucket].map(|key| self.bucket_for(key))
+    }
+
+    // with metadata, a bucket which isn't marked empty is full. without it, the bucket
+    // is read.
+    fn is_empty(&self, bucket: usize, probes: &mut usize) -> bool {
+        if self.meta.hint_empty(bucket) {
+            check_hint(|| self.buckets[bucket].is_none(), bucket);
+            return true;
+        }
+        if self.meta.bits() == 0 {
+            *probes += 1;
+            return self.buckets[bucket].is_none();
+        }
+        check_hint(|| self.buckets[bucket].is_some(), bucket);
+        false
+    }
+
+    // keys with the home of `key` lie right of the keys with earlier homes and left of
+    // those with later ones. search from the home towards that run, in both directions
+    // if the home holds a key with the same home.
+    fn search(&self, key: u64) -> Search {
+        let home = self.bucket_for(key);
+        explain!("home bucket {home}");
+        let mut search = Search {
+            found: None,
+            gap: home,
+            free: None,
+            probes: 0,
+        };
+
+        let home_offset = match self.inspect(home, key, home, &mut search.probes) {
+            Seen::Empty => {
+                search.free = Some(home);
+                return search;
+            }
+            Seen::Key => {
+                search.found = Some(home);
+                return search;
+            }
+            Seen::Other(other_home) => self.offset(home, other_home),
+        };
+
+        if home_offset <= 0 {
+            let mut bucket = home;
+            for _ in 1..self.buckets.len() {
+                bucket = self.step(bucket, 1);
+                match self.inspect(bucket, key, home, &mut search.probes) {
+                    Seen::Empty => {
+                        search.free = Some(bucket);
+                        break;
+                    }
+                    Seen::Key => {
+                        search.found = Some(bucket);
+                        return search;
+                    }
+                    Seen::Other(other_home) if self.offset(home, other_home) > 0 => break,
+                    Seen::Other(_) => {}
+                }
+            }
+            search.gap = bucket;
+        }
+
+        if home_offset >= 0 {
+            let mut bucket = home;
+            for _ in 1..self.buckets.len() {
+                bucket = self.step(bucket, -1);
+                match self.inspect(bucket, key, home, &mut search.probes) {
+                    Seen::Empty => {
+                        search.free = search.free.or(Some(bucket));
+                        break;
+                    }
+                    Seen::Key => {
+                        search.found = Some(bucket);
+                        return search;
+                    }
+                    Seen::Other(other_home) if self.offset(home, other_home) < 0 => break,
+                    Seen::Other(_) => {}
+                }
+            }
+            if home_offset > 0 {
+                search.gap = self.step(bucket, 1);
+            }
+        }
+
+        explain!("the key is absent");
+        search
+    }
+
+    fn set_bucket(&mut self, bucket: usize, key: u64, home: usize) {
+        self.buckets[bucket] = Some(key);
+        let displacement = self.offset(home, bucket);
+        self.meta
+            .set_full(bucket, Metadata::Psl(encode(displacement)));
+    }
+
+    fn clear_bucket(&mut self, bucket: usize) {
+        self.buckets[bucket] = None;
+        self.meta.set_empty(bucket);
+    }
+
+    // move the key in `from` to `to`, which is next to it.
+    fn shift(&mut self, from: usize, to: usize, update: &mut Update) {
+        let key = self.buckets[from].unwrap();
+        let home = self
+            .metadata_home(from)
+            .unwrap_or_else(|| self.bucket_for(key));
+        explain!("shift {key} from bucket {from} to bucket {to}");
+        self.set_bucket(to, key, home);
+        update.total_writes += 1;
+    }
+}
+
+impl Map for Bidirectional {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.bucket_for(key)
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
+        }
+    }
+
+    fn occupied(&self) -> Option<Vec<bool>> {
+        Some(self.buckets.iter().map(Option::is_some).collect())
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let search = self.search(key);
+        Probe {
+            contained: search.found.is_some(),
+            probes: search.probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let search = self.search(key);
+        let mut update = Update {
+            total_probes: search.probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if search.found.is_some() {
+            return update;
+        }
+
+        let home = self.bucket_for(key);
+        let gap = search.gap;
+        let left_of_gap = self.step(gap, -1);
+        if let Some(free) = search
+            .free
+            .filter(|&free| free == gap || free == left_of_gap)
+        {
+            explain!("bucket {free} is empty, place the key there");
+            self.set_bucket(free, key, home);
+            self.len += 1;
+            return update;
+        }
+
+        // find the empty bucket nearest the gap, and shift the keys between towards it.
+        for distance in 0..self.buckets.len() as isize {
+            let right = self.step(gap, distance);
+            if self.is_empty(right, &mut update.total_probes) {
+                explain!("bucket {right} is the nearest empty, shift right to make room");
+                for i in 1..=distance {
+                    let bucket = self.step(gap, distance - i);
+                    self.shift(bucket, self.step(bucket, 1), &mut update);
+                }
+                self.set_bucket(gap, key, home);
+                self.len += 1;
+                return update;
+            }
+
+            let left = self.step(left_of_gap, -distance);
+            if self.is_empty(left, &mut update.total_probes) {
+                explain!("bucket {left} is the nearest empty, shift left to make room");
+                for i in 1..=distance {
+                    let bucket = self.step(left, i);
+                    self.shift(bucket, self.step(bucket, -1), &mut update);
+                }
+                self.set_bucket(left_of_gap, key, home);
+                self.len += 1;
+                return update;
+            }
+        }
+
+        explain!("no empty bucket left, give up");
+        update.completed = false;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let meta = self.meta.describe(bucket);
+                match self.buckets[bucket] {
+                    Some(key) => format!(
+                        "{bucket} {key} displacement={} meta={meta}",
+                        self.offset(self.bucket_for(key), bucket)
+                    ),
+                    None => format!("{bucket} - meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let search = self.search(key);
+        let mut update = Update {
+            total_probes: search.probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some(mut hole) = search.found else {
+            return update;
+        };
+
+        explain!("clear bucket {hole}, then shift back the keys displaced over it");
+        self.clear_bucket(hole);
+        self.len -= 1;
+        update.total_writes += 1;
+
+        // keys right of the hole with homes at or before it move left, or failing any,
+        // keys left of the hole with homes at or after it move right.
+        for direction in [1, -1] {
+            let mut shifted = false;
+            loop {
+                let next = self.step(hole, direction);
+                let Some(next_home) = self.home_at(next, &mut update.total_probes) else {
+                    break;
+                };
+                if self.offset(next_home, next) * direction <= 0 {
+                    break;
+                }
+
+                self.shift(next, hole, &mut update);
+                self.clear_bucket(next);
+                hole = next;
+                shifted = true;
+            }
+
+            if shifted {
+                break;
+            }
+        }
+
+        update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displacements_round_trip() {
+        for displacement in -100..=100 {
+            assert_eq!(decode(encode(displacement)), displacement);
+        }
+        assert_eq!(encode(0), 1);
+    }
+}
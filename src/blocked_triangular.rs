@@ -0,0 +1,262 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, Hint, HintStats, MetaMap, Metadata};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+pub const BLOCK_SLOTS: usize = 8;
+
+#[derive(Clone, Debug)]
+enum BucketItem {
+    Value(u64),
+    Empty,
+    Tombstone,
+}
+
+// the outcome of searching for a key.
+struct Search {
+    found: Option<usize>,
+    // the first tombstone or empty bucket on the way, where the key would be inserted.
+    free: Option<usize>,
+    probes: usize,
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements blocked triangular probing: the table is split into blocks of BLOCK_SLOTS
+// contiguous buckets, triangular offsets select blocks, and the buckets of a block are
+// searched in order. reading any number of buckets of a block counts as one probe, as
+// they share a cache line, and a block whose metadata rules out every bucket isn't
+// read at all. removals leave tombstones, as in OpenAddressing.
+pub struct BlockedTriaProb {
+    hasher: CountingHasher,
+    buckets: BucketArray<BucketItem>,
+    meta: MetaMap,
+    len: usize,
+}
+
+impl BlockedTriaProb {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        let capacity = std::cmp::max(1, capacity / BLOCK_SLOTS) * BLOCK_SLOTS;
+        BlockedTriaProb {
+            hasher: options.hasher(0),
+            buckets: bucket_array(capacity, BucketItem::Empty, options),
+            meta: MetaMap::new(capacity, meta_bits),
+            len: 0,
+        }
+    }
+
+    fn blocks(&self) -> usize {
+        self.buckets.len() / BLOCK_SLOTS
+    }
+
+    fn block_for(&self, hash: u64) -> usize {
+        (hash % self.blocks() as u64) as usize
+    }
+
+    // visit blocks at triangular offsets from the home block until the key or an empty
+    // bucket is found.
+    fn search(&self, key: u64) -> Search {
+        let hash = self.hasher.hash_one(key);
+        let home = self.block_for(hash);
+        explain!("home block {home}");
+        let mut search = Search {
+            found: None,
+            free: None,
+            probes: 0,
+        };
+
+        let mut offset = 0;
+        for i in 0..self.blocks() {
+            offset += i;
+            let block = (home + offset) % self.blocks();
+            let mut read = false;
+
+            let block_buckets = block * BLOCK_SLOTS..(block + 1) * BLOCK_SLOTS;
+            for (bucket, hint) in self.meta.hint_block(block_buckets, hash) {
+                match hint {
+                    Hint::Empty => {
+                        check_hint(|| matches!(self.buckets[bucket], BucketItem::Empty), bucket);
+                        explain!("bucket {bucket}: metadata says empty, the key is absent");
+                        search.free = search.free.or(Some(bucket));
+                        return search;
+                    }
+                    Hint::Tombstone => {
+                        check_hint(
+                            || matches!(self.buckets[bucket], BucketItem::Tombstone),
+                            bucket,
+                        );
+                        explain!("bucket {bucket}: metadata says tombstone");
+                        search.free = search.free.or(Some(bucket));
+                        continue;
+                    }
+                    Hint::NotMatch => {
+                        check_hint(
+                            || !matches!(self.buckets[bucket], BucketItem::Value(k) if k == key),
+                            bucket,
+                        );
+                        explain!("bucket {bucket}: metadata rules the key out, skip");
+                        continue;
+                    }
+                    Hint::Candidate => {}
+                }
+
+                if !read {
+                    explain!("block {block}: read");
+                    search.probes += 1;
+                    read = true;
+                }
+                self.meta
+                    .record_read(matches!(self.buckets[bucket], BucketItem::Value(k) if k == key));
+                match self.buckets[bucket] {
+                    BucketItem::Value(found_key) if found_key == key => {
+                        explain!("bucket {bucket}: holds the key");
+                        search.found = Some(bucket);
+                        return search;
+                    }
+                    BucketItem::Value(found_key) => {
+                        explain!("bucket {bucket}: holds {found_key}")
+                    }
+                    BucketItem::Empty => {
+                        assert_eq!(self.meta.bits(), 0);
+                        explain!("bucket {bucket}: empty, the key is absent");
+                        search.free = search.free.or(Some(bucket));
+                        return search;
+                    }
+                    BucketItem::Tombstone => {
+                        assert!(self.meta.bits() < 2);
+                        explain!("bucket {bucket}: tombstone");
+                        search.free = search.free.or(Some(bucket));
+                    }
+                }
+            }
+        }
+
+        search
+    }
+
+    fn set_bucket(&mut self, bucket: usize, item: BucketItem) {
+        match item {
+            BucketItem::Value(key) => {
+                let hash = self.hasher.hash_one(key);
+                self.meta.set_full(bucket, Metadata::Hash(hash));
+            }
+            BucketItem::Empty => unreachable!(),
+            BucketItem::Tombstone => self.meta.set_tombstone(bucket),
+        }
+        self.buckets[bucket] = item;
+    }
+}
+
+impl Map for BlockedTriaProb {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.block_for(self.hasher.hash_one(key)) * BLOCK_SLOTS
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        self.meta.hint_stats()
+    }
+
+    fn occupied(&self) -> Option<Vec<bool>> {
+        Some(
+            self.buckets
+                .iter()
+                .map(|item| !matches!(item, BucketItem::Empty))
+                .collect(),
+        )
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let search = self.search(key);
+        Probe {
+            contained: search.found.is_some(),
+            probes: search.probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let search = self.search(key);
+        let mut update = Update {
+            total_probes: search.probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if search.found.is_some() {
+            return update;
+        }
+
+        let Some(bucket) = search.free else {
+            explain!("no free bucket on the probe sequence, give up");
+            update.completed = false;
+            return update;
+        };
+
+        explain!("place the key in bucket {bucket}");
+        self.set_bucket(bucket, BucketItem::Value(key));
+        self.len += 1;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let block = bucket / BLOCK_SLOTS;
+                let meta = self.meta.describe(bucket);
+                match self.buckets[bucket] {
+                    BucketItem::Value(key) => format!("{bucket} (block {block}) {key} meta={meta}"),
+                    BucketItem::Empty => format!("{bucket} (block {block}) - meta={meta}"),
+                    BucketItem::Tombstone => {
+                        format!("{bucket} (block {block}) tombstone meta={meta}")
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let search = self.search(key);
+        let mut update = Update {
+            total_probes: search.probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some(bucket) = search.found else {
+            return update;
+        };
+
+        explain!("leave a tombstone in bucket {bucket}");
+        self.set_bucket(bucket, BucketItem::Tombstone);
+        self.len -= 1;
+        update.total_writes += 1;
+        update
+    }
+}
@@ -0,0 +1,79 @@
+use bitvec::prelude::*;
+
+// the bits per key a page's filter may be sized for.
+pub const BITS_PER_KEY: [usize; 3] = [4, 8, 16];
+
+// a bloom filter over the hashes of the keys of a block of slots, consulted before any
+// of them is probed. keys can't be taken out, so a removed key's bits stay set until
+// the filter is rebuilt.
+pub struct Bloom {
+    bits: BitVec<u64, Msb0>,
+    // bit positions set per key, the optimal count for the bits per key.
+    k: usize,
+}
+
+impl Bloom {
+    pub fn new(keys: usize, bits_per_key: usize) -> Self {
+        Bloom {
+            bits: bitvec![u64, Msb0; 0; keys * bits_per_key],
+            k: std::cmp::max(
+                1,
+                (bits_per_key as f64 * std::f64::consts::LN_2).round() as usize,
+            ),
+        }
+    }
+
+    pub fn insert(&mut self, hash: u64) {
+        for bit in self.positions(hash) {
+            self.bits.set(bit, true);
+        }
+    }
+
+    // false means definitely absent.
+    pub fn may_contain(&self, hash: u64) -> bool {
+        self.positions(hash).all(|bit| self.bits[bit])
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.bits.len().div_ceil(8)
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.fill(false);
+    }
+
+    // the hash's low bits choose the block and its high bits the fingerprints, so the
+    // positions are drawn from a remixed hash, by double hashing. the remix is the
+    // splitmix64 finalizer, which spreads every bit of the hash over the result.
+    fn positions(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let mut mixed = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        mixed ^= mixed >> 31;
+        let (a, b) = (mixed >> 32, mixed as u32 as u64 | 1);
+        let len = self.bits.len() as u64;
+        (0..self.k as u64).map(move |i| (a.wrapping_add(i.wrapping_mul(b)) % len) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    #[test]
+    fn no_false_negatives() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        for bits_per_key in BITS_PER_KEY {
+            let mut bloom = Bloom::new(8, bits_per_key);
+            let hashes: Vec<u64> = (0..8).map(|_| rng.gen()).collect();
+            for &hash in &hashes {
+                bloom.insert(hash);
+            }
+            assert!(hashes.iter().all(|&hash| bloom.may_contain(hash)));
+
+            bloom.clear();
+            assert!(!bloom.may_contain(hashes[0]));
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::candidates;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata};
+use crate::{EvictionRng, Map, MapOptions, MemoryUsage, Probe, Update};
+use rand::prelude::*;
+
+pub const SLOTS_PER_BUCKET: usize = 4;
+
+// dummy hash-set for u64 keys.
+//
+// implements (2, 4)-cuckoo hashing: every key has two candidate buckets of
+// SLOTS_PER_BUCKET slots, one in each half of the table. metadata is kept per slot,
+// and every slot read counts as one probe.
+pub struct BucketizedCuckoo {
+    hashers: Vec<CountingHasher>,
+    slots: BucketArray<Option<u64>>,
+    meta: MetaMap,
+    len: usize,
+    rng: EvictionRng,
+}
+
+impl BucketizedCuckoo {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        let capacity = std::cmp::max(2, capacity / SLOTS_PER_BUCKET) * SLOTS_PER_BUCKET;
+        BucketizedCuckoo {
+            hashers: (0..2).map(|i| options.hasher(i)).collect(),
+            slots: bucket_array(capacity, None, options),
+            meta: MetaMap::new(capacity, meta_bits),
+            len: 0,
+            rng: options.hashes.eviction_rng(),
+        }
+    }
+
+    // (hash, [bucket_a, bucket_b])
+    fn buckets(&self, key: u64) -> (u64, [usize; 2]) {
+        let hash_a = self.hashers[0].hash_one(key);
+        let buckets = self.slots.len() / SLOTS_PER_BUCKET;
+        let a_map_len = buckets / 2;
+        let b_map_len = buckets - a_map_len;
+
+        let bucket_a = (hash_a % a_map_len as u64) as usize;
+        let bucket_b = (self.hashers[1].hash_one(key) % b_map_len as u64) as usize + a_map_len;
+
+        (hash_a, [bucket_a, bucket_b])
+    }
+
+    // the slots of both candidate buckets.
+    fn candidate_slots(buckets: [usize; 2]) -> Vec<usize> {
+        buckets
+            .iter()
+            .flat_map(|&bucket| bucket * SLOTS_PER_BUCKET..(bucket + 1) * SLOTS_PER_BUCKET)
+            .collect()
+    }
+
+    fn set_slot(&mut self, slot: usize, key: u64, hash: u64) {
+        self.slots[slot] = Some(key);
+        self.meta.set_full(slot, Metadata::Hash(hash));
+    }
+
+    fn clear_slot(&mut self, slot: usize) {
+        self.slots[slot] = None;
+        self.meta.set_empty(slot);
+    }
+}
+
+impl Map for BucketizedCuckoo {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.buckets(key).1[0] * SLOTS_PER_BUCKET
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hashers.iter().map(|h| h.stats()).sum()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.slots[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        self.meta.hint_stats()
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (hash, buckets) = self.buckets(key);
+        explain!("candidate buckets {buckets:?}");
+        let (found, probes) = candidates::find(
+            &self.meta,
+            &self.slots,
+            &Self::candidate_slots(buckets),
+            key,
+            hash,
+        );
+
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        const MAX_CHAIN: usize = 128;
+
+        let mut update = Update {
+            total_probes: 0,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let mut active_key = key;
+        let mut key_info = self.buckets(key);
+
+        // test for presence.
+        {
+            let (hash, buckets) = key_info;
+            explain!("candidate buckets {buckets:?}");
+            let (found, probes) = candidates::find(
+                &self.meta,
+                &self.slots,
+                &Self::candidate_slots(buckets),
+                key,
+                hash,
+            );
+            update.total_probes += probes;
+            if found.is_some() {
+                return update;
+            }
+        }
+
+        self.len += 1;
+
+        // bit `i` is set if `buckets[i]` is a candidate for the active key. a displaced
+        // key may only move to its other bucket.
+        let mut candidates: u8 = 0b11;
+
+        for _ in 0..MAX_CHAIN {
+            let (hash, buckets) = key_info;
+
+            // if any slot of a candidate bucket is empty, use that.
+            for (i, &bucket) in buckets.iter().enumerate() {
+                if candidates & (1 << i) == 0 {
+                    continue;
+                }
+
+                for slot in bucket * SLOTS_PER_BUCKET..(bucket + 1) * SLOTS_PER_BUCKET {
+                    if self.meta.hint_empty(slot) {
+                        check_hint(|| self.slots[slot].is_none(), slot);
+                        explain!("slot {slot}: metadata says empty, place {active_key}");
+                    } else if self.meta.bits() == 0 {
+                        update.total_probes += 1;
+                        if self.slots[slot].is_some() {
+                            continue;
+                        }
+                        explain!("slot {slot}: read, empty, place {active_key}");
+                    } else {
+                        continue;
+                    }
+
+                    if active_key != key {
+                        update.total_writes += 1;
+                    }
+                    self.set_slot(slot, active_key, hash);
+                    return update;
+                }
+            }
+
+            // every slot is full. evict a random slot of a random candidate bucket.
+            let evict_bucket = match candidates {
+                0b01 => buckets[0],
+                0b10 => buckets[1],
+                _ => buckets[self.rng.gen_range(0..2)],
+            };
+            let evict_slot =
+                evict_bucket * SLOTS_PER_BUCKET + self.rng.gen_range(0..SLOTS_PER_BUCKET);
+
+            // without metadata, every slot has already been read.
+            if self.meta.bits() > 0 {
+                update.total_probes += 1;
+            }
+
+            let swap_key = self.slots[evict_slot].unwrap();
+            explain!(
+                "no candidate slot is empty, evict {swap_key} from slot {evict_slot} and place {active_key}"
+            );
+            update.total_writes += 1;
+            update.evictions += 1;
+            self.set_slot(evict_slot, active_key, hash);
+
+            key_info = self.buckets(swap_key);
+            candidates = if evict_bucket == key_info.1[0] {
+                0b10
+            } else {
+                0b01
+            };
+            active_key = swap_key;
+        }
+
+        explain!("eviction chain too long, give up");
+        update.completed = false;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.slots.len())
+            .map(|slot| {
+                let meta = self.meta.describe(slot);
+                let bucket = slot / SLOTS_PER_BUCKET;
+                match self.slots[slot] {
+                    Some(key) => format!("{slot} (bucket {bucket}) {key} meta={meta}"),
+                    None => format!("{slot} (bucket {bucket}) - meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (hash, buckets) = self.buckets(key);
+        explain!("candidate buckets {buckets:?}");
+        let (found, total_probes) = candidates::find(
+            &self.meta,
+            &self.slots,
+            &Self::candidate_slots(buckets),
+            key,
+            hash,
+        );
+
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if let Some(slot) = found {
+            explain!("clear slot {slot}");
+            self.clear_slot(slot);
+            self.len -= 1;
+            update.total_writes += 1;
+        }
+
+        update
+    }
+}
@@ -0,0 +1,229 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+pub const SLOTS_PER_LINE: usize = 7;
+
+// the tag is a whole byte stored next to the keys.
+pub const TAG_BITS: usize = 8;
+
+// EMPTY: `0x00`
+// TOMBSTONE: `0x01`
+// FULL: `1 | top 7 bits of the hash`
+const EMPTY: u8 = 0x00;
+const TOMBSTONE: u8 = 0x01;
+
+// one 64-byte cache line: 7 tag bytes (plus one spare) followed by 7 keys.
+#[derive(Clone, Copy)]
+#[repr(C, align(64))]
+struct Line {
+    tags: [u8; SLOTS_PER_LINE + 1],
+    keys: [u64; SLOTS_PER_LINE],
+}
+
+impl Line {
+    const EMPTY: Line = Line {
+        tags: [EMPTY; SLOTS_PER_LINE + 1],
+        keys: [0; SLOTS_PER_LINE],
+    };
+
+    fn find(&self, key: u64, tag: u8) -> Option<usize> {
+        (0..SLOTS_PER_LINE).find(|&slot| self.tags[slot] == tag && self.keys[slot] == key)
+    }
+
+    fn free_slot(&self) -> Option<usize> {
+        (0..SLOTS_PER_LINE).find(|&slot| self.tags[slot] == EMPTY || self.tags[slot] == TOMBSTONE)
+    }
+
+    // a search never continues past a line with an empty slot, because
+    // an insertion would have used it.
+    fn has_empty(&self) -> bool {
+        self.tags[..SLOTS_PER_LINE].contains(&EMPTY)
+    }
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements linear probing over cache lines, with the tags co-located with the keys
+// so that a single line access serves both the metadata check and the key compare.
+// every line touched counts as one probe.
+pub struct CacheLine {
+    hasher: CountingHasher,
+    lines: BucketArray<Line>,
+    len: usize,
+}
+
+impl CacheLine {
+    pub fn new(capacity: usize, options: MapOptions) -> Self {
+        let lines = std::cmp::max(1, capacity / SLOTS_PER_LINE);
+        CacheLine {
+            hasher: options.hasher(0),
+            lines: bucket_array(lines, Line::EMPTY, options),
+            len: 0,
+        }
+    }
+
+    // (tag, home line)
+    fn tag_and_line(&self, key: u64) -> (u8, usize) {
+        let hash = self.hasher.hash_one(key);
+        let tag = 0x80 | (hash >> 57) as u8;
+        (tag, (hash % self.lines.len() as u64) as usize)
+    }
+
+    // returns the (line, slot) holding the key, along with the number of lines probed.
+    fn search(&self, key: u64) -> (Option<(usize, usize)>, usize) {
+        let (tag, home) = self.tag_and_line(key);
+        explain!("home line {home}, tag {tag:02x}");
+
+        for i in 0..self.lines.len() {
+            let line_index = (home + i) % self.lines.len();
+            let line = &self.lines[line_index];
+
+            if let Some(slot) = line.find(key, tag) {
+                explain!("line {line_index}: the key is in slot {slot}");
+                return (Some((line_index, slot)), i + 1);
+            }
+
+            if line.has_empty() {
+                explain!("line {line_index}: no match, and an empty slot, the key is absent");
+                return (None, i + 1);
+            }
+            explain!("line {line_index}: no match, and full, go on");
+        }
+
+        (None, self.lines.len())
+    }
+}
+
+impl Map for CacheLine {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.lines.len() * SLOTS_PER_LINE
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.tag_and_line(key).1
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    // the tags are the metadata, though they share a line with the keys.
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: self.lines.len() * std::mem::size_of::<[u64; SLOTS_PER_LINE]>(),
+            metadata: self.lines.len() * std::mem::size_of::<[u8; SLOTS_PER_LINE + 1]>(),
+            auxiliary: 0,
+        }
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, probes) = self.search(key);
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let mut update = Update {
+            total_probes: 0,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let (tag, home) = self.tag_and_line(key);
+        explain!("home line {home}, tag {tag:02x}");
+        let mut target = None;
+
+        for i in 0..self.lines.len() {
+            let line_index = (home + i) % self.lines.len();
+            let line = &self.lines[line_index];
+            update.total_probes += 1;
+
+            if line.find(key, tag).is_some() {
+                explain!("line {line_index}: already holds the key");
+                return update;
+            }
+
+            if target.is_none() {
+                target = line.free_slot().map(|slot| (line_index, slot));
+            }
+
+            if line.has_empty() {
+                explain!("line {line_index}: has an empty slot, stop");
+                break;
+            }
+            explain!("line {line_index}: no match, and full, go on");
+        }
+
+        let Some((line_index, slot)) = target else {
+            update.completed = false;
+            return update;
+        };
+
+        explain!("place the key in line {line_index}, slot {slot}");
+        let line = &mut self.lines[line_index];
+        line.tags[slot] = tag;
+        line.keys[slot] = key;
+        self.len += 1;
+
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(line_index, line)| {
+                let slots: Vec<String> = (0..SLOTS_PER_LINE)
+                    .map(|slot| match line.tags[slot] {
+                        EMPTY => "-".to_string(),
+                        TOMBSTONE => "tombstone".to_string(),
+                        tag => format!("{}:{tag:02x}", line.keys[slot]),
+                    })
+                    .collect();
+                format!("{line_index} {}", slots.join(" "))
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some((line_index, slot)) = found else {
+            update.completed = false;
+            return update;
+        };
+
+        // if the line already holds an empty slot, no search passes it, so the
+        // slot can be cleared outright rather than leaving a tombstone.
+        let line = &mut self.lines[line_index];
+        line.tags[slot] = if line.has_empty() { EMPTY } else { TOMBSTONE };
+        explain!(
+            "clear line {line_index}, slot {slot} (tag {:02x})",
+            line.tags[slot]
+        );
+        update.total_writes += 1;
+        self.len -= 1;
+
+        update
+    }
+}
@@ -0,0 +1,183 @@
+use crate::meta_map::{check_hint, MetaMap};
+use std::ops::{Index, Range};
+
+// search the candidate buckets of a multi-choice scheme for a key.
+//
+// a bucket is only read when its metadata doesn't rule the key out, and every read
+// counts as one probe.
+//
+// return a tuple containing:
+//  + option containing the bucket holding the key
+//  + number of probes
+pub fn find(
+    meta: &MetaMap,
+    buckets: &impl Index<usize, Output = Option<u64>>,
+    candidates: &[usize],
+    key: u64,
+    hash: u64,
+) -> (Option<usize>, usize) {
+    let mut probes = 0;
+
+    for &bucket in candidates {
+        if meta.hint_not_match(bucket, hash) {
+            check_hint(|| buckets[bucket] != Some(key), bucket);
+            explain!("bucket {bucket}: metadata rules the key out, skip");
+            continue;
+        }
+
+        probes += 1;
+        meta.record_read(buckets[bucket] == Some(key));
+        if buckets[bucket] == Some(key) {
+            explain!("bucket {bucket}: read, holds the key");
+            return (Some(bucket), probes);
+        }
+        match buckets[bucket] {
+            Some(other) => explain!("bucket {bucket}: read, holds {other}"),
+            None => explain!("bucket {bucket}: read, empty"),
+        }
+    }
+
+    (None, probes)
+}
+
+// like `find`, for a contiguous run of candidate buckets. their metadata is scanned a
+// group at a time rather than bucket by bucket.
+pub fn find_range(
+    meta: &MetaMap,
+    buckets: &impl Index<usize, Output = Option<u64>>,
+    candidates: Range<usize>,
+    key: u64,
+    hash: u64,
+) -> (Option<usize>, usize) {
+    let mut probes = 0;
+    let mut next = candidates.start;
+
+    for bucket in meta.match_candidates(candidates.clone(), hash) {
+        check_hint(|| (next..bucket).all(|b| buckets[b] != Some(key)), next);
+        next = bucket + 1;
+
+        probes += 1;
+        meta.record_read(buckets[bucket] == Some(key));
+        if buckets[bucket] == Some(key) {
+            explain!("bucket {bucket}: read, holds the key");
+            return (Some(bucket), probes);
+        }
+        match buckets[bucket] {
+            Some(other) => explain!("bucket {bucket}: read, holds {other}"),
+            None => explain!("bucket {bucket}: read, empty"),
+        }
+    }
+
+    check_hint(
+        || (next..candidates.end).all(|b| buckets[b] != Some(key)),
+        next,
+    );
+    explain!("no other bucket of {candidates:?} matches the key's metadata");
+    (None, probes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, find_range};
+    use crate::meta_map::{MetaMap, Metadata};
+
+    const KEY: u64 = 7;
+    const HASH: u64 = 0xA000_0000_0000_0000;
+    const OTHER_HASH: u64 = 0x5000_0000_0000_0000;
+
+    // buckets 0..4, with the key stored in `at`. every other bucket holds a key
+    // whose metadata differs from the key's.
+    fn setup(meta_bits: usize, at: usize) -> (MetaMap, Vec<Option<u64>>) {
+        let mut meta = MetaMap::new(4, meta_bits);
+        let mut buckets = vec![None; 4];
+        for (bucket, slot) in buckets.iter_mut().enumerate() {
+            if bucket == at {
+                *slot = Some(KEY);
+                meta.set_full(bucket, Metadata::Hash(HASH));
+            } else {
+                *slot = Some(100 + bucket as u64);
+                meta.set_full(bucket, Metadata::Hash(OTHER_HASH));
+            }
+        }
+
+        (meta, buckets)
+    }
+
+    #[test]
+    fn no_hints_reads_every_candidate_up_to_the_key() {
+        let (meta, buckets) = setup(0, 2);
+        assert_eq!(find(&meta, &buckets, &[0, 1, 2], KEY, HASH), (Some(2), 3));
+        assert_eq!(find(&meta, &buckets, &[0, 1, 3], KEY, HASH), (None, 3));
+    }
+
+    #[test]
+    fn hints_skip_mismatching_candidates() {
+        let (meta, buckets) = setup(4, 2);
+        assert_eq!(find(&meta, &buckets, &[0, 1, 2], KEY, HASH), (Some(2), 1));
+        assert_eq!(find(&meta, &buckets, &[0, 1, 3], KEY, HASH), (None, 0));
+    }
+
+    #[test]
+    fn hints_skip_empty_candidates() {
+        let mut meta = MetaMap::new(4, 2);
+        let mut buckets = vec![None; 4];
+        buckets[3] = Some(KEY);
+        meta.set_full(3, Metadata::Hash(HASH));
+
+        assert_eq!(find(&meta, &buckets, &[0, 1, 3], KEY, HASH), (Some(3), 1));
+        assert_eq!(find(&meta, &buckets, &[0, 1, 2], KEY, HASH), (None, 0));
+    }
+
+    #[test]
+    fn the_first_candidate_is_checked() {
+        for meta_bits in [0, 1, 2, 8] {
+            let (meta, buckets) = setup(meta_bits, 0);
+            assert_eq!(find(&meta, &buckets, &[0, 1, 2], KEY, HASH).0, Some(0));
+        }
+    }
+
+    #[test]
+    fn find_range_agrees_with_find() {
+        for meta_bits in [0, 1, 2, 4, 8] {
+            for at in 0..4 {
+                let (meta, buckets) = setup(meta_bits, at);
+                for range in [0..4, 1..3, 0..2, 3..4, 2..2] {
+                    let slots: Vec<usize> = range.clone().collect();
+                    assert_eq!(
+                        find_range(&meta, &buckets, range, KEY, HASH),
+                        find(&meta, &buckets, &slots, KEY, HASH)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_hint_collision_costs_a_probe() {
+        // with one bit, every full bucket matches.
+        let (meta, buckets) = setup(1, 2);
+        assert_eq!(find(&meta, &buckets, &[0, 1, 2], KEY, HASH), (Some(2), 3));
+        assert_eq!(find(&meta, &buckets, &[0, 1, 3], KEY, HASH), (None, 3));
+    }
+
+    #[test]
+    fn hint_stats_count_false_matches() {
+        let (meta, buckets) = setup(1, 2);
+        find(&meta, &buckets, &[0, 1, 2], KEY, HASH);
+        let stats = meta.hint_stats();
+        assert_eq!(
+            (stats.matches, stats.false_matches, stats.rejections),
+            (3, 2, 0)
+        );
+        assert_eq!(stats.false_positive_rate(), 1.0);
+
+        let (meta, buckets) = setup(4, 2);
+        find_range(&meta, &buckets, 0..4, KEY, HASH);
+        let stats = meta.hint_stats();
+        assert_eq!(
+            (stats.matches, stats.false_matches, stats.rejections),
+            (1, 0, 3)
+        );
+        assert_eq!(stats.false_positive_rate(), 0.0);
+    }
+}
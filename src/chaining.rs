@@ -0,0 +1,129 @@
+use crate::chains::Chains;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+// dummy hash-set for u64 keys.
+//
+// implements separate chaining: every bucket heads a singly-linked chain of nodes,
+// kept in an arena by `Chains`. new keys are pushed to the front of their chain.
+//
+// every node visited counts as a probe, and reading a bucket's head is free, as an
+// empty chain plays the part of metadata. there is no meta-bits sweep.
+pub struct Chaining {
+    hasher: CountingHasher,
+    chains: Chains,
+    len: usize,
+}
+
+impl Chaining {
+    pub fn new(capacity: usize, options: MapOptions) -> Self {
+        Chaining {
+            hasher: options.hasher(0),
+            chains: Chains::new(capacity, options),
+            len: 0,
+        }
+    }
+
+    fn home(&self, key: u64) -> usize {
+        (self.hasher.hash_one(key) % self.chains.buckets() as u64) as usize
+    }
+
+    // walk the key's chain.
+    //
+    // return a tuple containing:
+    //  + option containing the node holding the key, and the node before it
+    //  + number of probes
+    fn search(&self, key: u64) -> (Option<(usize, Option<usize>)>, usize) {
+        let bucket = self.home(key);
+        explain!("bucket {bucket}");
+        let mut probes = 0;
+        let found = self.chains.find(bucket, key, &mut probes);
+        if found.is_none() {
+            explain!("end of the chain, the key is absent");
+        }
+        (found, probes)
+    }
+}
+
+impl Map for Chaining {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.chains.buckets()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.home(key)
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        self.chains.memory_usage()
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, probes) = self.search(key);
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if found.is_some() {
+            return update;
+        }
+
+        // the node, then the bucket's head.
+        let bucket = self.home(key);
+        let index = self.chains.push(bucket, key);
+        explain!("push node {index} to the front of bucket {bucket}");
+        self.len += 1;
+        update.total_writes += 1;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        self.chains.dump()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some((node, prev)) = found else {
+            return update;
+        };
+
+        explain!("unlink node {node}");
+        let bucket = self.home(key);
+        self.chains.unlink(bucket, node, prev);
+        self.len -= 1;
+        update.total_writes += 1;
+        update
+    }
+}
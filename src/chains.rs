@@ -0,0 +1,115 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::{MapOptions, MemoryUsage};
+
+// a chain node, linked by its index in the arena.
+#[derive(Clone, Copy)]
+struct Node {
+    key: u64,
+    next: Option<usize>,
+}
+
+// singly-linked chains of keys, one headed by each bucket. the nodes live in one
+// contiguous arena rather than separate allocations, and removed nodes are kept on a
+// free list for reuse. new keys are pushed to the front of their chain.
+pub struct Chains {
+    heads: BucketArray<Option<usize>>,
+    arena: Vec<Node>,
+    free: Vec<usize>,
+}
+
+impl Chains {
+    pub fn new(buckets: usize, options: MapOptions) -> Self {
+        Chains {
+            heads: bucket_array(buckets, None, options),
+            arena: Vec::with_capacity(buckets),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn buckets(&self) -> usize {
+        self.heads.len()
+    }
+
+    // walk the bucket's chain, counting every node visited as a probe.
+    //
+    // return option containing the node holding the key, and the node before it
+    pub fn find(
+        &self,
+        bucket: usize,
+        key: u64,
+        probes: &mut usize,
+    ) -> Option<(usize, Option<usize>)> {
+        let mut prev = None;
+        let mut next = self.heads[bucket];
+        while let Some(node) = next {
+            *probes += 1;
+            if self.arena[node].key == key {
+                explain!("node {node}: read, holds the key");
+                return Some((node, prev));
+            }
+            explain!("node {node}: read, holds {}", self.arena[node].key);
+            prev = Some(node);
+            next = self.arena[node].next;
+        }
+        None
+    }
+
+    // push the key to the front of the bucket's chain, writing the node and then the
+    // head. return the node.
+    pub fn push(&mut self, bucket: usize, key: u64) -> usize {
+        let node = Node {
+            key,
+            next: self.heads[bucket],
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.arena[index] = node;
+                index
+            }
+            None => {
+                self.arena.push(node);
+                self.arena.len() - 1
+            }
+        };
+        self.heads[bucket] = Some(index);
+        index
+    }
+
+    // unlink a node found in the bucket's chain, and free it.
+    pub fn unlink(&mut self, bucket: usize, node: usize, prev: Option<usize>) {
+        let next = self.arena[node].next;
+        match prev {
+            Some(prev) => self.arena[prev].next = next,
+            None => self.heads[bucket] = next,
+        }
+        self.free.push(node);
+    }
+
+    // the nodes hold the keys, so count as buckets along with the heads.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.heads[..])
+                + std::mem::size_of_val(&self.arena[..]),
+            metadata: 0,
+            auxiliary: std::mem::size_of_val(&self.free[..]),
+        }
+    }
+
+    pub fn dump(&self) -> Vec<String> {
+        (0..self.heads.len())
+            .map(|bucket| {
+                let mut keys = Vec::new();
+                let mut next = self.heads[bucket];
+                while let Some(node) = next {
+                    keys.push(format!("{} (node {node})", self.arena[node].key));
+                    next = self.arena[node].next;
+                }
+                if keys.is_empty() {
+                    format!("{bucket} -")
+                } else {
+                    format!("{bucket} {}", keys.join(" -> "))
+                }
+            })
+            .collect()
+    }
+}
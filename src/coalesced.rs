@@ -0,0 +1,337 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, MetaMap, Metadata};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+// the fraction of buckets keys may hash to. the rest form the cellar, which is only
+// reached through collision chains.
+const ADDRESS_FACTOR: f64 = 0.86;
+
+#[derive(Clone, Debug)]
+enum BucketItem {
+    // a key and the next bucket of its chain.
+    Value(u64, Option<usize>),
+    Empty,
+    // a removed key. the bucket stays linked into its chain, and is reused by the next
+    // key inserted along it.
+    Deleted(Option<usize>),
+}
+
+impl BucketItem {
+    fn next(&self) -> Option<usize> {
+        match *self {
+            BucketItem::Value(_, next) | BucketItem::Deleted(next) => next,
+            BucketItem::Empty => None,
+        }
+    }
+}
+
+// the outcome of walking a key's chain.
+struct Search {
+    found: Option<usize>,
+    // the first deleted bucket on the chain, where the key would be inserted.
+    deleted: Option<usize>,
+    // the last bucket of the chain, or None if the home bucket is empty.
+    tail: Option<usize>,
+    probes: usize,
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements coalesced hashing: keys hash into the first ADDRESS_FACTOR of the
+// buckets, and colliding keys are linked into a chain through every bucket, taking
+// free buckets from the end of the table, cellar first. chains of different homes may
+// merge.
+//
+// every bucket read on a chain counts as a probe, as the link to the next bucket is
+// stored with the key. metadata can only tell that a home bucket is empty. when no
+// free bucket is left but keys have been removed, the table is rebuilt, with the
+// writes charged to the insert which triggers it.
+pub struct Coalesced {
+    hasher: CountingHasher,
+    buckets: BucketArray<BucketItem>,
+    meta: MetaMap,
+    len: usize,
+    address_len: usize,
+    // free buckets are searched for downwards from here.
+    free: usize,
+    deleted: usize,
+}
+
+impl Coalesced {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        Coalesced {
+            hasher: options.hasher(0),
+            buckets: bucket_array(capacity, BucketItem::Empty, options),
+            meta: MetaMap::new(capacity, meta_bits),
+            len: 0,
+            address_len: std::cmp::max(1, (capacity as f64 * ADDRESS_FACTOR) as usize),
+            free: capacity,
+            deleted: 0,
+        }
+    }
+
+    fn home(&self, hash: u64) -> usize {
+        (hash % self.address_len as u64) as usize
+    }
+
+    fn search(&self, key: u64) -> Search {
+        let hash = self.hasher.hash_one(key);
+        let home = self.home(hash);
+        explain!("home bucket {home}");
+        let mut search = Search {
+            found: None,
+            deleted: None,
+            tail: None,
+            probes: 0,
+        };
+
+        if self.meta.hint_empty(home) {
+            check_hint(|| matches!(self.buckets[home], BucketItem::Empty), home);
+            explain!("bucket {home}: metadata says empty, the key is absent");
+            return search;
+        }
+
+        let mut next = Some(home);
+        while let Some(bucket) = next {
+            search.probes += 1;
+            match self.buckets[bucket] {
+                BucketItem::Value(found_key, _) if found_key == key => {
+                    explain!("bucket {bucket}: read, holds the key");
+                    search.found = Some(bucket);
+                    return search;
+                }
+                BucketItem::Value(found_key, _) => {
+                    explain!("bucket {bucket}: read, holds {found_key}")
+                }
+                BucketItem::Deleted(_) => {
+                    explain!("bucket {bucket}: read, deleted");
+                    search.deleted = search.deleted.or(Some(bucket));
+                }
+                BucketItem::Empty => {
+                    assert_eq!(self.meta.bits(), 0);
+                    explain!("bucket {bucket}: read, empty, the key is absent");
+                    return search;
+                }
+            }
+            search.tail = Some(bucket);
+            next = self.buckets[bucket].next();
+        }
+
+        explain!("end of the chain, the key is absent");
+        search
+    }
+
+    // move the free pointer down to the next empty bucket, reading buckets whose
+    // metadata can't tell.
+    fn take_free(&mut self, update: &mut Update) -> Option<usize> {
+        while self.free > 0 {
+            self.free -= 1;
+            let bucket = self.free;
+            if self.meta.hint_empty(bucket) {
+                check_hint(|| matches!(self.buckets[bucket], BucketItem::Empty), bucket);
+                return Some(bucket);
+            }
+            if self.meta.bits() == 0 {
+                update.total_probes += 1;
+                if matches!(self.buckets[bucket], BucketItem::Empty) {
+                    return Some(bucket);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn set_bucket(&mut self, bucket: usize, item: BucketItem) {
+        match item {
+            BucketItem::Value(key, _) => {
+                let hash = self.hasher.hash_one(key);
+                self.meta.set_full(bucket, Metadata::Hash(hash));
+            }
+            BucketItem::Empty => self.meta.set_empty(bucket),
+            BucketItem::Deleted(_) => self.meta.set_tombstone(bucket),
+        }
+        self.buckets[bucket] = item;
+    }
+
+    // put an absent key into the table, given the search for it.
+    fn place(&mut self, key: u64, search: Search, update: &mut Update) -> bool {
+        if let Some(bucket) = search.deleted {
+            explain!("reuse deleted bucket {bucket}");
+            let next = self.buckets[bucket].next();
+            self.set_bucket(bucket, BucketItem::Value(key, next));
+            self.deleted -= 1;
+            return true;
+        }
+
+        let Some(tail) = search.tail else {
+            let home = self.home(self.hasher.hash_one(key));
+            explain!("place the key in its home bucket {home}");
+            self.set_bucket(home, BucketItem::Value(key, None));
+            return true;
+        };
+
+        let Some(bucket) = self.take_free(update) else {
+            return false;
+        };
+
+        explain!("place the key in free bucket {bucket}, linked from bucket {tail}");
+        self.set_bucket(bucket, BucketItem::Value(key, None));
+        let BucketItem::Value(tail_key, _) = self.buckets[tail] else {
+            unreachable!()
+        };
+        self.set_bucket(tail, BucketItem::Value(tail_key, Some(bucket)));
+        update.total_writes += 1;
+        true
+    }
+
+    // empty the table and insert every key again, dropping deleted buckets.
+    fn rebuild(&mut self, update: &mut Update) {
+        explain!("no free bucket left, rebuild");
+        let keys: Vec<u64> = self
+            .buckets
+            .iter()
+            .filter_map(|item| match *item {
+                BucketItem::Value(key, _) => Some(key),
+                _ => None,
+            })
+            .collect();
+
+        for bucket in 0..self.buckets.len() {
+            self.set_bucket(bucket, BucketItem::Empty);
+        }
+        self.free = self.buckets.len();
+        self.deleted = 0;
+
+        // the probes of the rebuild aren't charged, only its writes.
+        let mut rebuild_update = Update {
+            total_probes: 0,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+        for key in keys {
+            let search = self.search(key);
+            assert!(self.place(key, search, &mut rebuild_update));
+            update.total_writes += 1;
+            update.rebuild_moves += 1;
+        }
+        update.total_writes += rebuild_update.total_writes;
+        update.rebuilds += 1;
+    }
+}
+
+impl Map for Coalesced {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.home(self.hasher.hash_one(key))
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
+        }
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let search = self.search(key);
+        Probe {
+            contained: search.found.is_some(),
+            probes: search.probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let search = self.search(key);
+        let mut update = Update {
+            total_probes: search.probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if search.found.is_some() {
+            return update;
+        }
+
+        if !self.place(key, search, &mut update) {
+            if self.deleted == 0 {
+                explain!("no free bucket left, give up");
+                update.completed = false;
+                return update;
+            }
+
+            self.rebuild(&mut update);
+            let search = self.search(key);
+            if !self.place(key, search, &mut update) {
+                update.completed = false;
+                return update;
+            }
+        }
+
+        self.len += 1;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let meta = self.meta.describe(bucket);
+                let next = match self.buckets[bucket].next() {
+                    Some(next) => format!(" -> {next}"),
+                    None => String::new(),
+                };
+                match self.buckets[bucket] {
+                    BucketItem::Value(key, _) => format!("{bucket} {key}{next} meta={meta}"),
+                    BucketItem::Empty => format!("{bucket} - meta={meta}"),
+                    BucketItem::Deleted(_) => format!("{bucket} deleted{next} meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let search = self.search(key);
+        let mut update = Update {
+            total_probes: search.probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some(bucket) = search.found else {
+            return update;
+        };
+
+        explain!("mark bucket {bucket} deleted, keeping its link");
+        let next = self.buckets[bucket].next();
+        self.set_bucket(bucket, BucketItem::Deleted(next));
+        self.len -= 1;
+        self.deleted += 1;
+        update.total_writes += 1;
+        update
+    }
+}
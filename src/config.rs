@@ -0,0 +1,495 @@
+use crate::cost::{self, CostModel};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::str::FromStr;
+
+// the percentiles written per series, unless configured otherwise.
+pub const DEFAULT_PERCENTILES: [f64; 4] = [50.0, 95.0, 99.0, 99.9];
+
+// bytes per page of bucket arrays, unless configured otherwise.
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+
+// an experiment description, read from a TOML file with `--config`. every field is
+// optional and defaults to the built-in experiment, e.g.
+//
+// ```toml
+// maps = ["robinhood", "cuckoo"]
+// meta_bits = [0, 8]
+// size = 16
+// tests = ["probe", "churn"]
+//
+// [probe]
+// increment = 0.05
+// ops = 100000
+// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // map names and meta-bits settings to run. empty lists select everything.
+    pub maps: Vec<String>,
+    pub meta_bits: Vec<usize>,
+    // log2 of the number of buckets in every map.
+    pub size: u32,
+    // log2 sizes simulated one after another, in place of `size`. empty runs `size` alone.
+    pub sizes: Vec<u32>,
+    // seeds the hashers, keys and evictions of every map. random when unset.
+    pub seed: Option<u64>,
+    // how the keys inserted into every map are drawn.
+    pub keys: KeyStrategy,
+    // which live key every removal takes.
+    pub removals: RemovalOrder,
+    // how every test's records are written.
+    pub output: OutputFormat,
+    // bytes per page of bucket arrays, for counting the pages each write dirties.
+    pub page_size: usize,
+    // time every operation, as well as counting its probes.
+    pub timed: bool,
+    // write every histogram's full distribution beside the summaries.
+    pub hgrm: bool,
+    // log every operation of the grow, probe, churn and mixed tests.
+    pub log_ops: bool,
+    // back bucket arrays with transparent hugepages.
+    pub hugepages: bool,
+    // remember the hash of every key, rather than rehashing it.
+    pub hash_cache: bool,
+    // hash every map with the same seeded hashers.
+    pub shared_hashes: bool,
+    // record 1 in every this many operations into the histograms.
+    pub sample_rate: u64,
+    // raw values kept per series, for comparing runs and bootstrapping. none unless
+    // bootstrapping, when unset.
+    pub reservoir_size: Option<usize>,
+    // write bootstrap intervals of the mean and p99 of every series.
+    pub bootstrap: bool,
+    // how many times every measurement of the sweeping tests is repeated, with fresh
+    // hashers and keys each time.
+    pub trials: usize,
+    // the percentiles written for every series, between the mean and the max.
+    pub percentiles: Vec<f64>,
+    // the backends every operation is priced on, each adding a mean cost column to the
+    // grow, probe, churn and mixed results.
+    pub cost_models: Vec<CostModel>,
+    pub tests: Vec<Test>,
+    pub grow: Sweep,
+    pub probe: Sweep,
+    pub churn: Sweep,
+    pub bulk: Sweep,
+    pub analyze: Sweep,
+    pub failures: Trials,
+    pub mixed: Sweep,
+    pub drain: Sweep,
+    pub hot: Sweep,
+    // left out of the default tests, as its runs are long.
+    pub soak: Sweep,
+    pub batch: Sweep,
+    // cycles are long, so the sawtooth test only runs when asked for.
+    pub sawtooth: Sawtooth,
+    // the keys in every batch of the batch test.
+    pub batch_size: usize,
+    // which keys the hot test's operations go to.
+    pub hot_set: HotSet,
+    // the proportions of lookups, inserts and removes the mixed test makes.
+    pub mix: Mix,
+    // the fraction of the probe test's lookups which are for absent keys.
+    pub misses: f64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Test {
+    Grow,
+    Probe,
+    Churn,
+    Bulk,
+    Amortized,
+    Analyze,
+    Failures,
+    Mixed,
+    Drain,
+    Hot,
+    Soak,
+    Batch,
+    Sawtooth,
+}
+
+// a hot set of live keys taking most of a workload's operations, which moves on to
+// younger keys as the workload goes on.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotSet {
+    // the fraction of live keys in the hot set.
+    pub keys: f64,
+    // the fraction of operations going to the hot set, rather than to any live key.
+    pub share: f64,
+    // the fraction of operations which update their key, removing and reinserting it,
+    // rather than looking it up.
+    pub updates: f64,
+    // the operations made before the hot set moves on by a key.
+    pub shift: usize,
+}
+
+impl HotSet {
+    pub fn is_valid(&self) -> bool {
+        [self.keys, self.share, self.updates]
+            .iter()
+            .all(|fraction| (0.0..=1.0).contains(fraction))
+            && self.shift > 0
+    }
+}
+
+impl Default for HotSet {
+    fn default() -> Self {
+        HotSet {
+            keys: 0.01,
+            share: 0.9,
+            updates: 0.1,
+            shift: 10,
+        }
+    }
+}
+
+// relative proportions of the operations of a mixed workload, e.g. 90/5/5. lookups are
+// of present keys.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mix {
+    pub lookups: f64,
+    pub inserts: f64,
+    pub removes: f64,
+}
+
+impl Mix {
+    pub fn total(&self) -> f64 {
+        self.lookups + self.inserts + self.removes
+    }
+
+    pub fn is_valid(&self) -> bool {
+        [self.lookups, self.inserts, self.removes]
+            .iter()
+            .all(|&ratio| ratio >= 0.0)
+            && self.total() > 0.0
+    }
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Mix {
+            lookups: 90.0,
+            inserts: 5.0,
+            removes: 5.0,
+        }
+    }
+}
+
+// `lookups/inserts/removes`.
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ratios = s
+            .split('/')
+            .map(|ratio| ratio.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| format!("invalid mix {s}"))?;
+        let &[lookups, inserts, removes] = ratios.as_slice() else {
+            return Err(format!("expected lookups/inserts/removes in {s}"));
+        };
+
+        let mix = Mix {
+            lookups,
+            inserts,
+            removes,
+        };
+        if !mix.is_valid() {
+            return Err(format!("invalid mix {s}"));
+        }
+        Ok(mix)
+    }
+}
+
+// how the keys inserted into a map are drawn. absent keys are drawn the same way
+// under every strategy.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyStrategy {
+    // 0, 1, 2 and so on.
+    #[default]
+    Sequential,
+    // distinct keys spread uniformly over the key space.
+    RandomUnique,
+    // runs of consecutive keys, each starting at a random point in the key space.
+    Clustered,
+}
+
+// how records are written: as CSVs per output and map, merged once the run is done, or
+// as lines of a single JSON lines file, appended as they're taken.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+// which live key a removal takes. the order keys are removed in decides where tombstones
+// are left, and how far backward shifts have to move keys.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemovalOrder {
+    // the oldest live key.
+    #[default]
+    Fifo,
+    // the newest live key.
+    Lifo,
+    // any live key, uniformly.
+    Random,
+}
+
+// the load factors a test is run at, and how many operations it measures at each.
+// the grow test measures every insert from empty, so ignores `start` and `ops`. the
+// drain test fills to `max_load` and removes down to `start`, a step of `increment` at
+// a time. the bulk, analyze and drain tests ignore `ops`. only the churn and soak tests
+// split their operations into windows of `window`, each written as a record of its own,
+// or keep them whole at 0.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sweep {
+    pub start: f64,
+    pub increment: f64,
+    pub max_load: f64,
+    pub ops: usize,
+    pub window: usize,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Sweep {
+            start: 0.1,
+            increment: 0.02,
+            max_load: 0.98,
+            ops: 10_000,
+            window: 0,
+        }
+    }
+}
+
+// the bounds a map's load factor cycles between, growing to `high` and draining back to
+// `low`, and the number of cycles made.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sawtooth {
+    pub low: f64,
+    pub high: f64,
+    pub cycles: usize,
+}
+
+impl Sawtooth {
+    pub fn is_valid(&self) -> bool {
+        0.0 <= self.low && self.low < self.high && self.high <= 1.0
+    }
+}
+
+impl Default for Sawtooth {
+    fn default() -> Self {
+        Sawtooth {
+            low: 0.5,
+            high: 0.9,
+            cycles: 10,
+        }
+    }
+}
+
+// how many times a test is repeated, with fresh hashers and keys each time.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Trials {
+    pub trials: usize,
+}
+
+impl Default for Trials {
+    fn default() -> Self {
+        Trials { trials: 10 }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            maps: Vec::new(),
+            meta_bits: Vec::new(),
+            size: 20,
+            sizes: Vec::new(),
+            seed: None,
+            keys: KeyStrategy::Sequential,
+            removals: RemovalOrder::Fifo,
+            output: OutputFormat::Csv,
+            page_size: DEFAULT_PAGE_SIZE,
+            timed: false,
+            hgrm: false,
+            log_ops: false,
+            hugepages: false,
+            hash_cache: false,
+            shared_hashes: false,
+            sample_rate: 1,
+            reservoir_size: None,
+            bootstrap: false,
+            trials: 1,
+            percentiles: DEFAULT_PERCENTILES.to_vec(),
+            cost_models: cost::default_models(),
+            tests: vec![
+                Test::Grow,
+                Test::Probe,
+                Test::Churn,
+                Test::Bulk,
+                Test::Amortized,
+                Test::Analyze,
+                Test::Failures,
+                Test::Mixed,
+                Test::Drain,
+                Test::Hot,
+                Test::Batch,
+            ],
+            grow: Sweep {
+                increment: 0.01,
+                ..Sweep::default()
+            },
+            probe: Sweep::default(),
+            churn: Sweep::default(),
+            bulk: Sweep::default(),
+            analyze: Sweep::default(),
+            failures: Trials::default(),
+            mixed: Sweep::default(),
+            drain: Sweep::default(),
+            hot: Sweep::default(),
+            soak: Sweep {
+                start: 0.9,
+                max_load: 0.9,
+                ops: 5_000_000,
+                window: 100_000,
+                ..Sweep::default()
+            },
+            batch: Sweep::default(),
+            sawtooth: Sawtooth::default(),
+            batch_size: 64,
+            hot_set: HotSet::default(),
+            mix: Mix::default(),
+            misses: 0.5,
+        }
+    }
+}
+
+// errors name the line and column they're at.
+pub fn read(path: &str) -> Result<Config, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("can't read config {path}: {e}"))?;
+    parse(&text).map_err(|e| format!("{path}:{e}"))
+}
+
+fn parse(text: &str) -> Result<Config, String> {
+    toml::from_str(text).map_err(|e| {
+        let (line, column) = e.span().map_or((0, 0), |span| {
+            let before = &text[..span.start];
+            let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+            (
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        });
+        format!("{line}:{column}: {}", e.message().trim_end())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_name_their_line_and_column() {
+        let error = parse("size = 12\nmaps = [\"cuckoo\"]\nseed = \"one\"\n").unwrap_err();
+        assert!(error.starts_with("3:8: "), "{error}");
+
+        let error = parse("size = 12\n[probe]\n  ops = 1\n  bogus = 2\n").unwrap_err();
+        assert!(error.starts_with("4:3: "), "{error}");
+    }
+
+    #[test]
+    fn missing_fields_take_defaults() {
+        let config: Config =
+            toml::from_str("maps = [\"cuckoo\"]\ntests = [\"probe\"]\n[probe]\nincrement = 0.05\n")
+                .unwrap();
+
+        assert_eq!(config.maps, ["cuckoo"]);
+        assert_eq!(config.tests, [Test::Probe]);
+        assert_eq!(config.size, 20);
+        assert!(config.sizes.is_empty());
+        assert_eq!(config.keys, KeyStrategy::Sequential);
+        assert_eq!(config.removals, RemovalOrder::Fifo);
+        assert_eq!(config.output, OutputFormat::Csv);
+        assert_eq!(config.page_size, DEFAULT_PAGE_SIZE);
+        assert!(!config.timed);
+        assert_eq!(config.trials, 1);
+        assert_eq!(config.sample_rate, 1);
+        assert_eq!(config.percentiles, DEFAULT_PERCENTILES);
+        assert_eq!(config.cost_models, cost::default_models());
+        assert_eq!(config.probe.increment, 0.05);
+        assert_eq!(config.probe.ops, 10_000);
+        assert_eq!(config.grow.increment, 0.01);
+        assert_eq!(config.failures.trials, 10);
+        assert_eq!(config.mix, Mix::default());
+        assert_eq!(config.misses, 0.5);
+        assert!(config.hot_set.is_valid());
+        assert!(config.sawtooth.is_valid());
+        assert!(!Config::default().tests.contains(&Test::Soak));
+        assert!(!Config::default().tests.contains(&Test::Sawtooth));
+    }
+
+    #[test]
+    fn cost_models_replace_the_defaults() {
+        let config: Config = toml::from_str(
+            "[[cost_models]]\nname = \"hdd\"\nrandom_read = 8000.0\nsequential_read = 100.0\npage_write = 8000.0\nmeta_access = 0.005\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.cost_models,
+            ["hdd:8000,100,8000,0.005".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn key_strategies_are_kebab_case() {
+        let config: Config = toml::from_str("keys = \"random-unique\"\n").unwrap();
+        assert_eq!(config.keys, KeyStrategy::RandomUnique);
+    }
+
+    #[test]
+    fn mixes_parse_as_ratios() {
+        let mix: Mix = "80/15/5".parse().unwrap();
+        assert_eq!(mix.total(), 100.0);
+        assert_eq!(mix.inserts, 15.0);
+
+        assert!("80/20".parse::<Mix>().is_err());
+        assert!("80/-5/5".parse::<Mix>().is_err());
+        assert!("0/0/0".parse::<Mix>().is_err());
+    }
+
+    #[test]
+    fn run_time_options_are_read() {
+        let config: Config = toml::from_str(
+            "sample_rate = 10\nreservoir_size = 1000\nhugepages = true\nhash_cache = true\nshared_hashes = true\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.sample_rate, 10);
+        assert_eq!(config.reservoir_size, Some(1000));
+        assert!(config.hugepages && config.hash_cache && config.shared_hashes);
+
+        let config = Config::default();
+        assert!(!config.hugepages && !config.hash_cache && !config.shared_hashes);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(toml::from_str::<Config>("sizes = 16\n").is_err());
+    }
+}
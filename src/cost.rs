@@ -0,0 +1,95 @@
+use crate::meta_map::Accesses;
+use serde::Deserialize;
+use std::str::FromStr;
+
+// what each kind of access costs on some backend, in microseconds. every measured
+// operation is priced under each model, so that one run compares backends.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CostModel {
+    pub name: String,
+    // a page of buckets read away from the last one the operation read.
+    pub random_read: f64,
+    // a page of buckets read right after a neighbouring one.
+    pub sequential_read: f64,
+    pub page_write: f64,
+    pub meta_access: f64,
+}
+
+impl CostModel {
+    pub fn cost(&self, accesses: &Accesses) -> f64 {
+        accesses.random_page_reads as f64 * self.random_read
+            + accesses.sequential_page_reads as f64 * self.sequential_read
+            + accesses.dirty_pages as f64 * self.page_write
+            + accesses.meta_probes as f64 * self.meta_access
+    }
+}
+
+// rough figures for buckets in memory, on a SATA SSD and on an NVMe drive, with the
+// metamap in memory throughout.
+pub fn default_models() -> Vec<CostModel> {
+    let model = |name: &str, random_read, sequential_read, page_write, meta_access| CostModel {
+        name: name.to_string(),
+        random_read,
+        sequential_read,
+        page_write,
+        meta_access,
+    };
+
+    vec![
+        model("ram", 0.1, 0.02, 0.1, 0.005),
+        model("ssd", 100.0, 10.0, 200.0, 0.005),
+        model("nvme", 20.0, 4.0, 30.0, 0.005),
+    ]
+}
+
+// `name:random_read,sequential_read,page_write,meta_access`.
+impl FromStr for CostModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, costs) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected name:costs in {s}"))?;
+        let costs = costs
+            .split(',')
+            .map(|cost| match cost.parse::<f64>() {
+                Ok(cost) if cost >= 0.0 => Ok(cost),
+                _ => Err(format!("invalid cost {cost} in {s}")),
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        let &[random_read, sequential_read, page_write, meta_access] = costs.as_slice() else {
+            return Err(format!("expected 4 costs in {s}"));
+        };
+
+        Ok(CostModel {
+            name: name.to_string(),
+            random_read,
+            sequential_read,
+            page_write,
+            meta_access,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prices_accesses() {
+        let model: CostModel = "disk:100,10,200,0.5".parse().unwrap();
+        let accesses = Accesses {
+            random_page_reads: 1,
+            sequential_page_reads: 2,
+            dirty_pages: 1,
+            meta_probes: 4,
+            ..Accesses::default()
+        };
+        assert_eq!(model.cost(&accesses), 100.0 + 20.0 + 200.0 + 2.0);
+
+        assert!("disk".parse::<CostModel>().is_err());
+        assert!("disk:1,2,3".parse::<CostModel>().is_err());
+        assert!("disk:1,2,3,-4".parse::<CostModel>().is_err());
+    }
+}
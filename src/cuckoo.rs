@@ -1,48 +1,373 @@
-use crate::meta_map::{MetaMap, Metadata};
-use crate::{Map, Probe, Update};
-use ahash::RandomState;
+use crate::allocation::{bucket_array, BucketArray};
+use crate::candidates;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata};
+use crate::{EvictionRng, ExpectedProbes, Map, MapOptions, MemoryUsage, Probe, Update};
+use rand::prelude::*;
+use std::collections::HashSet;
+
+// picks which candidate bucket an insertion evicts from.
+//
+// the simulation always evicts at random; recording and scripting are for
+// reproducing eviction chains in regression tests.
+#[cfg_attr(not(test), allow(dead_code))]
+pub enum Evictions {
+    Random(EvictionRng),
+    // random, but remembering every choice made so the chain can be replayed.
+    Recorded(EvictionRng, Vec<usize>),
+    // replays a fixed sequence of choices, e.g. one recorded while observing a failure.
+    Scripted(std::vec::IntoIter<usize>),
+}
+
+impl Evictions {
+    // the index, into the key's `d` candidate buckets, of the bucket to evict from.
+    // `candidates` has bit `i` set if bucket `i` may be chosen.
+    fn choose(&mut self, candidates: u8, d: usize) -> usize {
+        let random = |rng: &mut EvictionRng| loop {
+            let evict = rng.gen_range(0..d);
+            if candidates & (1 << evict) != 0 {
+                break evict;
+            }
+        };
+
+        match self {
+            Evictions::Random(rng) => random(rng),
+            Evictions::Recorded(rng, choices) => {
+                let evict = random(rng);
+                choices.push(evict);
+                evict
+            }
+            Evictions::Scripted(choices) => {
+                let evict = choices.next().expect("eviction script exhausted");
+                assert!(
+                    candidates & (1 << evict) != 0,
+                    "scripted eviction {evict} is not a candidate"
+                );
+                evict
+            }
+        }
+    }
+}
 
-const HASHER_COUNT: usize = 5;
+// the most hash functions a map may use, as candidates are tracked in a u8.
+pub const MAX_D: usize = 8;
+
+// how an insertion into a full set of candidates makes room.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Insertion {
+    // evict from a random candidate, then re-insert the evicted key the same way.
+    RandomWalk,
+    // search breadth-first for the shortest chain of moves which ends in an empty
+    // bucket, then make the moves. nothing moves unless a chain is found.
+    Bfs,
+}
+
+// the bit a map may keep per bucket beside the meta bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtraBit {
+    None,
+    Alternate,
+    Aging,
+}
 
 // dummy hash-set for u64 keys.
 //
-// implements cuckoo hashing.
-pub struct Cuckoo {
-    hashers: Vec<RandomState>,
-    buckets: Vec<Option<u64>>,
+// implements d-ary cuckoo hashing. the table is split into `d` parts, and every key
+// has one candidate bucket in each.
+//
+// with an alternate bit, a key's primary bucket (its candidate in the first part) is
+// marked once the key is placed in any other candidate. lookups check the primary
+// bucket first, and only go on to the others if it is marked. where a key lives
+// follows from the part it's in, so the bit is kept on the bucket it was displaced
+// from rather than with its own metadata.
+//
+// with an aging bit, lookups which find a key mark it referenced, and evictions choose
+// among the candidates holding unreferenced keys where there are any, so that keys
+// looked up often stay where they are. the bits are cleared periodically. keys further
+// along an eviction chain can't go back where they came from, so with 2 hash functions
+// only the first eviction of a chain has a choice to make.
+pub struct DAryCuckoo {
+    d: usize,
+    hashers: Vec<CountingHasher>,
+    buckets: BucketArray<Option<u64>>,
     meta: MetaMap,
     len: usize,
+    insertion: Insertion,
+    evictions: Evictions,
 }
 
-impl Cuckoo {
-    pub fn new(capacity: usize, meta_bits: usize) -> Self {
-        Cuckoo {
-            hashers: (0..HASHER_COUNT).map(|_| RandomState::new()).collect(),
-            buckets: vec![None; capacity],
-            meta: MetaMap::new(capacity, meta_bits),
+impl DAryCuckoo {
+    pub fn new(
+        capacity: usize,
+        d: usize,
+        meta_bits: usize,
+        extra_bit: ExtraBit,
+        options: MapOptions,
+        insertion: Insertion,
+        evictions: Evictions,
+    ) -> Self {
+        assert!((2..=MAX_D).contains(&d) && capacity >= d);
+        DAryCuckoo {
+            d,
+            hashers: (0..d).map(|i| options.hasher(i)).collect(),
+            buckets: bucket_array(capacity, None, options),
+            meta: match extra_bit {
+                ExtraBit::None => MetaMap::new(capacity, meta_bits),
+                ExtraBit::Alternate => MetaMap::with_alternates(capacity, meta_bits),
+                ExtraBit::Aging => MetaMap::with_aging(capacity, meta_bits),
+            },
             len: 0,
+            insertion,
+            evictions,
         }
     }
 
-    fn buckets(&self, key: u64) -> (u64, usize, usize) {
+    // (hash, candidate buckets). only the first `d` buckets are used.
+    fn buckets(&self, key: u64) -> (u64, [usize; MAX_D]) {
         let hash_a = self.hashers[0].hash_one(key);
-        let bucket_a = (hash_a % self.buckets.len() as u64) as usize;
-        let mut bucket_b = bucket_a;
 
-        let mut cur_hasher = 0;
+        // every part is the same length, except the last, which takes the remainder.
+        let part_len = self.buckets.len() / self.d;
+        let mut buckets = [0; MAX_D];
+        for (i, bucket) in buckets.iter_mut().enumerate().take(self.d) {
+            let len = if i + 1 == self.d {
+                self.buckets.len() - part_len * i
+            } else {
+                part_len
+            };
+            let hash = if i == 0 {
+                hash_a
+            } else {
+                self.hashers[i].hash_one(key)
+            };
+            *bucket = (hash % len as u64) as usize + part_len * i;
+        }
+
+        (hash_a, buckets)
+    }
+
+    // search the key's candidate buckets, skipping all but the primary one when its
+    // alternate bit is clear.
+    fn search(&self, key: u64, key_info: (u64, [usize; MAX_D])) -> (Option<usize>, usize) {
+        let (hash, buckets) = key_info;
+        let buckets = &buckets[..self.d];
+        explain!("candidate buckets {buckets:?}");
+        let (found, probes) = candidates::find(&self.meta, &self.buckets, &buckets[..1], key, hash);
+        if found.is_some() {
+            return (found, probes);
+        }
+
+        if self.meta.hint_primary_only(buckets[0]) {
+            check_hint(
+                || buckets[1..].iter().all(|&b| self.buckets[b] != Some(key)),
+                buckets[0],
+            );
+            explain!(
+                "bucket {}: no key was displaced from it, the key is absent",
+                buckets[0]
+            );
+            return (None, probes);
+        }
+
+        let (found, alternate_probes) =
+            candidates::find(&self.meta, &self.buckets, &buckets[1..], key, hash);
+        (found, probes + alternate_probes)
+    }
+
+    fn random_walk(
+        &mut self,
+        key: u64,
+        mut key_info: (u64, [usize; MAX_D]),
+        mut update: Update,
+    ) -> Update {
+        const MAX_CHAIN: usize = 128;
+
+        let mut active_key = key;
+        // whether the active key carries a referenced bit. the new key doesn't.
+        let mut active_referenced = false;
+
+        // bit `i` is set if `buckets[i]` is a candidate for the active key.
+        let all_candidates: u8 = ((1u16 << self.d) - 1) as u8;
+        let mut candidates = all_candidates;
+
+        // all targets full. evict randomly.
+        for _ in 0..MAX_CHAIN {
+            let (hash, buckets) = key_info;
+
+            // if there is an empty bucket, use that.
+            for (i, &bucket_index) in buckets[..self.d].iter().enumerate() {
+                if candidates & (1 << i) == 0 {
+                    continue;
+                }
+
+                if self.meta.hint_empty(bucket_index) {
+                    check_hint(|| self.buckets[bucket_index].is_none(), bucket_index);
+                    explain!("bucket {bucket_index}: metadata says empty, place {active_key}");
+                    if active_key != key {
+                        update.total_writes += 1;
+                    }
+                    self.set_bucket(bucket_index, active_key, hash);
+                    self.meta.set_referenced(bucket_index, active_referenced);
+                    return update;
+                } else if self.meta.bits() == 0 {
+                    update.total_probes += 1;
+                    if self.buckets[bucket_index].is_none() {
+                        explain!("bucket {bucket_index}: read, empty, place {active_key}");
+                        if active_key != key {
+                            update.total_writes += 1;
+                        }
+                        self.set_bucket(bucket_index, active_key, hash);
+                        self.meta.set_referenced(bucket_index, active_referenced);
+                        return update;
+                    }
+                }
+            }
+
+            // no bucket is empty. choose one at random, cold ones first.
+            let cold = self.cold_candidates(candidates, &buckets[..self.d]);
+            let evict_bucket = buckets[self.evictions.choose(cold, self.d)];
+
+            // in this case we've already probed every candidate and don't double count
+            if self.meta.bits() > 0 {
+                update.total_probes += 1;
+            }
+
+            let swap_key = self.buckets[evict_bucket].unwrap();
+            explain!("no candidate is empty, evict {swap_key} from bucket {evict_bucket} and place {active_key}");
+            update.total_writes += 1;
+            update.evictions += 1;
+            let swap_referenced = self.meta.referenced(evict_bucket);
+            self.set_bucket(evict_bucket, active_key, hash);
+            self.meta.set_referenced(evict_bucket, active_referenced);
+            active_referenced = swap_referenced;
+
+            key_info = self.buckets(swap_key);
 
-        while bucket_b == bucket_a {
-            cur_hasher += 1;
+            // the swapped key may go anywhere but the bucket it was evicted from.
+            let evicted_from = key_info.1[..self.d]
+                .iter()
+                .position(|&bucket| bucket == evict_bucket)
+                .unwrap();
+            candidates = all_candidates & !(1 << evicted_from);
 
-            bucket_b = (self.hashers[cur_hasher].hash_one(key) % self.buckets.len() as u64) as usize
+            active_key = swap_key;
         }
 
-        (hash_a, bucket_a, bucket_b)
+        explain!("eviction chain too long, give up");
+        update.completed = false;
+        update
     }
 
+    // the candidates whose keys aren't referenced, or all of them if every key is.
+    fn cold_candidates(&self, candidates: u8, buckets: &[usize]) -> u8 {
+        let cold = buckets
+            .iter()
+            .enumerate()
+            .filter(|&(_, &bucket)| !self.meta.referenced(bucket))
+            .fold(0, |cold, (i, _)| cold | 1 << i)
+            & candidates;
+        if cold == 0 {
+            candidates
+        } else {
+            cold
+        }
+    }
+
+    // whether `bucket` is empty, reading it if its metadata can't tell.
+    fn is_empty(&self, bucket: usize, update: &mut Update) -> bool {
+        if self.meta.hint_empty(bucket) {
+            check_hint(|| self.buckets[bucket].is_none(), bucket);
+            return true;
+        }
+        if self.meta.bits() == 0 {
+            update.total_probes += 1;
+            return self.buckets[bucket].is_none();
+        }
+        false
+    }
+
+    fn bfs(&mut self, key: u64, key_info: (u64, [usize; MAX_D]), mut update: Update) -> Update {
+        const MAX_EXAMINED: usize = 512;
+
+        let (hash, buckets) = key_info;
+        for &bucket in &buckets[..self.d] {
+            if self.is_empty(bucket, &mut update) {
+                explain!("bucket {bucket}: empty, place {key}");
+                self.set_bucket(bucket, key, hash);
+                return update;
+            }
+        }
+
+        // every bucket examined, along with the index of the one whose key would move
+        // into it. the key's own candidates have none.
+        let mut nodes: Vec<(usize, Option<usize>)> =
+            buckets[..self.d].iter().map(|&b| (b, None)).collect();
+        let mut visited: HashSet<usize> = nodes.iter().map(|&(b, _)| b).collect();
+
+        let mut next = 0;
+        while next < nodes.len() && nodes.len() < MAX_EXAMINED {
+            let (bucket, _) = nodes[next];
+
+            // without metadata, the bucket was read when checked for emptiness.
+            if self.meta.bits() > 0 {
+                update.total_probes += 1;
+            }
+            let occupant = self.buckets[bucket].unwrap();
+            let (_, alternatives) = self.buckets(occupant);
+
+            for &alternative in &alternatives[..self.d] {
+                if !visited.insert(alternative) {
+                    continue;
+                }
+
+                if !self.is_empty(alternative, &mut update) {
+                    nodes.push((alternative, Some(next)));
+                    continue;
+                }
+
+                // walk the chain back from the empty bucket, moving every key one step
+                // along it, then place the key at its start.
+                let mut to = alternative;
+                let mut from = Some(next);
+                while let Some(node) = from {
+                    let (bucket, parent) = nodes[node];
+                    let moved = self.buckets[bucket].unwrap();
+                    explain!("move {moved} from bucket {bucket} to bucket {to}");
+                    let moved_hash = self.buckets(moved).0;
+                    let referenced = self.meta.referenced(bucket);
+                    self.set_bucket(to, moved, moved_hash);
+                    self.meta.set_referenced(to, referenced);
+                    update.total_writes += 1;
+                    update.evictions += 1;
+                    to = bucket;
+                    from = parent;
+                }
+
+                explain!("place {key} in bucket {to}");
+                self.set_bucket(to, key, hash);
+                return update;
+            }
+
+            next += 1;
+        }
+
+        explain!("no chain to an empty bucket within {MAX_EXAMINED} buckets, give up");
+        self.len -= 1;
+        update.completed = false;
+        update
+    }
+
+    // the key starts out unreferenced. callers moving a key carry its bit over.
     fn set_bucket(&mut self, bucket: usize, key: u64, hash: u64) {
         self.buckets[bucket] = Some(key);
         self.meta.set_full(bucket, Metadata::Hash(hash));
+        self.meta.set_referenced(bucket, false);
+
+        // the first hash picks the bucket in the first part, as in `buckets`.
+        let primary = (hash % (self.buckets.len() / self.d) as u64) as usize;
+        if bucket != primary {
+            self.meta.set_alternate(primary);
+        }
     }
 
     fn clear_bucket(&mut self, bucket: usize) {
@@ -51,7 +376,7 @@ impl Cuckoo {
     }
 }
 
-impl Map for Cuckoo {
+impl Map for DAryCuckoo {
     fn len(&self) -> usize {
         self.len
     }
@@ -60,137 +385,295 @@ impl Map for Cuckoo {
         self.buckets.len()
     }
 
-    fn probe(&self, key: u64) -> Probe {
-        let (hash, bucket_a, bucket_b) = self.buckets(key);
+    fn home_bucket(&self, key: u64) -> usize {
+        self.buckets(key).1[0]
+    }
 
-        let mut probes = 0;
+    fn displacements(&self) -> Option<Vec<usize>> {
+        Some(
+            (0..self.buckets.len())
+                .filter_map(|bucket| {
+                    let candidates = self.buckets(self.buckets[bucket]?).1;
+                    candidates[..self.d].iter().position(|&b| b == bucket)
+                })
+                .collect(),
+        )
+    }
 
-        if !self.meta.hint_not_match(bucket_a, hash) {
-            probes += 1;
-            if self.buckets[bucket_a] == Some(key) {
-                return Probe {
-                    contained: true,
-                    probes,
-                };
-            }
+    fn hash_stats(&self) -> HashStats {
+        self.hashers.iter().map(|h| h.stats()).sum()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
         }
+    }
 
-        if !self.meta.hint_not_match(bucket_b, hash) {
-            probes += 1;
-            if self.buckets[bucket_b] == Some(key) {
-                return Probe {
-                    contained: true,
-                    probes,
-                };
-            }
+    fn hint_stats(&self) -> HintStats {
+        self.meta.hint_stats()
+    }
+
+    // a miss reads every candidate bucket without metadata. with it, a candidate is
+    // only read when full and its fingerprint collides. alternate bits skip candidates
+    // in a way uniform hashing doesn't predict.
+    fn expected_probes(&self, load_factor: f64) -> ExpectedProbes {
+        let absent = match self.meta.bits() {
+            _ if self.meta.extra_bits() > 0 => None,
+            0 => Some(self.d as f64),
+            _ => Some(self.d as f64 * load_factor * self.meta.expected_false_positive_rate()),
+        };
+        ExpectedProbes {
+            present: None,
+            absent,
+        }
+    }
+
+    fn extra_meta_bits(&self) -> usize {
+        self.meta.extra_bits()
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, probes) = self.search(key, self.buckets(key));
+        if let Some(bucket) = found {
+            self.meta.reference(bucket);
         }
 
         Probe {
-            contained: false,
+            contained: found.is_some(),
             probes,
         }
     }
 
     fn insert(&mut self, key: u64) -> Update {
-        const MAX_CHAIN: usize = 128;
-
         let mut update = Update {
             total_probes: 0,
             total_writes: 1,
             completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
         };
 
-        let mut active_key = key;
-        let mut use_bucket_a = true;
-        let mut key_info = self.buckets(key);
+        let key_info = self.buckets(key);
 
         // test for presence.
-        {
-            let (hash, _, bucket_b) = key_info;
-            if !self.meta.hint_not_match(bucket_b, hash) {
-                update.total_probes += 1;
-                if self.buckets[bucket_b] == Some(key) {
-                    return update;
-                }
-            }
+        let (found, probes) = self.search(key, key_info);
+        update.total_probes += probes;
+        if found.is_some() {
+            return update;
         }
 
         self.len += 1;
+        match self.insertion {
+            Insertion::RandomWalk => self.random_walk(key, key_info, update),
+            Insertion::Bfs => self.bfs(key, key_info, update),
+        }
+    }
 
-        for _ in 0..MAX_CHAIN {
-            let (hash, bucket_a, bucket_b) = key_info;
-            let target_bucket = if use_bucket_a { bucket_a } else { bucket_b };
-
-            if self.meta.hint_empty(target_bucket) {
-                if active_key != key {
-                    update.total_writes += 1;
-                }
-                self.set_bucket(target_bucket, active_key, hash);
-                return update;
-            }
-
-            update.total_probes += 1;
-            let swap_key = match self.buckets[target_bucket] {
-                None => {
-                    if active_key != key {
-                        update.total_writes += 1;
-                    }
-
-                    self.set_bucket(target_bucket, active_key, hash);
-                    return update;
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let meta = self.meta.describe(bucket);
+                match self.buckets[bucket] {
+                    Some(key) => format!("{bucket} {key} meta={meta}"),
+                    None => format!("{bucket} - meta={meta}"),
                 }
-                Some(k) => {
-                    if k == active_key {
-                        assert_eq!(active_key, key);
-                        // this can only happen for the initial key because bucket_a != bucket_b
-                        self.len -= 1;
-                        return update;
-                    }
+            })
+            .collect()
+    }
 
-                    update.total_writes += 1;
-                    self.set_bucket(target_bucket, active_key, hash);
-                    k
-                }
-            };
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key, self.buckets(key));
 
-            key_info = self.buckets(swap_key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
 
-            // if this is our next key's "bucket b", use it's bucket a.
-            use_bucket_a = key_info.2 == target_bucket;
-            active_key = swap_key;
+        if let Some(bucket) = found {
+            explain!("clear bucket {bucket}");
+            self.clear_bucket(bucket);
+            self.len -= 1;
+            update.total_writes += 1;
         }
 
-        update.completed = false;
         update
     }
+}
 
-    fn remove(&mut self, key: u64) -> Update {
-        let (hash, bucket_a, bucket_b) = self.buckets(key);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut update = Update {
-            total_probes: 0,
-            total_writes: 0,
-            completed: true,
-        };
+    const CAPACITY: usize = 96;
 
-        if !self.meta.hint_not_match(bucket_a, hash) {
-            update.total_probes += 1;
-            if self.buckets[bucket_a] == Some(key) {
-                self.clear_bucket(bucket_a);
-                update.total_writes += 1;
-                return update;
+    // a map with the same hashers, which replays the given eviction choices.
+    fn replay(map: &DAryCuckoo, choices: Vec<usize>) -> DAryCuckoo {
+        DAryCuckoo {
+            d: map.d,
+            insertion: map.insertion,
+            hashers: map.hashers.clone(),
+            buckets: bucket_array(CAPACITY, None, MapOptions::seeded(1)),
+            meta: MetaMap::new(CAPACITY, map.meta.bits()),
+            len: 0,
+            evictions: Evictions::Scripted(choices.into_iter()),
+        }
+    }
+
+    // fill past capacity, so that insertions are sure to fail, then replay the whole run
+    // and check that every update and the final layout are reproduced exactly.
+    #[test]
+    fn recorded_chains_replay_exactly() {
+        for (d, meta_bits) in [(2, 0), (3, 0), (3, 4), (6, 4)] {
+            let rng = EvictionRng::seed_from_u64(1);
+            let mut recorded = DAryCuckoo::new(
+                CAPACITY,
+                d,
+                meta_bits,
+                ExtraBit::None,
+                MapOptions::seeded(1),
+                Insertion::RandomWalk,
+                Evictions::Recorded(rng, Vec::new()),
+            );
+            let updates: Vec<_> = (0..2 * CAPACITY as u64)
+                .map(|key| recorded.insert(key))
+                .collect();
+            assert!(updates.iter().any(|update| !update.completed));
+
+            let Evictions::Recorded(_, choices) = &recorded.evictions else {
+                unreachable!()
+            };
+            let mut replayed = replay(&recorded, choices.clone());
+            for (key, update) in (0..2 * CAPACITY as u64).zip(&updates) {
+                let replayed_update = replayed.insert(key);
+                assert_eq!(replayed_update.completed, update.completed);
+                assert_eq!(replayed_update.total_probes, update.total_probes);
+                assert_eq!(replayed_update.total_writes, update.total_writes);
+                assert_eq!(replayed_update.evictions, update.evictions);
             }
+            assert_eq!(replayed.buckets[..], recorded.buckets[..]);
         }
+    }
 
-        if !self.meta.hint_not_match(bucket_b, hash) {
-            update.total_probes += 1;
-            if self.buckets[bucket_b] == Some(key) {
-                self.clear_bucket(bucket_b);
-                update.total_writes += 1;
-                return update;
+    // breadth-first insertion only moves keys once it has found a chain, so a failed
+    // insertion leaves the map as it was.
+    #[test]
+    fn bfs_failures_move_nothing() {
+        for (d, meta_bits) in [(2, 0), (3, 4)] {
+            let mut map = DAryCuckoo::new(
+                CAPACITY,
+                d,
+                meta_bits,
+                ExtraBit::None,
+                MapOptions::seeded(1),
+                Insertion::Bfs,
+                Evictions::Random(EvictionRng::seed_from_u64(1)),
+            );
+            let mut inserted = Vec::new();
+            for key in 0..2 * CAPACITY as u64 {
+                let before = map.buckets.to_vec();
+                let update = map.insert(key);
+                if update.completed {
+                    inserted.push(key);
+                } else {
+                    assert_eq!(update.evictions, 0);
+                    assert_eq!(map.buckets[..], before);
+                }
             }
+
+            assert_eq!(map.len(), inserted.len());
+            assert!(inserted.iter().all(|&key| map.probe(key).contained));
         }
+    }
 
-        update
+    // the alternate bit doesn't change where keys go, and only ever saves probes.
+    #[test]
+    fn alternate_bit_saves_probes() {
+        let options = MapOptions::seeded(7);
+        let build = |extra_bit| {
+            DAryCuckoo::new(
+                CAPACITY,
+                2,
+                4,
+                extra_bit,
+                options,
+                Insertion::RandomWalk,
+                Evictions::Random(options.hashes.eviction_rng()),
+            )
+        };
+        let (mut plain, mut alternate) = (build(ExtraBit::None), build(ExtraBit::Alternate));
+        for key in 0..CAPACITY as u64 / 2 {
+            plain.insert(key);
+            alternate.insert(key);
+        }
+        assert_eq!(plain.buckets[..], alternate.buckets[..]);
+
+        let absent_probes = |map: &DAryCuckoo| -> usize {
+            (CAPACITY as u64..2 * CAPACITY as u64)
+                .map(|key| map.probe(key).probes)
+                .sum()
+        };
+        assert!(absent_probes(&alternate) <= absent_probes(&plain));
+        for key in 0..CAPACITY as u64 / 2 {
+            assert!(alternate.probe(key).contained);
+        }
+    }
+
+    // keys looked up lately are evicted less often than they would be at random. after
+    // a chain's first eviction a 2-ary key has a single candidate left, so this takes
+    // more hash functions to show.
+    #[test]
+    fn referenced_keys_move_less() {
+        let hot_moves = |extra_bit, seed| {
+            let options = MapOptions::seeded(seed);
+            let mut map = DAryCuckoo::new(
+                CAPACITY,
+                3,
+                4,
+                extra_bit,
+                options,
+                Insertion::RandomWalk,
+                Evictions::Random(options.hashes.eviction_rng()),
+            );
+            for key in 0..CAPACITY as u64 / 4 {
+                map.insert(key);
+            }
+            let hot = 0..CAPACITY as u64 / 16;
+            for key in hot.clone() {
+                assert!(map.probe(key).contained);
+            }
+
+            let at = |map: &DAryCuckoo, key| map.buckets.iter().position(|&b| b == Some(key));
+            let before: Vec<_> = hot.clone().map(|key| at(&map, key)).collect();
+            for key in CAPACITY as u64 / 4..CAPACITY as u64 * 7 / 8 {
+                map.insert(key);
+            }
+            hot.zip(before)
+                .filter(|&(key, bucket)| at(&map, key) != bucket)
+                .count()
+        };
+        let moves = |extra_bit| {
+            (0..16)
+                .map(|seed| hot_moves(extra_bit, seed))
+                .sum::<usize>()
+        };
+        let (plain, aging) = (moves(ExtraBit::None), moves(ExtraBit::Aging));
+        assert!(aging < plain, "aging moved {aging} hot keys, plain {plain}");
+    }
+
+    #[test]
+    #[should_panic(expected = "not a candidate")]
+    fn scripted_choices_must_be_candidates() {
+        let mut evictions = Evictions::Scripted(vec![0].into_iter());
+        evictions.choose(0b110, 3);
     }
 }
@@ -0,0 +1,249 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::{EvictionRng, Map, MapOptions, MemoryUsage, Probe, Update};
+use rand::prelude::*;
+
+pub const SLOTS_PER_BUCKET: usize = 4;
+
+// the most fingerprint bits, as fingerprints are stored in a u16.
+pub const MAX_FINGERPRINT_BITS: usize = 16;
+
+// mixes a fingerprint into the offset between a key's two buckets. this is a fixed
+// function of the stored bits, so it isn't counted as a hash.
+const FINGERPRINT_MIX: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// dummy approximate set for u64 keys.
+//
+// implements a cuckoo filter: every slot holds only a fingerprint of `fingerprint_bits`
+// bits, never 0, which marks an empty slot. a key's first bucket comes from its hash and
+// its second from the first and its fingerprint, so that a fingerprint can be evicted to
+// its other bucket without knowing the key. a probe for an absent key reports it
+// contained whenever a slot of either bucket holds the same fingerprint.
+//
+// like a real filter, inserts don't search for the key first, so the same key may be
+// inserted twice, and only keys which were inserted may be removed. every slot read
+// counts as one probe.
+pub struct CuckooFilter {
+    hasher: CountingHasher,
+    slots: BucketArray<u16>,
+    fingerprint_bits: usize,
+    len: usize,
+    rng: EvictionRng,
+}
+
+impl CuckooFilter {
+    pub fn new(capacity: usize, fingerprint_bits: usize, options: MapOptions) -> Self {
+        assert!((1..=MAX_FINGERPRINT_BITS).contains(&fingerprint_bits));
+        let capacity = std::cmp::max(2, capacity / SLOTS_PER_BUCKET) * SLOTS_PER_BUCKET;
+        CuckooFilter {
+            hasher: options.hasher(0),
+            slots: bucket_array(capacity, 0, options),
+            fingerprint_bits,
+            len: 0,
+            rng: options.hashes.eviction_rng(),
+        }
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.slots.len() / SLOTS_PER_BUCKET
+    }
+
+    // (fingerprint, first bucket)
+    fn fingerprint(&self, key: u64) -> (u16, usize) {
+        let hash = self.hasher.hash_one(key);
+        let values = (1u64 << self.fingerprint_bits) - 1;
+        let fingerprint = ((hash >> 32) % values + 1) as u16;
+        let bucket = (hash % self.bucket_count() as u64) as usize;
+        (fingerprint, bucket)
+    }
+
+    // the other bucket of a fingerprint in `bucket`. applying it twice gives back
+    // `bucket`.
+    fn alternate(&self, bucket: usize, fingerprint: u16) -> usize {
+        let buckets = self.bucket_count() as u64;
+        let offset = (fingerprint as u64).wrapping_mul(FINGERPRINT_MIX) % buckets;
+        ((offset + buckets - bucket as u64) % buckets) as usize
+    }
+
+    fn bucket_slots(bucket: usize) -> std::ops::Range<usize> {
+        bucket * SLOTS_PER_BUCKET..(bucket + 1) * SLOTS_PER_BUCKET
+    }
+
+    // the first slot of either bucket holding the fingerprint, along with the number of
+    // probes made.
+    fn search(&self, key: u64) -> (Option<usize>, usize) {
+        let (fingerprint, bucket) = self.fingerprint(key);
+        let buckets = [bucket, self.alternate(bucket, fingerprint)];
+        explain!("fingerprint {fingerprint}, candidate buckets {buckets:?}");
+        let mut probes = 0;
+        for bucket in buckets {
+            for slot in Self::bucket_slots(bucket) {
+                probes += 1;
+                if self.slots[slot] == fingerprint {
+                    explain!("slot {slot}: read, holds the fingerprint");
+                    return (Some(slot), probes);
+                }
+            }
+        }
+
+        explain!("no slot holds the fingerprint, the key is absent");
+        (None, probes)
+    }
+}
+
+impl Map for CuckooFilter {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn approximate(&self) -> bool {
+        true
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.fingerprint(key).1 * SLOTS_PER_BUCKET
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.slots[..]),
+            metadata: 0,
+            auxiliary: 0,
+        }
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, probes) = self.search(key);
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        const MAX_CHAIN: usize = 128;
+
+        let mut update = Update {
+            total_probes: 0,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let (mut fingerprint, first) = self.fingerprint(key);
+        let mut buckets = vec![first, self.alternate(first, fingerprint)];
+        explain!("fingerprint {fingerprint}, candidate buckets {buckets:?}");
+        self.len += 1;
+
+        for _ in 0..MAX_CHAIN {
+            for &bucket in &buckets {
+                for slot in Self::bucket_slots(bucket) {
+                    update.total_probes += 1;
+                    if self.slots[slot] == 0 {
+                        explain!("slot {slot}: read, empty, place fingerprint {fingerprint}");
+                        self.slots[slot] = fingerprint;
+                        return update;
+                    }
+                }
+            }
+
+            // every slot is full. evict a random slot of a random candidate bucket, and
+            // move its fingerprint to its other bucket.
+            let evict_bucket = buckets[self.rng.gen_range(0..buckets.len())];
+            let evict_slot =
+                evict_bucket * SLOTS_PER_BUCKET + self.rng.gen_range(0..SLOTS_PER_BUCKET);
+            let evicted = self.slots[evict_slot];
+            explain!(
+                "no candidate slot is empty, evict fingerprint {evicted} from slot {evict_slot} and place {fingerprint}"
+            );
+            self.slots[evict_slot] = fingerprint;
+            update.total_writes += 1;
+            update.evictions += 1;
+
+            fingerprint = evicted;
+            buckets = vec![self.alternate(evict_bucket, evicted)];
+        }
+
+        explain!("eviction chain too long, give up");
+        update.completed = false;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.slots.len())
+            .map(|slot| match self.slots[slot] {
+                0 => format!("{slot} -"),
+                fingerprint => format!("{slot} fingerprint={fingerprint}"),
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some(slot) = found else {
+            return update;
+        };
+
+        explain!("clear slot {slot}");
+        self.slots[slot] = 0;
+        self.len -= 1;
+        update.total_writes += 1;
+        update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternate_buckets_pair_up() {
+        let filter = CuckooFilter::new(1000, 8, MapOptions::seeded(0));
+        for bucket in 0..filter.bucket_count() {
+            for fingerprint in 1..=255 {
+                let other = filter.alternate(bucket, fingerprint);
+                assert_eq!(filter.alternate(other, fingerprint), bucket);
+            }
+        }
+    }
+
+    #[test]
+    fn no_false_negatives() {
+        let mut filter = CuckooFilter::new(1 << 12, 8, MapOptions::seeded(0));
+        let keys: Vec<u64> = (0..3500).collect();
+        for &key in &keys {
+            assert!(filter.insert(key).completed);
+        }
+        for &key in keys.iter().step_by(2) {
+            filter.remove(key);
+        }
+
+        assert!(keys
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .all(|&key| filter.probe(key).contained));
+        assert_eq!(filter.len(), keys.len() / 2);
+    }
+}
@@ -0,0 +1,21 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// narrate one step of an operation, for `--explain`. does nothing unless
+// explaining is switched on.
+macro_rules! explain {
+    ($($arg:tt)*) => {
+        if $crate::explain::enabled() {
+            println!("  {}", format!($($arg)*));
+        }
+    };
+}
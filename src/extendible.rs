@@ -0,0 +1,294 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::candidates;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+pub const SLOTS_PER_BUCKET: usize = 8;
+
+// splitting stops here, so that a run of keys with equal hashes can't grow the
+// directory without bound.
+const MAX_DEPTH: u32 = 40;
+
+const SLOTS: [usize; SLOTS_PER_BUCKET] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+struct Bucket {
+    // the number of low hash bits shared by every key in the bucket.
+    depth: u32,
+    slots: BucketArray<Option<u64>>,
+    meta: MetaMap,
+}
+
+impl Bucket {
+    fn new(depth: u32, meta_bits: usize, options: MapOptions) -> Self {
+        Bucket {
+            depth,
+            slots: bucket_array(SLOTS_PER_BUCKET, None, options),
+            meta: MetaMap::new(SLOTS_PER_BUCKET, meta_bits),
+        }
+    }
+
+    // the first empty slot, reading slots whose metadata can't tell.
+    fn free_slot(&self, probes: &mut usize) -> Option<usize> {
+        SLOTS.into_iter().find(|&slot| {
+            if self.meta.hint_empty(slot) {
+                check_hint(|| self.slots[slot].is_none(), slot);
+                true
+            } else if self.meta.bits() == 0 {
+                *probes += 1;
+                self.slots[slot].is_none()
+            } else {
+                false
+            }
+        })
+    }
+
+    fn set_slot(&mut self, slot: usize, key: u64, hash: u64) {
+        self.slots[slot] = Some(key);
+        self.meta.set_full(slot, Metadata::Hash(hash));
+    }
+
+    fn clear_slot(&mut self, slot: usize) {
+        self.slots[slot] = None;
+        self.meta.set_empty(slot);
+    }
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements extendible hashing: a directory indexed by the low `depth` bits of the
+// hash points to buckets of SLOTS_PER_BUCKET slots, which several entries may share. a
+// full bucket is split in two on the next hash bit, and the directory doubles when the
+// bucket already used all of its bits. buckets are never merged.
+//
+// the map grows, so its load is reported against the capacity it was created with.
+// looking up the directory is free, and every slot read counts as one probe.
+pub struct Extendible {
+    hasher: CountingHasher,
+    directory: Vec<usize>,
+    depth: u32,
+    buckets: Vec<Bucket>,
+    meta_bits: usize,
+    // the options new buckets are allocated with.
+    options: MapOptions,
+    len: usize,
+    initial_capacity: usize,
+}
+
+impl Extendible {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        let depth = std::cmp::max(1, capacity / SLOTS_PER_BUCKET).ilog2();
+        Extendible {
+            hasher: options.hasher(0),
+            directory: (0..1 << depth).collect(),
+            depth,
+            buckets: (0..1 << depth)
+                .map(|_| Bucket::new(depth, meta_bits, options))
+                .collect(),
+            meta_bits,
+            options,
+            len: 0,
+            initial_capacity: (1 << depth) * SLOTS_PER_BUCKET,
+        }
+    }
+
+    fn bucket_for(&self, hash: u64) -> usize {
+        self.directory[(hash & ((1 << self.depth) - 1)) as usize]
+    }
+
+    // returns the bucket and slot holding the key, along with the number of probes made.
+    fn search(&self, key: u64) -> (Option<(usize, usize)>, usize) {
+        let hash = self.hasher.hash_one(key);
+        let bucket = self.bucket_for(hash);
+        explain!("bucket {bucket}");
+        let (found, probes) = candidates::find(
+            &self.buckets[bucket].meta,
+            &self.buckets[bucket].slots,
+            &SLOTS,
+            key,
+            hash,
+        );
+
+        (found.map(|slot| (bucket, slot)), probes)
+    }
+
+    // split a full bucket on its next hash bit, doubling the directory first if needed.
+    // every key moved to the new bucket is a write.
+    fn split(&mut self, bucket: usize, update: &mut Update) {
+        let depth = self.buckets[bucket].depth;
+        if depth == self.depth {
+            explain!("bucket {bucket} uses every directory bit, double the directory");
+            self.directory.extend_from_within(..);
+            self.depth += 1;
+        }
+
+        let new_bucket = self.buckets.len();
+        explain!("split bucket {bucket} on hash bit {depth} into bucket {new_bucket}");
+        self.buckets[bucket].depth += 1;
+        self.buckets
+            .push(Bucket::new(depth + 1, self.meta_bits, self.options));
+        for (index, entry) in self.directory.iter_mut().enumerate() {
+            if *entry == bucket && index & (1 << depth) != 0 {
+                *entry = new_bucket;
+            }
+        }
+
+        for slot in SLOTS {
+            let Some(key) = self.buckets[bucket].slots[slot] else {
+                continue;
+            };
+            let hash = self.hasher.hash_one(key);
+            if hash & (1 << depth) != 0 {
+                self.buckets[bucket].clear_slot(slot);
+                let new_slot = self.buckets[new_bucket]
+                    .free_slot(&mut 0)
+                    .expect("a new bucket has room for its half of a split");
+                self.buckets[new_bucket].set_slot(new_slot, key, hash);
+                update.total_writes += 1;
+            }
+        }
+        update.splits += 1;
+    }
+}
+
+impl Map for Extendible {
+    fn load_factor(&self) -> f64 {
+        self.len as f64 / self.initial_capacity as f64
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len() * SLOTS_PER_BUCKET
+    }
+
+    fn resizable(&self) -> bool {
+        true
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.bucket_for(self.hasher.hash_one(key))
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| std::mem::size_of_val(&bucket.slots[..]))
+                .sum(),
+            metadata: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.meta.memory_usage())
+                .sum(),
+            auxiliary: std::mem::size_of_val(&self.directory[..]),
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        self.buckets.iter().map(|b| b.meta.hint_stats()).sum()
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, probes) = self.search(key);
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if found.is_some() {
+            return update;
+        }
+
+        let hash = self.hasher.hash_one(key);
+        loop {
+            let bucket = self.bucket_for(hash);
+            if let Some(slot) = self.buckets[bucket].free_slot(&mut update.total_probes) {
+                explain!("place the key in bucket {bucket}, slot {slot}");
+                self.buckets[bucket].set_slot(slot, key, hash);
+                self.len += 1;
+                return update;
+            }
+
+            if self.buckets[bucket].depth == MAX_DEPTH {
+                explain!("bucket {bucket} can't be split further, give up");
+                update.completed = false;
+                return update;
+            }
+            self.split(bucket, &mut update);
+        }
+    }
+
+    fn dump(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .directory
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| {
+                format!(
+                    "directory {index:0w$b} -> bucket {bucket}",
+                    w = self.depth as usize
+                )
+            })
+            .collect();
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            for slot in SLOTS {
+                let meta = bucket.meta.describe(slot);
+                lines.push(match bucket.slots[slot] {
+                    Some(key) => format!(
+                        "bucket {index} (depth {}) slot {slot} {key} meta={meta}",
+                        bucket.depth
+                    ),
+                    None => format!(
+                        "bucket {index} (depth {}) slot {slot} - meta={meta}",
+                        bucket.depth
+                    ),
+                });
+            }
+        }
+        lines
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some((bucket, slot)) = found else {
+            return update;
+        };
+
+        explain!("clear bucket {bucket}, slot {slot}");
+        self.buckets[bucket].clear_slot(slot);
+        self.len -= 1;
+        update.total_writes += 1;
+        update
+    }
+}
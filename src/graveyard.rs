@@ -0,0 +1,300 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+#[derive(Clone, Debug)]
+enum BucketItem {
+    Value(u64),
+    Empty,
+    // left by removals, and seeded by rebuilds. either kind is reused by inserts.
+    Tombstone,
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements graveyard hashing: linear probing, rebuilt every `capacity / 4x`
+// inserts and removals, where the load is `1 - 1/x`. a rebuild drops every tombstone,
+// lays the keys out again in home-bucket order, and seeds a tombstone every `2x`
+// home buckets, so that inserts find a free slot soon after their home rather than at
+// the end of a cluster.
+//
+// a rebuild costs a write per key and tombstone, charged to the operation which
+// triggers it. its reads are one sequential scan, so aren't counted as probes.
+pub struct Graveyard {
+    hasher: CountingHasher,
+    buckets: BucketArray<BucketItem>,
+    meta: MetaMap,
+    len: usize,
+    // inserts and removals since the last rebuild, and how many trigger the next.
+    ops: usize,
+    rebuild_interval: usize,
+}
+
+impl Graveyard {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        Graveyard {
+            hasher: options.hasher(0),
+            buckets: bucket_array(capacity, BucketItem::Empty, options),
+            meta: MetaMap::new(capacity, meta_bits),
+            len: 0,
+            ops: 0,
+            rebuild_interval: std::cmp::max(1, capacity / 4),
+        }
+    }
+
+    fn home(&self, hash: u64) -> usize {
+        (hash % self.buckets.len() as u64) as usize
+    }
+
+    // walk the probe sequence until the key or an empty bucket is found.
+    //
+    // return a tuple containing:
+    //  + option containing the index of the bucket holding the key
+    //  + the first tombstone passed, where the key would be inserted
+    //  + number of probes
+    fn search(&self, key: u64) -> (Option<usize>, Option<usize>, usize) {
+        let hash = self.hasher.hash_one(key);
+        let home = self.home(hash);
+        explain!("home bucket {home}");
+        let mut probes = 0;
+        let mut tombstone = None;
+
+        for i in 0..self.buckets.len() {
+            let bucket = (home + i) % self.buckets.len();
+
+            if self.meta.hint_empty(bucket) {
+                check_hint(|| matches!(self.buckets[bucket], BucketItem::Empty), bucket);
+                explain!("bucket {bucket}: metadata says empty, the key is absent");
+                return (None, Some(tombstone.unwrap_or(bucket)), probes);
+            }
+
+            if self.meta.hint_tombstone(bucket) {
+                check_hint(
+                    || matches!(self.buckets[bucket], BucketItem::Tombstone),
+                    bucket,
+                );
+                explain!("bucket {bucket}: metadata says tombstone");
+                tombstone = tombstone.or(Some(bucket));
+                continue;
+            }
+
+            if self.meta.hint_not_match(bucket, hash) {
+                check_hint(|| self.doesnt_hold(bucket, key), bucket);
+                explain!("bucket {bucket}: metadata rules the key out, skip");
+                continue;
+            }
+
+            probes += 1;
+            self.meta.record_read(!self.doesnt_hold(bucket, key));
+            match self.buckets[bucket] {
+                BucketItem::Value(found_key) if found_key == key => {
+                    explain!("bucket {bucket}: read, holds the key");
+                    return (Some(bucket), None, probes);
+                }
+                BucketItem::Value(found_key) => {
+                    explain!("bucket {bucket}: read, holds {found_key}")
+                }
+                BucketItem::Empty => {
+                    assert_eq!(self.meta.bits(), 0);
+                    explain!("bucket {bucket}: read, empty, the key is absent");
+                    return (None, Some(tombstone.unwrap_or(bucket)), probes);
+                }
+                BucketItem::Tombstone => {
+                    assert!(self.meta.bits() < 2);
+                    explain!("bucket {bucket}: read, tombstone");
+                    tombstone = tombstone.or(Some(bucket));
+                }
+            }
+        }
+
+        (None, tombstone, probes)
+    }
+
+    fn doesnt_hold(&self, bucket: usize, key: u64) -> bool {
+        !matches!(self.buckets[bucket], BucketItem::Value(found_key) if found_key == key)
+    }
+
+    fn set_bucket(&mut self, bucket: usize, item: BucketItem) {
+        match item {
+            BucketItem::Value(key) => {
+                let hash = self.hasher.hash_one(key);
+                self.meta.set_full(bucket, Metadata::Hash(hash));
+            }
+            BucketItem::Empty => self.meta.set_empty(bucket),
+            BucketItem::Tombstone => self.meta.set_tombstone(bucket),
+        }
+        self.buckets[bucket] = item;
+    }
+
+    // count an insert or removal, rebuilding once enough have been made.
+    fn count_op(&mut self, update: &mut Update) {
+        self.ops += 1;
+        if self.ops >= self.rebuild_interval {
+            self.rebuild(update);
+        }
+    }
+
+    fn rebuild(&mut self, update: &mut Update) {
+        let capacity = self.buckets.len();
+        let x = 1.0 / (1.0 - self.load_factor()).max(1.0 / capacity as f64);
+        let spacing = std::cmp::max(1, (2.0 * x).ceil() as usize);
+        explain!("rebuild, seeding a tombstone every {spacing} buckets");
+
+        // (home bucket, key), with seeded tombstones as `None`. the tombstones sort
+        // before the keys sharing their home.
+        let mut items: Vec<(usize, Option<u64>)> = (0..capacity)
+            .step_by(spacing)
+            .map(|home| (home, None))
+            .collect();
+        for bucket in 0..capacity {
+            if let BucketItem::Value(key) = self.buckets[bucket] {
+                items.push((self.home(self.hasher.hash_one(key)), Some(key)));
+            }
+            self.set_bucket(bucket, BucketItem::Empty);
+        }
+        items.sort_unstable();
+
+        // placing in home-bucket order, each item goes to the first empty bucket from
+        // its home, as linear probing would have put it.
+        let mut placed = 0;
+        for (home, key) in items {
+            // keep enough buckets empty for lookups of absent keys to stop.
+            if key.is_none() && placed + 1 >= capacity - self.len {
+                continue;
+            }
+
+            let bucket = (0..capacity)
+                .map(|i| (home + i) % capacity)
+                .find(|&bucket| matches!(self.buckets[bucket], BucketItem::Empty))
+                .unwrap();
+            match key {
+                Some(key) => {
+                    self.set_bucket(bucket, BucketItem::Value(key));
+                    update.rebuild_moves += 1;
+                }
+                None => {
+                    self.set_bucket(bucket, BucketItem::Tombstone);
+                    placed += 1;
+                }
+            }
+            update.total_writes += 1;
+        }
+
+        update.rebuilds += 1;
+        self.ops = 0;
+        self.rebuild_interval = std::cmp::max(1, (capacity as f64 / (4.0 * x)) as usize);
+    }
+}
+
+impl Map for Graveyard {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.home(self.hasher.hash_one(key))
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        self.meta.hint_stats()
+    }
+
+    fn occupied(&self) -> Option<Vec<bool>> {
+        Some(
+            self.buckets
+                .iter()
+                .map(|item| !matches!(item, BucketItem::Empty))
+                .collect(),
+        )
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, _, probes) = self.search(key);
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let (found, free, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if found.is_some() {
+            return update;
+        }
+
+        let Some(bucket) = free else {
+            update.completed = false;
+            return update;
+        };
+
+        explain!("place the key in bucket {bucket}");
+        self.len += 1;
+        self.set_bucket(bucket, BucketItem::Value(key));
+        self.count_op(&mut update);
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let meta = self.meta.describe(bucket);
+                match self.buckets[bucket] {
+                    BucketItem::Value(key) => format!("{bucket} {key} meta={meta}"),
+                    BucketItem::Empty => format!("{bucket} - meta={meta}"),
+                    BucketItem::Tombstone => format!("{bucket} tombstone meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, _, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some(bucket) = found else {
+            return update;
+        };
+
+        explain!("leave a tombstone in bucket {bucket}");
+        self.len -= 1;
+        update.total_writes += 1;
+        self.set_bucket(bucket, BucketItem::Tombstone);
+        self.count_op(&mut update);
+        update
+    }
+}
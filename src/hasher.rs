@@ -0,0 +1,182 @@
+use ahash::RandomState;
+use rand::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::{Entry, HashMap};
+use std::iter::Sum;
+use std::ops::Sub;
+
+#[derive(Clone, Copy, Default)]
+pub struct HashStats {
+    // hashes actually computed.
+    pub invocations: usize,
+    // hashes served from the cache instead.
+    pub cache_hits: usize,
+}
+
+impl Sub for HashStats {
+    type Output = HashStats;
+
+    fn sub(self, other: HashStats) -> HashStats {
+        HashStats {
+            invocations: self.invocations - other.invocations,
+            cache_hits: self.cache_hits - other.cache_hits,
+        }
+    }
+}
+
+impl Sum for HashStats {
+    fn sum<I: Iterator<Item = HashStats>>(iter: I) -> HashStats {
+        iter.fold(HashStats::default(), |a, b| HashStats {
+            invocations: a.invocations + b.invocations,
+            cache_hits: a.cache_hits + b.cache_hits,
+        })
+    }
+}
+
+// where maps get their hashers from.
+//
+// with a seeded provider, the `i`th hasher of every map is the same function, so
+// that schemes compared in one run see the same bucket choices and fingerprints
+// rather than their own lucky or unlucky seeds.
+#[derive(Clone, Copy, Debug)]
+pub enum HashProvider {
+    Random,
+    Seeded(u64),
+}
+
+impl HashProvider {
+    // the `i`th hasher of a map.
+    pub fn hasher(&self, i: usize) -> CountingHasher {
+        let state = match *self {
+            HashProvider::Random => RandomState::new(),
+            HashProvider::Seeded(seed) => {
+                let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed.wrapping_add(i as u64));
+                RandomState::with_seeds(rng.gen(), rng.gen(), rng.gen(), rng.gen())
+            }
+        };
+
+        CountingHasher::new(state)
+    }
+
+    // a provider for a map nested in another, whose hashers are independent of the
+    // first `n` hashers of this one.
+    pub fn independent_of(&self, n: usize) -> HashProvider {
+        match *self {
+            HashProvider::Random => HashProvider::Random,
+            HashProvider::Seeded(seed) => HashProvider::Seeded(seed.wrapping_add(n as u64)),
+        }
+    }
+
+    // the RNG choosing which keys a test probes.
+    pub fn key_rng(&self) -> Xoshiro256PlusPlus {
+        self.rng(0)
+    }
+
+    // the RNG choosing which candidate a map evicts.
+    pub fn eviction_rng(&self) -> Xoshiro256PlusPlus {
+        self.rng(1)
+    }
+
+    // the RNG choosing which operation a mixed workload makes next.
+    pub fn op_rng(&self) -> Xoshiro256PlusPlus {
+        self.rng(2)
+    }
+
+    // seeded providers hand out the same, non-overlapping streams every time, so that
+    // a seeded run can be replayed exactly.
+    fn rng(&self, stream: usize) -> Xoshiro256PlusPlus {
+        match *self {
+            HashProvider::Random => Xoshiro256PlusPlus::from_entropy(),
+            HashProvider::Seeded(seed) => {
+                let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+                for _ in 0..=stream {
+                    rng.long_jump();
+                }
+                rng
+            }
+        }
+    }
+}
+
+// a hasher which counts how many times it has been invoked.
+//
+// with `--hash-cache`, hashes are remembered per key, so that repeatedly hashing
+// the same keys doesn't count towards the invocations.
+#[derive(Clone)]
+pub struct CountingHasher {
+    state: RandomState,
+    stats: Cell<HashStats>,
+    cache: Option<RefCell<HashMap<u64, u64>>>,
+}
+
+impl CountingHasher {
+    fn new(state: RandomState) -> Self {
+        CountingHasher {
+            state,
+            stats: Cell::new(HashStats::default()),
+            cache: None,
+        }
+    }
+
+    // the same hasher, remembering the hash of every key it's given.
+    pub fn cached(self) -> Self {
+        CountingHasher {
+            cache: Some(RefCell::new(HashMap::new())),
+            ..self
+        }
+    }
+
+    pub fn hash_one(&self, key: u64) -> u64 {
+        let mut stats = self.stats.get();
+        let hash = match &self.cache {
+            None => {
+                stats.invocations += 1;
+                self.state.hash_one(key)
+            }
+            Some(cache) => match cache.borrow_mut().entry(key) {
+                Entry::Occupied(entry) => {
+                    stats.cache_hits += 1;
+                    *entry.get()
+                }
+                Entry::Vacant(entry) => {
+                    stats.invocations += 1;
+                    *entry.insert(self.state.hash_one(key))
+                }
+            },
+        };
+
+        self.stats.set(stats);
+        hash
+    }
+
+    pub fn stats(&self) -> HashStats {
+        self.stats.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_providers_share_hashers() {
+        let [a, b] = [HashProvider::Seeded(7), HashProvider::Seeded(7)];
+        assert_eq!(a.hasher(0).hash_one(42), b.hasher(0).hash_one(42));
+        assert_eq!(a.hasher(2).hash_one(42), b.hasher(2).hash_one(42));
+        assert_ne!(a.hasher(0).hash_one(42), a.hasher(1).hash_one(42));
+        assert_ne!(
+            a.hasher(0).hash_one(42),
+            HashProvider::Seeded(8).hasher(0).hash_one(42)
+        );
+    }
+
+    #[test]
+    fn seeded_providers_replay_their_streams() {
+        let provider = HashProvider::Seeded(7);
+        let draw = |mut rng: Xoshiro256PlusPlus| rng.gen::<u64>();
+        assert_eq!(draw(provider.key_rng()), draw(provider.key_rng()));
+        assert_eq!(draw(provider.eviction_rng()), draw(provider.eviction_rng()));
+        assert_ne!(draw(provider.key_rng()), draw(provider.eviction_rng()));
+    }
+}
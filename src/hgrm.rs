@@ -0,0 +1,89 @@
+use hdrhistogram::Histogram;
+use std::fmt::Write as _;
+
+// percentile steps per halving of the distance to 100%, as HdrHistogram's own
+// percentile distributions use.
+const TICKS_PER_HALF_DISTANCE: u32 = 5;
+
+// a histogram's percentile distribution, in the `.hgrm` text format HdrHistogram's
+// tools write and plot. values are integers, so are written without decimals.
+pub fn format(histogram: &Histogram<u64>) -> String {
+    let mut out = format!(
+        "{:>12} {:>14} {:>10} {:>14}\n\n",
+        "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+    );
+
+    let mut total = 0;
+    for step in histogram.iter_quantiles(TICKS_PER_HALF_DISTANCE) {
+        total += step.count_since_last_iteration();
+        let quantile = step.quantile_iterated_to();
+        if quantile < 1.0 {
+            writeln!(
+                out,
+                "{:>12} {:2.12} {:>10} {:>14.2}",
+                step.value_iterated_to(),
+                quantile,
+                total,
+                1.0 / (1.0 - quantile)
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                out,
+                "{:>12} {:2.12} {:>10}",
+                step.value_iterated_to(),
+                quantile,
+                total
+            )
+            .unwrap();
+        }
+    }
+
+    let sub_buckets = (2 * 10u64.pow(histogram.sigfig() as u32)).next_power_of_two();
+    writeln!(
+        out,
+        "#[Mean    = {:>12.3}, StdDeviation   = {:>12.3}]",
+        histogram.mean(),
+        histogram.stdev()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "#[Max     = {:>12}, Total count    = {:>12}]",
+        histogram.max(),
+        histogram.len()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "#[Buckets = {:>12}, SubBuckets     = {:>12}]",
+        histogram.buckets(),
+        sub_buckets
+    )
+    .unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distribution_ends_at_the_max() {
+        let mut histogram = Histogram::<u64>::new(3).unwrap();
+        for value in 1..=100 {
+            histogram.record(value).unwrap();
+        }
+
+        let text = format(&histogram);
+        let rows: Vec<&str> = text
+            .lines()
+            .skip(2)
+            .take_while(|line| !line.starts_with('#'))
+            .collect();
+        let last: Vec<&str> = rows.last().unwrap().split_whitespace().collect();
+        assert_eq!(last, ["100", "1.000000000000", "100"]);
+        assert!(rows[0].split_whitespace().next() == Some("1"));
+        assert!(text.contains("Total count    =          100]"));
+    }
+}
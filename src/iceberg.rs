@@ -0,0 +1,272 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::candidates;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+use std::cell::Cell;
+
+pub const FRONT_SLOTS: usize = 64;
+pub const BACK_SLOTS: usize = 8;
+
+// the fraction of slots in the backyard.
+const BACKYARD_FACTOR: f64 = 0.1;
+
+// dummy hash-set for u64 keys.
+//
+// implements iceberg hashing: every key has one bucket of FRONT_SLOTS slots in the
+// front yard. keys which find it full go to the emptier of two buckets of BACK_SLOTS
+// slots in the small backyard. every front-yard bucket counts its keys in the backyard, so that
+// lookups only search there when it holds some, and can be counted as spills.
+//
+// metadata is kept per slot, and every slot read counts as one probe.
+pub struct Iceberg {
+    hashers: Vec<CountingHasher>,
+    slots: BucketArray<Option<u64>>,
+    meta: MetaMap,
+    len: usize,
+    front_buckets: usize,
+    back_buckets: usize,
+    // per front-yard bucket, how many of its keys are in the backyard.
+    overflow: Vec<u32>,
+    spills: Cell<usize>,
+    spill_probes: Cell<usize>,
+}
+
+impl Iceberg {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        let back_buckets =
+            std::cmp::max(2, (capacity as f64 * BACKYARD_FACTOR) as usize / BACK_SLOTS);
+        let front_buckets = std::cmp::max(
+            1,
+            capacity.saturating_sub(back_buckets * BACK_SLOTS) / FRONT_SLOTS,
+        );
+        let capacity = front_buckets * FRONT_SLOTS + back_buckets * BACK_SLOTS;
+        Iceberg {
+            hashers: (0..3).map(|i| options.hasher(i)).collect(),
+            slots: bucket_array(capacity, None, options),
+            meta: MetaMap::new(capacity, meta_bits),
+            len: 0,
+            front_buckets,
+            back_buckets,
+            overflow: vec![0; front_buckets],
+            spills: Cell::new(0),
+            spill_probes: Cell::new(0),
+        }
+    }
+
+    // (hash, front-yard bucket, [backyard buckets]). backyard buckets are numbered
+    // on from the front-yard ones.
+    fn buckets(&self, key: u64) -> (u64, usize, [usize; 2]) {
+        let hash = self.hashers[0].hash_one(key);
+        let front = (hash % self.front_buckets as u64) as usize;
+        let back = [1, 2].map(|i| {
+            self.front_buckets + (self.hashers[i].hash_one(key) % self.back_buckets as u64) as usize
+        });
+
+        (hash, front, back)
+    }
+
+    fn bucket_slots(&self, bucket: usize) -> std::ops::Range<usize> {
+        if bucket < self.front_buckets {
+            bucket * FRONT_SLOTS..(bucket + 1) * FRONT_SLOTS
+        } else {
+            let first =
+                self.front_buckets * FRONT_SLOTS + (bucket - self.front_buckets) * BACK_SLOTS;
+            first..first + BACK_SLOTS
+        }
+    }
+
+    // look in the front yard, then in the backyard if any of the front bucket's keys
+    // are there.
+    //
+    // return a tuple containing:
+    //  + option containing the slot holding the key
+    //  + option containing the probes made in the backyard, if it was searched
+    //  + number of probes
+    fn search(&self, key: u64) -> (Option<usize>, Option<usize>, usize) {
+        let (hash, front, back) = self.buckets(key);
+        explain!("front-yard bucket {front}, backyard buckets {back:?}");
+        let (found, mut probes) =
+            candidates::find_range(&self.meta, &self.slots, self.bucket_slots(front), key, hash);
+        if found.is_some() || self.overflow[front] == 0 {
+            return (found, None, probes);
+        }
+
+        explain!(
+            "{} keys of the bucket are in the backyard",
+            self.overflow[front]
+        );
+        let back_slots: Vec<usize> = back
+            .into_iter()
+            .flat_map(|bucket| self.bucket_slots(bucket))
+            .collect();
+        let (found, back_probes) =
+            candidates::find(&self.meta, &self.slots, &back_slots, key, hash);
+        probes += back_probes;
+        (found, Some(back_probes), probes)
+    }
+
+    // the empty slots of a bucket, reading slots whose metadata can't tell.
+    fn empty_slots(&self, bucket: usize, update: &mut Update) -> Vec<usize> {
+        self.bucket_slots(bucket)
+            .filter(|&slot| {
+                if self.meta.hint_empty(slot) {
+                    check_hint(|| self.slots[slot].is_none(), slot);
+                    true
+                } else if self.meta.bits() == 0 {
+                    update.total_probes += 1;
+                    self.slots[slot].is_none()
+                } else {
+                    false
+                }
+            })
+            .collect()
+    }
+
+    fn set_slot(&mut self, slot: usize, key: u64, hash: u64) {
+        self.slots[slot] = Some(key);
+        self.meta.set_full(slot, Metadata::Hash(hash));
+    }
+}
+
+impl Map for Iceberg {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.buckets(key).1 * FRONT_SLOTS
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hashers.iter().map(|h| h.stats()).sum()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.slots[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: std::mem::size_of_val(&self.overflow[..]),
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        self.meta.hint_stats()
+    }
+
+    fn spills(&self) -> usize {
+        self.spills.get()
+    }
+
+    fn spill_probes(&self) -> usize {
+        self.spill_probes.get()
+    }
+
+    fn overflow_occupancy(&self) -> f64 {
+        self.overflow.iter().sum::<u32>() as f64 / (self.back_buckets * BACK_SLOTS) as f64
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, spilled, probes) = self.search(key);
+        if let Some(spill_probes) = spilled {
+            self.spills.set(self.spills.get() + 1);
+            self.spill_probes
+                .set(self.spill_probes.get() + spill_probes);
+        }
+
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let (found, _, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if found.is_some() {
+            return update;
+        }
+
+        let (hash, front, back) = self.buckets(key);
+        if let Some(&slot) = self.empty_slots(front, &mut update).first() {
+            explain!("place the key in front-yard slot {slot}");
+            self.set_slot(slot, key, hash);
+            self.len += 1;
+            return update;
+        }
+
+        // the front bucket is full. take the emptier backyard bucket.
+        let [a, b] = back.map(|bucket| self.empty_slots(bucket, &mut update));
+        let empties = if b.len() > a.len() { b } else { a };
+        let Some(&slot) = empties.first() else {
+            explain!("both backyard buckets are full, give up");
+            update.completed = false;
+            return update;
+        };
+
+        explain!("front-yard bucket is full, place the key in backyard slot {slot}");
+        self.set_slot(slot, key, hash);
+        self.overflow[front] += 1;
+        self.len += 1;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.slots.len())
+            .map(|slot| {
+                let meta = self.meta.describe(slot);
+                let front_len = self.front_buckets * FRONT_SLOTS;
+                let (yard, bucket) = if slot < front_len {
+                    ("front", slot / FRONT_SLOTS)
+                } else {
+                    ("back", (slot - front_len) / BACK_SLOTS)
+                };
+                match self.slots[slot] {
+                    Some(key) => format!("{slot} ({yard} bucket {bucket}) {key} meta={meta}"),
+                    None => format!("{slot} ({yard} bucket {bucket}) - meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, spilled, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some(slot) = found else {
+            return update;
+        };
+
+        explain!("clear slot {slot}");
+        self.slots[slot] = None;
+        self.meta.set_empty(slot);
+        if spilled.is_some() {
+            let front = self.buckets(key).1;
+            self.overflow[front] -= 1;
+        }
+        self.len -= 1;
+        update.total_writes += 1;
+        update
+    }
+}
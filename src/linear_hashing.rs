@@ -0,0 +1,387 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::bloom::Bloom;
+use crate::candidates;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+use std::cell::Cell;
+
+pub const SLOTS_PER_PAGE: usize = 8;
+
+// a bucket is split whenever the keys fill more than this fraction of the slots in
+// primary pages.
+const SPLIT_LOAD: f64 = 0.8;
+
+const SLOTS: [usize; SLOTS_PER_PAGE] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+struct Page {
+    slots: BucketArray<Option<u64>>,
+    meta: MetaMap,
+    bloom: Option<Bloom>,
+}
+
+impl Page {
+    fn new(meta_bits: usize, bloom_bits: Option<usize>, options: MapOptions) -> Self {
+        Page {
+            slots: bucket_array(SLOTS_PER_PAGE, None, options),
+            meta: MetaMap::new(SLOTS_PER_PAGE, meta_bits),
+            bloom: bloom_bits.map(|bits_per_key| Bloom::new(SLOTS_PER_PAGE, bits_per_key)),
+        }
+    }
+
+    // false means the page definitely doesn't hold a key with this hash.
+    fn may_contain(&self, hash: u64) -> bool {
+        self.bloom
+            .as_ref()
+            .is_none_or(|bloom| bloom.may_contain(hash))
+    }
+
+    fn filter(&mut self, hash: u64) {
+        if let Some(bloom) = &mut self.bloom {
+            bloom.insert(hash);
+        }
+    }
+
+    // the first empty slot, reading slots whose metadata can't tell.
+    fn free_slot(&self, probes: &mut usize) -> Option<usize> {
+        SLOTS.into_iter().find(|&slot| {
+            if self.meta.hint_empty(slot) {
+                check_hint(|| self.slots[slot].is_none(), slot);
+                true
+            } else if self.meta.bits() == 0 {
+                *probes += 1;
+                self.slots[slot].is_none()
+            } else {
+                false
+            }
+        })
+    }
+
+    fn set_slot(&mut self, slot: usize, key: u64, hash: u64) {
+        self.slots[slot] = Some(key);
+        self.meta.set_full(slot, Metadata::Hash(hash));
+        self.filter(hash);
+    }
+
+    fn clear_slot(&mut self, slot: usize) {
+        self.slots[slot] = None;
+        self.meta.set_empty(slot);
+    }
+
+    fn is_unused(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements linear hashing: a bucket is a primary page of SLOTS_PER_PAGE slots,
+// followed by overflow pages once it fills. buckets are split one at a time, in order,
+// at the split pointer, whenever the load passes SPLIT_LOAD. a round of splits doubles
+// the bucket count, and buckets before the pointer are addressed with one more hash bit
+// than those after it. an overflowing bucket isn't split unless the pointer reaches it.
+//
+// pages may keep a bloom filter of their keys, consulted before their metadata. a
+// filter keeps the bits of removed keys until its bucket is next split, when it is
+// rebuilt from the keys which stay.
+//
+// the map grows, so its load is reported against the capacity it was created with.
+// every slot read counts as one probe, and moving a key during a split as one write.
+pub struct LinearHashing {
+    hasher: CountingHasher,
+    buckets: Vec<Vec<Page>>,
+    // the bucket count at the start of the current round of splits.
+    round_len: usize,
+    // the next bucket to split.
+    split: usize,
+    meta_bits: usize,
+    // the bloom filter bits per slot, if pages have filters.
+    bloom_bits: Option<usize>,
+    // the options new pages are allocated with.
+    options: MapOptions,
+    len: usize,
+    initial_capacity: usize,
+    // the hint stats of overflow pages dropped after splits.
+    dropped_hint_stats: HintStats,
+    // how well the filters ruled out the pages searched, counted per page.
+    filter_stats: Cell<HintStats>,
+}
+
+impl LinearHashing {
+    pub fn new(
+        capacity: usize,
+        meta_bits: usize,
+        bloom_bits: Option<usize>,
+        options: MapOptions,
+    ) -> Self {
+        let buckets = std::cmp::max(1, capacity / SLOTS_PER_PAGE);
+        LinearHashing {
+            hasher: options.hasher(0),
+            buckets: (0..buckets)
+                .map(|_| vec![Page::new(meta_bits, bloom_bits, options)])
+                .collect(),
+            round_len: buckets,
+            split: 0,
+            meta_bits,
+            bloom_bits,
+            options,
+            len: 0,
+            initial_capacity: buckets * SLOTS_PER_PAGE,
+            dropped_hint_stats: HintStats::default(),
+            filter_stats: Cell::new(HintStats::default()),
+        }
+    }
+
+    fn count_filter(&self, stats: HintStats) {
+        self.filter_stats
+            .set([self.filter_stats.get(), stats].into_iter().sum());
+    }
+
+    fn bucket_for(&self, hash: u64) -> usize {
+        let bucket = (hash % self.round_len as u64) as usize;
+        if bucket < self.split {
+            (hash % (2 * self.round_len) as u64) as usize
+        } else {
+            bucket
+        }
+    }
+
+    // returns the bucket, page and slot holding the key, along with the number of probes
+    // made.
+    fn search(&self, key: u64) -> (Option<(usize, usize, usize)>, usize) {
+        let hash = self.hasher.hash_one(key);
+        let bucket = self.bucket_for(hash);
+        explain!("bucket {bucket}");
+        let mut probes = 0;
+        for (index, page) in self.buckets[bucket].iter().enumerate() {
+            if !page.may_contain(hash) {
+                check_hint(|| !page.slots.contains(&Some(key)), index);
+                explain!("page {index}: filter rules the key out, skip");
+                self.count_filter(HintStats {
+                    rejections: 1,
+                    ..HintStats::default()
+                });
+                continue;
+            }
+
+            let (found, page_probes) =
+                candidates::find_range(&page.meta, &page.slots, 0..SLOTS_PER_PAGE, key, hash);
+            probes += page_probes;
+            if page.bloom.is_some() {
+                self.count_filter(HintStats {
+                    matches: 1,
+                    false_matches: usize::from(found.is_none()),
+                    rejections: 0,
+                });
+            }
+            if let Some(slot) = found {
+                return (Some((bucket, index, slot)), probes);
+            }
+        }
+
+        (None, probes)
+    }
+
+    // place a key in the first free slot of its bucket, adding an overflow page if every
+    // page is full.
+    fn place(&mut self, bucket: usize, key: u64, hash: u64, probes: &mut usize) {
+        for (index, page) in self.buckets[bucket].iter_mut().enumerate() {
+            if let Some(slot) = page.free_slot(probes) {
+                explain!("place {key} in bucket {bucket}, page {index}, slot {slot}");
+                page.set_slot(slot, key, hash);
+                return;
+            }
+        }
+
+        let index = self.buckets[bucket].len();
+        explain!("bucket {bucket} is full, add overflow page {index} for {key}");
+        let mut page = Page::new(self.meta_bits, self.bloom_bits, self.options);
+        page.set_slot(0, key, hash);
+        self.buckets[bucket].push(page);
+    }
+
+    // split the bucket at the split pointer, moving the keys which now address the new
+    // bucket at the end of the table, and advance the pointer.
+    fn split(&mut self, update: &mut Update) {
+        let bucket = self.split;
+        let new_bucket = self.buckets.len();
+        explain!("split bucket {bucket} into bucket {new_bucket}");
+        self.buckets.push(vec![Page::new(
+            self.meta_bits,
+            self.bloom_bits,
+            self.options,
+        )]);
+        self.split += 1;
+
+        for index in 0..self.buckets[bucket].len() {
+            if let Some(bloom) = &mut self.buckets[bucket][index].bloom {
+                bloom.clear();
+            }
+            for slot in SLOTS {
+                let Some(key) = self.buckets[bucket][index].slots[slot] else {
+                    continue;
+                };
+                let hash = self.hasher.hash_one(key);
+                if self.bucket_for(hash) == new_bucket {
+                    self.buckets[bucket][index].clear_slot(slot);
+                    self.place(new_bucket, key, hash, &mut 0);
+                    update.total_writes += 1;
+                } else {
+                    self.buckets[bucket][index].filter(hash);
+                }
+            }
+        }
+
+        // overflow pages emptied by the split are dropped.
+        while self.buckets[bucket].len() > 1 && self.buckets[bucket].last().unwrap().is_unused() {
+            let page = self.buckets[bucket].pop().unwrap();
+            self.dropped_hint_stats = [self.dropped_hint_stats, page.meta.hint_stats()]
+                .into_iter()
+                .sum();
+        }
+
+        if self.split == self.round_len {
+            explain!("every bucket of the round is split, start the next round");
+            self.round_len *= 2;
+            self.split = 0;
+        }
+        update.splits += 1;
+    }
+}
+
+impl Map for LinearHashing {
+    fn load_factor(&self) -> f64 {
+        self.len as f64 / self.initial_capacity as f64
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.iter().map(Vec::len).sum::<usize>() * SLOTS_PER_PAGE
+    }
+
+    fn resizable(&self) -> bool {
+        true
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.bucket_for(self.hasher.hash_one(key))
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    // the bloom filters are auxiliary.
+    fn memory_usage(&self) -> MemoryUsage {
+        let pages = || self.buckets.iter().flatten();
+        MemoryUsage {
+            buckets: pages()
+                .map(|page| std::mem::size_of_val(&page.slots[..]))
+                .sum(),
+            metadata: pages().map(|page| page.meta.memory_usage()).sum(),
+            auxiliary: pages()
+                .filter_map(|page| page.bloom.as_ref())
+                .map(|bloom| bloom.memory_usage())
+                .sum(),
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        let pages = self
+            .buckets
+            .iter()
+            .flatten()
+            .map(|page| page.meta.hint_stats());
+        pages.chain([self.dropped_hint_stats]).sum()
+    }
+
+    fn filter_stats(&self) -> HintStats {
+        self.filter_stats.get()
+    }
+
+    fn extra_meta_bits(&self) -> usize {
+        self.bloom_bits.unwrap_or(0)
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, probes) = self.search(key);
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if found.is_some() {
+            return update;
+        }
+
+        let hash = self.hasher.hash_one(key);
+        let bucket = self.bucket_for(hash);
+        self.place(bucket, key, hash, &mut update.total_probes);
+        self.len += 1;
+
+        while self.len as f64 > SPLIT_LOAD * (self.buckets.len() * SLOTS_PER_PAGE) as f64 {
+            self.split(&mut update);
+        }
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "round of {} buckets, split pointer at {}",
+            self.round_len, self.split
+        )];
+        for (bucket, pages) in self.buckets.iter().enumerate() {
+            for (index, page) in pages.iter().enumerate() {
+                for slot in SLOTS {
+                    let meta = page.meta.describe(slot);
+                    lines.push(match page.slots[slot] {
+                        Some(key) => {
+                            format!("bucket {bucket} page {index} slot {slot} {key} meta={meta}")
+                        }
+                        None => format!("bucket {bucket} page {index} slot {slot} - meta={meta}"),
+                    });
+                }
+            }
+        }
+        lines
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some((bucket, page, slot)) = found else {
+            return update;
+        };
+
+        explain!("clear bucket {bucket}, page {page}, slot {slot}");
+        self.buckets[bucket][page].clear_slot(slot);
+        self.len -= 1;
+        update.total_writes += 1;
+        update
+    }
+}
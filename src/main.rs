@@ -1,50 +1,238 @@
+use clap::{Parser, Subcommand};
 use csv::Writer;
 use hdrhistogram::Histogram;
 use rand::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
 
-use cuckoo::Cuckoo;
-use robinhood::RobinHood;
-use three_ary_cuckoo::ThreeAryCuckoo;
-use triangular_probing::TriaProb;
+use bidirectional::Bidirectional;
+use blocked_triangular::BlockedTriaProb;
+use bucketized_cuckoo::BucketizedCuckoo;
+use cache_line::CacheLine;
+use chaining::Chaining;
+use coalesced::Coalesced;
+use config::{Config, HotSet, KeyStrategy, Mix, OutputFormat, RemovalOrder};
+use cost::CostModel;
+use cuckoo::{DAryCuckoo, Evictions, ExtraBit, Insertion};
+use cuckoo_filter::CuckooFilter;
+use extendible::Extendible;
+use graveyard::Graveyard;
+use hasher::{CountingHasher, HashProvider, HashStats};
+use iceberg::Iceberg;
+use linear_hashing::LinearHashing;
+use meta_map::{count_accesses, Accesses, HintStats, Layout};
+use multi_level::MultiLevel;
+use open_addressing::{MetaKind, OpenAddressing, ProbeSequence};
+use overflow_area::OverflowArea;
+use rank::CostProfile;
+use robinhood::{Lookup, PslSource, RobinHood};
+use sink::{JsonLines, OutputSink};
+use swiss_table::SwissTable;
+use trace::Op;
+use two_choice::TwoChoice;
 
+// declared first, so that its macro is visible in every other module.
+#[macro_use]
+mod explain;
+
+mod allocation;
+mod bidirectional;
+mod blocked_triangular;
+mod bloom;
+mod bucketized_cuckoo;
+mod cache_line;
+mod candidates;
+mod chaining;
+mod chains;
+mod coalesced;
+mod config;
+mod cost;
 mod cuckoo;
+mod cuckoo_filter;
+mod extendible;
+mod graveyard;
+mod hasher;
+mod hgrm;
+mod iceberg;
+mod linear_hashing;
 mod meta_map;
+mod multi_level;
+mod open_addressing;
+mod overflow_area;
+mod rank;
+mod repl;
+mod resize;
 mod robinhood;
-mod three_ary_cuckoo;
-mod triangular_probing;
+mod sink;
+mod stats;
+mod summary;
+mod swiss_table;
+mod trace;
+mod two_choice;
+mod workload;
+
+// small, fast PRNG owned by each map for its eviction choices.
+type EvictionRng = Xoshiro256PlusPlus;
+
+// keys with this bit set are never pushed, so they are absent for any run length.
+const ABSENT_TAG: u64 = 1 << 63;
+
+// clustered keys come in runs of 2^CLUSTER_BITS consecutive keys.
+const CLUSTER_BITS: u32 = 6;
 
-#[derive(Default)]
+// a keyed bijection on `0..2^bits`, alternating xorshifts with odd multiplies, which
+// are both invertible modulo a power of two.
+fn permute(x: u64, bits: u32, seed: u64) -> u64 {
+    let mask = (1 << bits) - 1;
+    let mut x = (x ^ seed) & mask;
+    for multiplier in [0xbf58_476d_1ce4_e5b9, 0x94d0_49bb_1331_11eb] {
+        x ^= x >> (bits / 2);
+        x = x.wrapping_mul(multiplier) & mask;
+    }
+    x ^ (x >> (bits / 2))
+}
+
+// the live keys sit in the slots `min..max`. keys are pushed into the top slot and
+// popped from the bottom, the top or a random slot, depending on the removal order.
 struct KeySet {
     max: u64,
     min: u64,
+    // how many keys have been drawn, so that every push draws a fresh key.
+    drawn: u64,
+    // the index of the key drawn into each slot, where it isn't the slot itself.
+    indices: HashMap<u64, u64>,
+    strategy: KeyStrategy,
+    order: RemovalOrder,
+    // keys the permutation drawing random and clustered keys.
+    seed: u64,
+    // picks the keys probed.
+    rng: Xoshiro256PlusPlus,
+}
+
+// keys drawn at random.
+impl Default for KeySet {
+    fn default() -> Self {
+        KeySet::new(HashProvider::Random.key_rng())
+    }
 }
 
 impl KeySet {
-    fn len(&self) -> usize {
-        (self.max - self.min) as usize
+    fn new(rng: Xoshiro256PlusPlus) -> Self {
+        KeySet::with_strategy(rng, key_strategy())
+    }
+
+    // sequential keys leave the rng untouched, so that seeded runs probe the same keys
+    // they always have.
+    fn with_strategy(mut rng: Xoshiro256PlusPlus, strategy: KeyStrategy) -> Self {
+        let seed = match strategy {
+            KeyStrategy::Sequential => 0,
+            KeyStrategy::RandomUnique | KeyStrategy::Clustered => rng.gen(),
+        };
+        KeySet {
+            max: 0,
+            min: 0,
+            drawn: 0,
+            indices: HashMap::new(),
+            strategy,
+            order: removal_order(),
+            seed,
+            rng,
+        }
+    }
+
+    // the `index`th key drawn. every key is below ABSENT_TAG.
+    fn key(&self, index: u64) -> u64 {
+        match self.strategy {
+            KeyStrategy::Sequential => index,
+            KeyStrategy::RandomUnique => permute(index, 63, self.seed),
+            KeyStrategy::Clustered => {
+                let run = permute(index >> CLUSTER_BITS, 63 - CLUSTER_BITS, self.seed);
+                run << CLUSTER_BITS | index & ((1 << CLUSTER_BITS) - 1)
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.max == self.min
+    }
+
+    fn len(&self) -> u64 {
+        self.max - self.min
+    }
+
+    fn index(&self, slot: u64) -> u64 {
+        self.indices.get(&slot).copied().unwrap_or(slot)
+    }
+
+    fn set_index(&mut self, slot: u64, index: u64) {
+        if slot == index {
+            self.indices.remove(&slot);
+        } else {
+            self.indices.insert(slot, index);
+        }
+    }
+
+    // the live key `offset` slots above the bottom. under FIFO removal, the key `offset`
+    // keys after the oldest.
+    fn nth(&self, offset: u64) -> u64 {
+        assert!(offset < self.len());
+        self.key(self.index(self.min + offset))
     }
 
     fn push(&mut self) -> u64 {
+        assert!(self.drawn < ABSENT_TAG, "key space exhausted");
+        self.set_index(self.max, self.drawn);
         self.max += 1;
-        self.max - 1
+        self.drawn += 1;
+        self.key(self.drawn - 1)
     }
 
     fn pop(&mut self) -> u64 {
         assert!(self.max > self.min);
-        self.min += 1;
-        self.min
+        let slot = match self.order {
+            RemovalOrder::Fifo => self.min,
+            RemovalOrder::Lifo => self.max - 1,
+            RemovalOrder::Random => {
+                // swap a random key into the bottom slot and pop that.
+                let slot = self.rng.gen_range(self.min..self.max);
+                let (index, bottom) = (self.index(slot), self.index(self.min));
+                self.set_index(slot, bottom);
+                self.set_index(self.min, index);
+                self.min
+            }
+        };
+        if slot == self.min {
+            self.min += 1;
+        } else {
+            self.max -= 1;
+        }
+
+        let index = self.index(slot);
+        self.indices.remove(&slot);
+        self.key(index)
     }
 
-    fn existing(&self) -> u64 {
-        thread_rng().gen_range(self.min + 1..self.max)
+    fn existing(&mut self) -> u64 {
+        let slot = self.rng.gen_range(self.min..self.max);
+        self.key(self.index(slot))
     }
 
-    fn nonexisting(&self) -> u64 {
-        thread_rng().gen_range(self.max..u64::MAX)
+    fn nonexisting(&mut self) -> u64 {
+        self.rng.gen::<u64>() | ABSENT_TAG
     }
 }
 
+// the cache lines and pages an operation touches aren't fields of `Probe` or `Update`.
+// the bucket arrays and metamaps tally them as they are indexed, deduplicated across the
+// whole operation, including any rebuild or split it sets off, and `count_accesses`
+// returns the tally beside the operation's result. every map would otherwise have to
+// thread the same counts through each of its paths by hand.
 struct Probe {
     // whether the key was contained.
     contained: bool,
@@ -61,6 +249,74 @@ struct Update {
     total_writes: usize,
     // Whether the update completed.
     completed: bool,
+    // the number of keys evicted from their bucket to make room.
+    evictions: usize,
+    // the number of buckets split to grow the map.
+    splits: usize,
+    // the number of times the whole map was rebuilt, to grow it or to clear out
+    // tombstones, and the keys those rebuilds moved.
+    rebuilds: usize,
+    rebuild_moves: usize,
+}
+
+// bytes of memory held by a map.
+#[derive(Clone, Copy, Debug, Default)]
+struct MemoryUsage {
+    // the buckets or slots keys are stored in.
+    buckets: usize,
+    // metamaps, tags and other per-bucket hints.
+    metadata: usize,
+    // everything else: overflow counters, directories, filters, free lists.
+    auxiliary: usize,
+}
+
+impl MemoryUsage {
+    fn total(&self) -> usize {
+        self.buckets + self.metadata + self.auxiliary
+    }
+
+    // bytes per key of the buckets, metadata, auxiliary structures and all together.
+    fn per_key(&self, keys: usize) -> [f64; 4] {
+        let keys = std::cmp::max(1, keys) as f64;
+        [
+            self.buckets as f64 / keys,
+            self.metadata as f64 / keys,
+            self.auxiliary as f64 / keys,
+            self.total() as f64 / keys,
+        ]
+    }
+}
+
+impl std::ops::Add for MemoryUsage {
+    type Output = MemoryUsage;
+
+    fn add(self, other: MemoryUsage) -> MemoryUsage {
+        MemoryUsage {
+            buckets: self.buckets + other.buckets,
+            metadata: self.metadata + other.metadata,
+            auxiliary: self.auxiliary + other.auxiliary,
+        }
+    }
+}
+
+// the mean probes lookups should make, by closed-form analysis. None where a scheme
+// has no analysis for that kind of lookup.
+#[derive(Clone, Copy, Debug, Default)]
+struct ExpectedProbes {
+    present: Option<f64>,
+    absent: Option<f64>,
+}
+
+// record of a batch of updates.
+struct BatchUpdate {
+    // the update made for each key, in the order they were applied.
+    updates: Vec<Update>,
+}
+
+// record of a batch of lookups.
+struct BatchProbe {
+    // the lookup made for each key, in the order they were made.
+    probes: Vec<Probe>,
 }
 
 trait Map {
@@ -70,253 +326,3836 @@ trait Map {
     fn len(&self) -> usize;
     fn capacity(&self) -> usize;
 
+    // maps which grow as keys are inserted report their load against the capacity they
+    // were created with, so the grow test carries on past it.
+    fn resizable(&self) -> bool {
+        false
+    }
+
+    // maps which store only part of each key may report absent keys as contained. the
+    // probe test counts these as false positives rather than failing.
+    fn approximate(&self) -> bool {
+        false
+    }
+
+    // the first bucket (or block) a key hashes to.
+    fn home_bucket(&self, key: u64) -> usize;
+
+    // how many hashes the map's hashers have computed, or served from cache, so far.
+    fn hash_stats(&self) -> HashStats;
+
+    // the bytes held by the map's buckets, metadata and auxiliary structures. the
+    // hashers' caches aren't counted.
+    fn memory_usage(&self) -> MemoryUsage;
+
+    // how well metadata has filtered the buckets checked so far, for maps which compare
+    // hashes against it.
+    fn hint_stats(&self) -> HintStats {
+        HintStats::default()
+    }
+
+    // how many lookups so far have had to search an overflow area, for maps with one.
+    fn spills(&self) -> usize {
+        0
+    }
+
+    // how many probes those lookups made in the overflow area.
+    fn spill_probes(&self) -> usize {
+        0
+    }
+
+    // the fraction of the overflow area holding keys, for maps with one.
+    fn overflow_occupancy(&self) -> f64 {
+        0.0
+    }
+
+    // metadata bits per bucket kept outside the meta bits, such as a tombstone bit.
+    fn extra_meta_bits(&self) -> usize {
+        0
+    }
+
+    // how well filters kept beside the metadata, such as bloom filters of pages, have
+    // ruled out the blocks checked so far.
+    fn filter_stats(&self) -> HintStats {
+        HintStats::default()
+    }
+
+    // the mean probes lookups should make at `load_factor` under uniform hashing, for
+    // schemes with a closed-form analysis.
+    fn expected_probes(&self, _load_factor: f64) -> ExpectedProbes {
+        ExpectedProbes::default()
+    }
+
+    // which buckets hold a key or tombstone, for maps which keep every key in one array
+    // of buckets and probe it from a home bucket. searches can't stop at these.
+    fn occupied(&self) -> Option<Vec<bool>> {
+        None
+    }
+
+    // how far each stored key sits from its home bucket: the steps along its probe
+    // sequence, or the index of the hash function whose bucket holds it. 0 for keys in
+    // their home bucket.
+    fn displacements(&self) -> Option<Vec<usize>> {
+        None
+    }
+
+    // how many times an adaptive metamap has grown its bits so far.
+    fn meta_upgrades(&self) -> usize {
+        0
+    }
+
     fn probe(&self, key: u64) -> Probe;
     fn insert(&mut self, key: u64) -> Update;
     fn remove(&mut self, key: u64) -> Update;
+
+    // one line per bucket describing its contents, for debugging.
+    fn dump(&self) -> Vec<String>;
+
+    // insert many keys at once. all keys are hashed up front and inserted in
+    // home-bucket order, the way an offline build would lay them out. stops at the first
+    // failed insert, leaving the keys after it out.
+    fn insert_batch(&mut self, keys: &[u64]) -> BatchUpdate {
+        let mut keys: Vec<(usize, u64)> = keys
+            .iter()
+            .map(|&key| (self.home_bucket(key), key))
+            .collect();
+        keys.sort_unstable();
+
+        let mut updates = Vec::new();
+        for (_, key) in keys {
+            let update = self.insert(key);
+            let failed = failure(&update).is_some();
+            updates.push(update);
+            if failed {
+                break;
+            }
+        }
+        BatchUpdate { updates }
+    }
+
+    // look up many keys at once, in home-bucket order, so that neighbouring lookups
+    // share their lines and pages.
+    fn probe_batch(&self, keys: &[u64]) -> BatchProbe {
+        let mut keys: Vec<(usize, u64)> = keys
+            .iter()
+            .map(|&key| (self.home_bucket(key), key))
+            .collect();
+        keys.sort_unstable();
+
+        BatchProbe {
+            probes: keys.into_iter().map(|(_, key)| self.probe(key)).collect(),
+        }
+    }
+}
+
+// decides which operations are recorded into the histograms. one in every `rate`
+// operations is recorded, weighted by `rate`, so that counts still stand for every operation.
+struct Sampler {
+    rate: u64,
+    ops: u64,
+}
+
+impl Sampler {
+    fn new(rate: u64) -> Self {
+        Sampler { rate, ops: 0 }
+    }
+
+    // the weight to record the current operation with, if it is sampled.
+    fn sample(&mut self) -> Option<u64> {
+        self.ops += 1;
+        self.ops.is_multiple_of(self.rate).then_some(self.rate)
+    }
+}
+
+// run `f`, along with the nanoseconds it took. 0 unless timing.
+fn timed<T>(f: impl FnOnce() -> T) -> (T, u64) {
+    if !timing() {
+        return (f(), 0);
+    }
+
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed().as_nanos() as u64)
+}
+
+// apply an update, along with the number of hash invocations it made.
+fn counting_hashes(
+    map: &mut dyn Map,
+    update: impl FnOnce(&mut dyn Map) -> Update,
+) -> (Update, usize) {
+    let before = map.hash_stats();
+    let update = update(map);
+    (update, (map.hash_stats() - before).invocations)
+}
+
+// inserts needing more probes than this end a run, as if they had failed.
+const MAX_PROBES: usize = 128;
+
+// why an insert counts as failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Failure {
+    // the map gave up on placing the key.
+    Incomplete,
+    // the key was placed, but only after more than MAX_PROBES probes.
+    ProbeLimit,
+}
+
+fn failure(update: &Update) -> Option<Failure> {
+    if !update.completed {
+        Some(Failure::Incomplete)
+    } else if update.total_probes > MAX_PROBES {
+        Some(Failure::ProbeLimit)
+    } else {
+        None
+    }
 }
 
-fn grow(map: &mut dyn Map, keys: &mut KeySet, increment: f64) -> Option<Record> {
-    let mut probes = Histogram::new(3).unwrap();
-    let mut writes = Histogram::new_with_bounds(1, u64::max_value(), 3).unwrap();
+fn grow(map: &mut dyn Map, keys: &mut KeySet, increment: f64, log: &mut OpLog) -> Option<Record> {
+    let mut probes = Series::new(Histogram::new(3).unwrap());
+    let mut writes = Series::new(Histogram::new_with_bounds(1, u64::MAX, 3).unwrap());
+    let mut evictions = Series::new(Histogram::new(3).unwrap());
+    let mut hashes = Series::new(Histogram::new(3).unwrap());
+    let mut splits = Series::new(Histogram::new(3).unwrap());
+    let mut lines = Series::new(Histogram::new(3).unwrap());
+    let mut bucket_lines = Series::new(Histogram::new(3).unwrap());
+    let mut meta_probes = Series::new(Histogram::new(3).unwrap());
+    let mut dirty_pages = Series::new(Histogram::new(3).unwrap());
+    // the evictions of only those inserts which evicted a key, so that the tail of
+    // eviction chains isn't drowned out by the inserts finding a free bucket at once.
+    let mut chains = Series::new(Histogram::new(3).unwrap());
+    let mut latency = Series::new(Histogram::new(3).unwrap());
+    let mut sampler = Sampler::new(sample_rate());
 
     let initial_load = map.load_factor();
     let load_target = initial_load + increment;
+    let meta_upgrades = map.meta_upgrades();
+    let mut costs = Costs::new();
     while map.load_factor() < load_target {
         if map.len() == map.capacity() {
             break;
         }
-        let update = map.insert(keys.push());
+        let key = keys.push();
+        let (((update, hashes_made), accesses), nanos) =
+            timed(|| count_accesses(|| counting_hashes(map, |map| map.insert(key))));
+        log.update(Op::Insert(key), &update, map.load_factor());
 
-        if !update.completed {
-            return None;
-        } else if update.total_probes > 128 {
+        if failure(&update).is_some() {
             return None;
         }
 
-        probes.record(update.total_probes as u64).unwrap();
-        writes.record(update.total_writes as u64).unwrap();
+        costs.record(&accesses);
+        if let Some(weight) = sampler.sample() {
+            probes.record(update.total_probes as u64, weight);
+            writes.record(update.total_writes as u64, weight);
+            evictions.record(update.evictions as u64, weight);
+            hashes.record(hashes_made as u64, weight);
+            splits.record(update.splits as u64, weight);
+            lines.record(accesses.meta_lines as u64, weight);
+            bucket_lines.record(accesses.bucket_lines as u64, weight);
+            meta_probes.record(accesses.meta_probes as u64, weight);
+            dirty_pages.record(accesses.dirty_pages as u64, weight);
+            if update.evictions > 0 {
+                chains.record(update.evictions as u64, weight);
+            }
+            if timing() {
+                latency.record(nanos, weight);
+            }
+        }
     }
 
+    // the metadata upgrades made during the step, and the mean cost of an insert under
+    // each cost model.
     Some(Record {
         load_factor: initial_load,
-        histograms: vec![probes, writes],
+        series: vec![
+            probes,
+            writes,
+            evictions,
+            hashes,
+            splits,
+            lines,
+            bucket_lines,
+            meta_probes,
+            dirty_pages,
+            chains,
+            latency,
+        ],
+        scalars: std::iter::once((map.meta_upgrades() - meta_upgrades) as f64)
+            .chain(costs.means())
+            .collect(),
+        bytes_per_key: map.memory_usage().per_key(map.len()),
     })
 }
 
-fn probe(map: &dyn Map, keys: &KeySet, count: usize) -> Record {
-    let mut present = Histogram::new(3).unwrap();
-    let mut absent = Histogram::new(3).unwrap();
+// remove the oldest keys until the load factor has fallen by `decrement`, recording
+// each removal.
+fn drain(map: &mut dyn Map, keys: &mut KeySet, decrement: f64, log: &mut OpLog) -> Record {
+    let mut probes = Series::new(Histogram::new(3).unwrap());
+    let mut writes = Series::new(Histogram::new(3).unwrap());
+    let mut lines = Series::new(Histogram::new(3).unwrap());
+    let mut bucket_lines = Series::new(Histogram::new(3).unwrap());
+    let mut meta_probes = Series::new(Histogram::new(3).unwrap());
+    let mut dirty_pages = Series::new(Histogram::new(3).unwrap());
+    let mut latency = Series::new(Histogram::new(3).unwrap());
+    let mut sampler = Sampler::new(sample_rate());
 
-    let load_factor = map.load_factor();
-    for _ in 0..count {
-        let probe = map.probe(keys.existing());
-        present.record(probe.probes as u64).unwrap();
-        assert!(probe.contained);
-    }
-    for _ in 0..count {
-        let probe = map.probe(keys.nonexisting());
-        absent.record(probe.probes as u64).unwrap();
-        assert!(!probe.contained);
+    let initial_load = map.load_factor();
+    let load_target = initial_load - decrement;
+    let (mut removals, mut rebuilds, mut rebuild_moves) = (0, 0, 0);
+    let mut costs = Costs::new();
+    while map.load_factor() > load_target && !keys.is_empty() {
+        let key = keys.pop();
+        let ((update, accesses), nanos) = timed(|| count_accesses(|| map.remove(key)));
+        log.update(Op::Remove(key), &update, map.load_factor());
+
+        removals += 1;
+        rebuilds += update.rebuilds;
+        rebuild_moves += update.rebuild_moves;
+        costs.record(&accesses);
+        if let Some(weight) = sampler.sample() {
+            probes.record(update.total_probes as u64, weight);
+            writes.record(update.total_writes as u64, weight);
+            lines.record(accesses.meta_lines as u64, weight);
+            bucket_lines.record(accesses.bucket_lines as u64, weight);
+            meta_probes.record(accesses.meta_probes as u64, weight);
+            dirty_pages.record(accesses.dirty_pages as u64, weight);
+            if timing() {
+                latency.record(nanos, weight);
+            }
+        }
     }
 
+    // the rebuilds made during the step, the keys they moved per removal, and the mean
+    // cost of a removal under each cost model.
     Record {
-        load_factor,
-        histograms: vec![present, absent],
+        load_factor: initial_load,
+        series: vec![
+            probes,
+            writes,
+            lines,
+            bucket_lines,
+            meta_probes,
+            dirty_pages,
+            latency,
+        ],
+        scalars: vec![
+            rebuilds as f64,
+            rebuild_moves as f64 / std::cmp::max(1, removals) as f64,
+        ]
+        .into_iter()
+        .chain(costs.means())
+        .collect(),
+        bytes_per_key: map.memory_usage().per_key(map.len()),
     }
 }
 
-fn churn(map: &mut dyn Map, keys: &mut KeySet, count: usize) -> Record {
-    let mut probes = Histogram::new(3).unwrap();
-    let mut writes = Histogram::new_with_bounds(1, u64::max_value(), 3).unwrap();
+// fill the map up to the target load with a single batch insert.
+// also insert the same keys into `incremental` one at a time, in the order they were
+// drawn, and then look up every key in both maps, to compare the layouts the two builds
+// leave behind.
+fn bulk_load(
+    map: &mut dyn Map,
+    incremental: &mut dyn Map,
+    keys: &mut KeySet,
+    load_target: f64,
+) -> Option<Record> {
+    let mut probes = Series::new(Histogram::new(3).unwrap());
+    let mut writes = Series::new(Histogram::new_with_bounds(1, u64::MAX, 3).unwrap());
+    let mut evictions = Series::new(Histogram::new(3).unwrap());
+    let mut bulk_lookups = Series::new(Histogram::new(3).unwrap());
+    let mut incremental_lookups = Series::new(Histogram::new(3).unwrap());
+    let mut sampler = Sampler::new(sample_rate());
 
-    let load_factor = map.load_factor();
-    for _ in 0..count {
-        let update = map.remove(keys.pop());
-        probes.record(update.total_probes as u64).unwrap();
-        writes.record(update.total_writes as u64).unwrap();
+    let target_len = std::cmp::min(
+        (load_target * map.capacity() as f64).ceil() as usize,
+        map.capacity(),
+    );
+    let batch: Vec<u64> = (map.len()..target_len).map(|_| keys.push()).collect();
 
-        let update = map.insert(keys.push());
-        probes.record(update.total_probes as u64).unwrap();
-        writes.record(update.total_writes as u64).unwrap();
+    for update in map.insert_batch(&batch).updates {
+        if failure(&update).is_some() {
+            return None;
+        }
+
+        if let Some(weight) = sampler.sample() {
+            probes.record(update.total_probes as u64, weight);
+            writes.record(update.total_writes as u64, weight);
+            evictions.record(update.evictions as u64, weight);
+        }
+    }
+    for &key in &batch {
+        if failure(&incremental.insert(key)).is_some() {
+            return None;
+        }
+    }
+
+    for &key in &batch {
+        if let Some(weight) = sampler.sample() {
+            bulk_lookups.record(map.probe(key).probes as u64, weight);
+            incremental_lookups.record(incremental.probe(key).probes as u64, weight);
+        }
+    }
+
+    Some(Record {
+        load_factor: map.load_factor(),
+        series: vec![probes, writes, evictions, bulk_lookups, incremental_lookups],
+        scalars: Vec::new(),
+        bytes_per_key: map.memory_usage().per_key(map.len()),
+    })
+}
+
+// `2 * count` lookups, the fraction `misses` of them for absent keys.
+fn probe(map: &dyn Map, keys: &mut KeySet, count: usize, misses: f64, log: &mut OpLog) -> Record {
+    let mut present = Series::new(Histogram::new(3).unwrap());
+    let mut absent = Series::new(Histogram::new(3).unwrap());
+    let mut present_lines = Series::new(Histogram::new(3).unwrap());
+    let mut absent_lines = Series::new(Histogram::new(3).unwrap());
+    let mut present_bucket_lines = Series::new(Histogram::new(3).unwrap());
+    let mut absent_bucket_lines = Series::new(Histogram::new(3).unwrap());
+    let mut present_meta_probes = Series::new(Histogram::new(3).unwrap());
+    let mut absent_meta_probes = Series::new(Histogram::new(3).unwrap());
+    let mut present_latency = Series::new(Histogram::new(3).unwrap());
+    let mut absent_latency = Series::new(Histogram::new(3).unwrap());
+    let mut sampler = Sampler::new(sample_rate());
+
+    let load_factor = map.load_factor();
+    let spills = map.spills();
+    let absent_count = ((2 * count) as f64 * misses).round() as usize;
+    let present_count = 2 * count - absent_count;
+    let (mut present_costs, mut absent_costs) = (Costs::new(), Costs::new());
+    let mut lookup_probes = 0;
+    for _ in 0..present_count {
+        let key = keys.existing();
+        let ((probe, accesses), nanos) = timed(|| count_accesses(|| map.probe(key)));
+        log.probe(key, &probe, load_factor);
+        present_costs.record(&accesses);
+        lookup_probes += probe.probes;
+        if let Some(weight) = sampler.sample() {
+            present.record(probe.probes as u64, weight);
+            present_lines.record(accesses.meta_lines as u64, weight);
+            present_bucket_lines.record(accesses.bucket_lines as u64, weight);
+            present_meta_probes.record(accesses.meta_probes as u64, weight);
+            if timing() {
+                present_latency.record(nanos, weight);
+            }
+        }
+        assert!(probe.contained);
+    }
+    let present_spills = map.spills() - spills;
+    let spill_probes = map.spill_probes();
+    let hint_stats = map.hint_stats();
+    let filter_stats = map.filter_stats();
+    let mut false_positives = 0;
+    for _ in 0..absent_count {
+        let key = keys.nonexisting();
+        let ((probe, accesses), nanos) = timed(|| count_accesses(|| map.probe(key)));
+        log.probe(key, &probe, load_factor);
+        absent_costs.record(&accesses);
+        lookup_probes += probe.probes;
+        if let Some(weight) = sampler.sample() {
+            absent.record(probe.probes as u64, weight);
+            absent_lines.record(accesses.meta_lines as u64, weight);
+            absent_bucket_lines.record(accesses.bucket_lines as u64, weight);
+            absent_meta_probes.record(accesses.meta_probes as u64, weight);
+            if timing() {
+                absent_latency.record(nanos, weight);
+            }
+        }
+        if map.approximate() {
+            false_positives += probe.contained as usize;
+        } else {
+            assert!(!probe.contained);
+        }
     }
+    let absent_spills = map.spills() - spills - present_spills;
+    let absent_spill_probes = map.spill_probes() - spill_probes;
+    let hint_stats = map.hint_stats() - hint_stats;
+    let filter_stats = map.filter_stats() - filter_stats;
 
+    // the fraction of present and absent lookups which spilled into an overflow area,
+    // the fraction of absent lookups which were false positives, the fraction of the
+    // overflow area in use, the mean probes absent lookups made in it, the metadata
+    // bits per bucket on top of the meta bits, the fraction of buckets not holding the
+    // key which metadata failed to rule out, the fraction of blocks not holding it
+    // which filters failed to rule out, the mean probes analysis predicts of present
+    // and absent lookups, blank where there is none, the mean cost of present and then
+    // absent lookups under each cost model, and the mean probes of a lookup at the
+    // fraction of misses. fractions of no lookups are left blank.
+    let expected = map.expected_probes(load_factor);
     Record {
         load_factor,
-        histograms: vec![probes, writes],
+        series: vec![
+            present,
+            absent,
+            present_lines,
+            absent_lines,
+            present_bucket_lines,
+            absent_bucket_lines,
+            present_meta_probes,
+            absent_meta_probes,
+            present_latency,
+            absent_latency,
+        ],
+        scalars: vec![
+            present_spills as f64 / present_count as f64,
+            absent_spills as f64 / absent_count as f64,
+            false_positives as f64 / absent_count as f64,
+            map.overflow_occupancy(),
+            absent_spill_probes as f64 / absent_count as f64,
+            map.extra_meta_bits() as f64,
+            hint_stats.false_positive_rate(),
+            filter_stats.false_positive_rate(),
+            expected.present.unwrap_or(f64::NAN),
+            expected.absent.unwrap_or(f64::NAN),
+        ]
+        .into_iter()
+        .chain(present_costs.means())
+        .chain(absent_costs.means())
+        .chain([lookup_probes as f64 / (2 * count) as f64])
+        .collect(),
+        bytes_per_key: map.memory_usage().per_key(map.len()),
     }
 }
 
-struct Record {
-    load_factor: f64,
-    histograms: Vec<Histogram<u64>>,
-}
+// the lengths of the runs of occupied buckets, for maps which report occupancy. runs
+// may wrap around the end of the array.
+fn cluster_lengths(occupied: &[bool]) -> Vec<usize> {
+    let Some(empty) = occupied.iter().position(|&occupied| !occupied) else {
+        return vec![occupied.len()];
+    };
 
-impl Record {
-    fn write(&self, writer: &mut Writer<File>, map_spec: MapSpec) {
-        let mut csv_data = vec![
-            format!("{:.2}", self.load_factor),
-            format!("{}", map_spec.size()),
-            format!("{}", map_spec.meta_bits()),
-        ];
-        let histogram_data = self.histograms.iter().flat_map(|h| {
-            vec![
-                h.mean(),
-                h.value_at_percentile(50.0) as f64,
-                h.value_at_percentile(95.0) as f64,
-                h.value_at_percentile(99.0) as f64,
-            ]
-            .into_iter()
-            .map(|value| format!("{value:.2}"))
-        });
+    let mut lengths = Vec::new();
+    let mut run = 0;
+    for i in 1..=occupied.len() {
+        if occupied[(empty + i) % occupied.len()] {
+            run += 1;
+        } else if run > 0 {
+            lengths.push(run);
+            run = 0;
+        }
+    }
+    lengths
+}
 
-        csv_data.extend(histogram_data);
+fn clusters(map: &dyn Map) -> Option<Record> {
+    let lengths = cluster_lengths(&map.occupied()?);
+    let mut series = Series::new(Histogram::new(3).unwrap());
+    for &length in &lengths {
+        series.record(length as u64, 1);
+    }
 
-        writer.write_record(csv_data).unwrap();
+    // the number of clusters, and the mean length of the cluster an occupied bucket is
+    // in. the latter weighs clusters by length, as probe sequences run into long
+    // clusters more often.
+    let occupied: usize = lengths.iter().sum();
+    let squares: usize = lengths.iter().map(|length| length * length).sum();
+    Some(Record {
+        load_factor: map.load_factor(),
+        series: vec![series],
+        scalars: vec![
+            lengths.len() as f64,
+            squares as f64 / std::cmp::max(1, occupied) as f64,
+        ],
+        bytes_per_key: map.memory_usage().per_key(map.len()),
+    })
+}
 
-        writer.flush().unwrap();
+fn displacements(map: &dyn Map) -> Option<Record> {
+    let displacements = map.displacements()?;
+    let mut series = Series::new(Histogram::new(3).unwrap());
+    for &displacement in &displacements {
+        series.record(displacement as u64, 1);
     }
+
+    // the fraction of keys in their home bucket.
+    let home = displacements.iter().filter(|&&d| d == 0).count();
+    Some(Record {
+        load_factor: map.load_factor(),
+        series: vec![series],
+        scalars: vec![home as f64 / std::cmp::max(1, displacements.len()) as f64],
+        bytes_per_key: map.memory_usage().per_key(map.len()),
+    })
 }
 
-struct Writers {
-    grow: Writer<File>,
-    probe: Writer<File>,
-    churn: Writer<File>,
+// inserts past the probe limit in a row after which a map is taken to be as full as
+// it gets.
+const FAILURE_STREAK: usize = 100;
+
+// the first insert to fail while filling a map.
+#[derive(Debug)]
+struct FirstFailure {
+    load_factor: f64,
+    failure: Failure,
+    probes: usize,
+    evictions: usize,
 }
 
-impl Writers {
-    fn build(name: String) -> Self {
-        Writers {
-            grow: Writer::from_path(format!("out/grow_{name}.csv")).unwrap(),
-            probe: Writer::from_path(format!("out/probe_{name}.csv")).unwrap(),
-            churn: Writer::from_path(format!("out/churn_{name}.csv")).unwrap(),
+// insert keys until one is given up on, FAILURE_STREAK in a row pass the probe limit,
+// or every bucket is full. a map giving up may have dropped a key, so isn't filled
+// further. returns the first failure, if any, and the highest load reached.
+fn fill(map: &mut dyn Map, keys: &mut KeySet) -> (Option<FirstFailure>, f64) {
+    let mut first_failure = None;
+    let mut streak = 0;
+    while streak < FAILURE_STREAK && map.len() < map.capacity() {
+        let update = map.insert(keys.push());
+        let Some(failure) = failure(&update) else {
+            streak = 0;
+            continue;
+        };
+
+        streak += 1;
+        first_failure.get_or_insert(FirstFailure {
+            load_factor: map.load_factor(),
+            failure,
+            probes: update.total_probes,
+            evictions: update.evictions,
+        });
+        if failure == Failure::Incomplete {
+            break;
         }
     }
+
+    (first_failure, map.load_factor())
 }
 
-const SIZE: usize = 1 << 20;
+// loads are recorded in hundredths of a percent, as histograms hold integers, with
+// enough precision to keep them exact.
+const LOAD_SCALE: f64 = 10_000.0;
+const LOAD_SIGFIGS: u8 = 5;
 
-#[derive(Clone, Copy)]
-enum MapSpec {
-    RobinHood(usize),
-    Cuckoo(usize),
-    ThreeAryCuckoo(usize),
-    TriaProb(usize),
+// hashers a map may use. each trial's provider skips this many, so that trials of a
+// seeded run don't share hashers.
+const TRIAL_HASHERS: usize = 64;
+
+// the options, and so the hashers and keys, of a trial. the first trial's are the
+// run's own.
+fn trial_options(options: MapOptions, trial: usize) -> MapOptions {
+    options.independent_of(trial * TRIAL_HASHERS)
 }
 
-impl MapSpec {
-    fn build(&self) -> Box<dyn Map> {
-        match *self {
-            MapSpec::RobinHood(meta_bits) => Box::new(RobinHood::new(SIZE, meta_bits)),
-            MapSpec::Cuckoo(meta_bits) => Box::new(Cuckoo::new(SIZE, meta_bits)),
-            MapSpec::ThreeAryCuckoo(meta_bits) => Box::new(ThreeAryCuckoo::new(SIZE, meta_bits)),
-            MapSpec::TriaProb(meta_bits) => Box::new(TriaProb::new(SIZE, meta_bits)),
+// the records of every trial of a test, merged position by position. trials which
+// stopped early, e.g. on a failed insert, cut the others short.
+fn merge_trials(runs: Vec<Vec<Record>>) -> Vec<Record> {
+    let len = runs.iter().map(Vec::len).min().unwrap_or(0);
+    let mut runs: Vec<_> = runs.into_iter().map(Vec::into_iter).collect();
+    (0..len)
+        .map(|_| Record::merge(runs.iter_mut().map(|run| run.next().unwrap()).collect()))
+        .collect()
+}
+
+// `count` remove/insert pairs, after the first `start` pairs of a run.
+fn churn(
+    map: &mut dyn Map,
+    keys: &mut KeySet,
+    start: usize,
+    count: usize,
+    log: &mut OpLog,
+) -> Record {
+    let mut probes = Series::new(Histogram::new(3).unwrap());
+    let mut writes = Series::new(Histogram::new_with_bounds(1, u64::MAX, 3).unwrap());
+    let mut evictions = Series::new(Histogram::new(3).unwrap());
+    let mut hashes = Series::new(Histogram::new(3).unwrap());
+    let mut splits = Series::new(Histogram::new(3).unwrap());
+    let mut lines = Series::new(Histogram::new(3).unwrap());
+    let mut bucket_lines = Series::new(Histogram::new(3).unwrap());
+    let mut meta_probes = Series::new(Histogram::new(3).unwrap());
+    let mut dirty_pages = Series::new(Histogram::new(3).unwrap());
+    let mut chains = Series::new(Histogram::new(3).unwrap());
+    let mut latency = Series::new(Histogram::new(3).unwrap());
+    let mut sampler = Sampler::new(sample_rate());
+
+    let load_factor = map.load_factor();
+    let hash_stats = map.hash_stats();
+    let hint_stats = map.hint_stats();
+    let (mut rebuilds, mut rebuild_moves) = (0, 0);
+    let mut costs = Costs::new();
+    for _ in 0..count {
+        let (removed, inserted) = (keys.pop(), keys.push());
+        let removal = timed(|| count_accesses(|| counting_hashes(map, |map| map.remove(removed))));
+        log.update(Op::Remove(removed), &removal.0 .0 .0, map.load_factor());
+        let insertion =
+            timed(|| count_accesses(|| counting_hashes(map, |map| map.insert(inserted))));
+        log.update(Op::Insert(inserted), &insertion.0 .0 .0, map.load_factor());
+
+        costs.record(&removal.0 .1);
+        costs.record(&insertion.0 .1);
+        for update in [&removal.0 .0 .0, &insertion.0 .0 .0] {
+            rebuilds += update.rebuilds;
+            rebuild_moves += update.rebuild_moves;
         }
-    }
 
-    fn size(&self) -> usize {
-        SIZE
+        // the removal and insertion are sampled as a pair.
+        if let Some(weight) = sampler.sample() {
+            for (((update, hashes_made), accesses), nanos) in [removal, insertion] {
+                probes.record(update.total_probes as u64, weight);
+                writes.record(update.total_writes as u64, weight);
+                evictions.record(update.evictions as u64, weight);
+                hashes.record(hashes_made as u64, weight);
+                splits.record(update.splits as u64, weight);
+                lines.record(accesses.meta_lines as u64, weight);
+                bucket_lines.record(accesses.bucket_lines as u64, weight);
+                meta_probes.record(accesses.meta_probes as u64, weight);
+                dirty_pages.record(accesses.dirty_pages as u64, weight);
+                if update.evictions > 0 {
+                    chains.record(update.evictions as u64, weight);
+                }
+                if timing() {
+                    latency.record(nanos, weight);
+                }
+            }
+        }
     }
 
-    fn meta_bits(&self) -> usize {
-        match *self {
-            MapSpec::RobinHood(meta_bits) => meta_bits,
-            MapSpec::Cuckoo(meta_bits) => meta_bits,
-            MapSpec::ThreeAryCuckoo(meta_bits) => meta_bits,
-            MapSpec::TriaProb(meta_bits) => meta_bits,
-        }
+    // the hash cache hit rate, the metadata false positive rate, the rebuilds made during
+    // the run, the keys they moved per operation, the pairs made before the run, and the
+    // mean cost of an operation under each cost model.
+    Record {
+        load_factor,
+        series: vec![
+            probes,
+            writes,
+            evictions,
+            hashes,
+            splits,
+            lines,
+            bucket_lines,
+            meta_probes,
+            dirty_pages,
+            chains,
+            latency,
+        ],
+        scalars: vec![
+            hash_cache_hit_rate(map.hash_stats() - hash_stats),
+            (map.hint_stats() - hint_stats).false_positive_rate(),
+            rebuilds as f64,
+            rebuild_moves as f64 / (2 * count) as f64,
+            start as f64,
+        ]
+        .into_iter()
+        .chain(costs.means())
+        .collect(),
+        bytes_per_key: map.memory_usage().per_key(map.len()),
     }
 }
 
-fn grow_test(writers: &mut Writers, map_spec: MapSpec) {
-    const INCREMENT: f64 = 0.01;
-    const MAX_LOAD: f64 = 0.98;
+// `count` lookups and updates, interleaved at random in the proportions of `mix`. the
+// map's load is held at the one it started at: an update inserts below it and removes
+// above it, and only at it is an insert or a remove drawn. removes take the oldest key,
+// and an empty map is inserted into whatever was drawn. an insert which would fill the
+// map, or which fails, ends the run early.
+fn mixed(
+    map: &mut dyn Map,
+    keys: &mut KeySet,
+    rng: &mut Xoshiro256PlusPlus,
+    mix: Mix,
+    count: usize,
+    log: &mut OpLog,
+) -> Record {
+    let mut lookups = Series::new(Histogram::new(3).unwrap());
+    let mut inserts = Series::new(Histogram::new(3).unwrap());
+    let mut removes = Series::new(Histogram::new(3).unwrap());
+    let mut writes = Series::new(Histogram::new_with_bounds(1, u64::MAX, 3).unwrap());
+    let mut dirty_pages = Series::new(Histogram::new(3).unwrap());
+    let mut latency = Series::new(Histogram::new(3).unwrap());
+    let mut sampler = Sampler::new(sample_rate());
 
-    let mut map = map_spec.build();
-    let mut key_set = KeySet::default();
-    while map.load_factor() + INCREMENT < MAX_LOAD {
-        if let Some(record) = grow(&mut *map, &mut key_set, INCREMENT) {
-            record.write(&mut writers.grow, map_spec);
+    let load_factor = map.load_factor();
+    let target = map.len();
+    let mut costs = Costs::new();
+    let mut made = 0;
+    while made < count {
+        let choice = rng.gen_range(0.0..mix.total());
+        let insert = (mix.lookups..mix.lookups + mix.inserts).contains(&choice);
+        let op = if keys.is_empty() {
+            Op::Insert(keys.push())
+        } else if choice < mix.lookups {
+            Op::Probe(keys.existing())
+        } else if map.len() < target || (map.len() == target && insert) {
+            if map.len() + 1 >= map.capacity() {
+                break;
+            }
+            Op::Insert(keys.push())
         } else {
-            break;
+            Op::Remove(keys.pop())
+        };
+
+        let weight = sampler.sample();
+        let (accesses, nanos) = match op {
+            Op::Probe(key) => {
+                let ((probe, accesses), nanos) = timed(|| count_accesses(|| map.probe(key)));
+                log.probe(key, &probe, map.load_factor());
+                assert!(probe.contained);
+                if let Some(weight) = weight {
+                    lookups.record(probe.probes as u64, weight);
+                }
+                (accesses, nanos)
+            }
+            Op::Insert(key) | Op::Remove(key) => {
+                let ((update, accesses), nanos) = timed(|| {
+                    count_accesses(|| match op {
+                        Op::Insert(_) => map.insert(key),
+                        _ => map.remove(key),
+                    })
+                });
+                log.update(op, &update, map.load_factor());
+                if matches!(op, Op::Insert(_)) && failure(&update).is_some() {
+                    break;
+                }
+                if let Some(weight) = weight {
+                    let probes = match op {
+                        Op::Insert(_) => &mut inserts,
+                        _ => &mut removes,
+                    };
+                    probes.record(update.total_probes as u64, weight);
+                    writes.record(update.total_writes as u64, weight);
+                    dirty_pages.record(accesses.dirty_pages as u64, weight);
+                }
+                (accesses, nanos)
+            }
+        };
+
+        costs.record(&accesses);
+        if let Some(weight) = weight {
+            if timing() {
+                latency.record(nanos, weight);
+            }
         }
+        made += 1;
+    }
+
+    // the operations made before the run ended, the load factor it ended at, and the
+    // mean cost of an operation under each cost model.
+    Record {
+        load_factor,
+        series: vec![lookups, inserts, removes, writes, dirty_pages, latency],
+        scalars: vec![made as f64, map.load_factor()]
+            .into_iter()
+            .chain(costs.means())
+            .collect(),
+        bytes_per_key: map.memory_usage().per_key(map.len()),
     }
 }
 
-fn probe_test(writers: &mut Writers, map_spec: MapSpec) {
-    const INCREMENT: f64 = 0.02;
-    const MAX_LOAD: f64 = 0.98;
+// `count` operations, most of them on the keys of a hot set which moves on to younger
+// keys as the run goes on. a failed reinsert ends the run early.
+fn hot(
+    map: &mut dyn Map,
+    keys: &mut KeySet,
+    rng: &mut Xoshiro256PlusPlus,
+    hot_set: HotSet,
+    count: usize,
+    log: &mut OpLog,
+) -> Record {
+    let mut lookups = Series::new(Histogram::new(3).unwrap());
+    let mut updates = Series::new(Histogram::new(3).unwrap());
+    let mut writes = Series::new(Histogram::new_with_bounds(1, u64::MAX, 3).unwrap());
+    let mut lines = Series::new(Histogram::new(3).unwrap());
+    let mut recent_lines = Series::new(Histogram::new(3).unwrap());
+    let mut dirty_pages = Series::new(Histogram::new(3).unwrap());
+    let mut latency = Series::new(Histogram::new(3).unwrap());
+    let mut sampler = Sampler::new(sample_rate());
 
-    let mut load = 0.1;
-    while load <= MAX_LOAD {
-        let mut map = map_spec.build();
-        let mut key_set = KeySet::default();
-        if grow(&mut *map, &mut key_set, load).is_none() {
-            break;
+    let load_factor = map.load_factor();
+    let live = keys.len();
+    let hot_keys = std::cmp::max(1, (live as f64 * hot_set.keys).round() as u64);
+    let (mut total_lines, mut total_recent_lines) = (0, 0);
+    let mut costs = Costs::new();
+    let mut made = 0;
+    while made < count && live > 0 {
+        let offset = if rng.gen_bool(hot_set.share) {
+            (made / hot_set.shift) as u64 + rng.gen_range(0..hot_keys)
+        } else {
+            rng.gen_range(0..live)
+        };
+        let key = keys.nth(offset % live);
+
+        let weight = sampler.sample();
+        let (accesses, nanos) = if rng.gen_bool(hot_set.updates) {
+            let (((removal, insertion), accesses), nanos) =
+                timed(|| count_accesses(|| (map.remove(key), map.insert(key))));
+            log.update(Op::Remove(key), &removal, map.load_factor());
+            log.update(Op::Insert(key), &insertion, map.load_factor());
+            if failure(&insertion).is_some() {
+                break;
+            }
+            if let Some(weight) = weight {
+                let probes = removal.total_probes + insertion.total_probes;
+                updates.record(probes as u64, weight);
+                writes.record(
+                    (removal.total_writes + insertion.total_writes) as u64,
+                    weight,
+                );
+                dirty_pages.record(accesses.dirty_pages as u64, weight);
+            }
+            (accesses, nanos)
+        } else {
+            let ((probe, accesses), nanos) = timed(|| count_accesses(|| map.probe(key)));
+            log.probe(key, &probe, load_factor);
+            assert!(probe.contained);
+            if let Some(weight) = weight {
+                lookups.record(probe.probes as u64, weight);
+            }
+            (accesses, nanos)
         };
 
-        let record = probe(&*map, &key_set, 10_000);
-        record.write(&mut writers.probe, map_spec);
-        load += INCREMENT;
+        costs.record(&accesses);
+        total_lines += accesses.bucket_lines + accesses.meta_lines;
+        total_recent_lines += accesses.recent_lines;
+        if let Some(weight) = weight {
+            lines.record((accesses.bucket_lines + accesses.meta_lines) as u64, weight);
+            recent_lines.record(accesses.recent_lines as u64, weight);
+            if timing() {
+                latency.record(nanos, weight);
+            }
+        }
+        made += 1;
+    }
+
+    // the operations made before the run ended, the fraction of the cache lines they
+    // touched which a recent operation had touched too, and the mean cost of an
+    // operation under each cost model.
+    Record {
+        load_factor,
+        series: vec![
+            lookups,
+            updates,
+            writes,
+            lines,
+            recent_lines,
+            dirty_pages,
+            latency,
+        ],
+        scalars: vec![made as f64, total_recent_lines as f64 / total_lines as f64]
+            .into_iter()
+            .chain(costs.means())
+            .collect(),
+        bytes_per_key: map.memory_usage().per_key(map.len()),
     }
 }
 
-fn churn_test(writers: &mut Writers, map_spec: MapSpec) {
-    const INCREMENT: f64 = 0.02;
-    const MAX_LOAD: f64 = 0.98;
+// `count` inserts and as many lookups, in batches of `batch_size`, made as batches on
+// `batched` and one at a time on `single`, which hold the same keys. the accesses of a
+// batch are counted together, so that lines and pages touched by several of its keys
+// count once. the inserts raise the load as the run goes, and a failed one ends it.
+fn batch(
+    batched: &mut dyn Map,
+    single: &mut dyn Map,
+    keys: &mut KeySet,
+    batch_size: usize,
+    count: usize,
+    max_load: f64,
+) -> Record {
+    let series = || Series::new(Histogram::new(3).unwrap());
+    let [mut batched_insert_lines, mut single_insert_lines] = [series(), series()];
+    let [mut batched_dirty_pages, mut single_dirty_pages] = [series(), series()];
+    let [mut batched_lookup_lines, mut single_lookup_lines] = [series(), series()];
+    let [mut batched_page_reads, mut single_page_reads] = [series(), series()];
+    let mut sampler = Sampler::new(sample_rate());
 
-    let mut load = 0.1;
-    while load <= MAX_LOAD {
-        let mut map = map_spec.build();
-        let mut key_set = KeySet::default();
-        if grow(&mut *map, &mut key_set, load).is_none() {
+    let load_factor = batched.load_factor();
+    let mut costs = [(); 4].map(|_| Costs::new());
+    let mut batches = 0;
+    // only as many keys as fit under `max_load` are inserted, so that no map is filled.
+    let room = ((max_load * batched.capacity() as f64) as usize).saturating_sub(batched.len());
+    'batches: for _ in 0..std::cmp::min(count, room) / batch_size {
+        let inserted: Vec<u64> = (0..batch_size).map(|_| keys.push()).collect();
+        let (updates, batched_accesses) = count_accesses(|| batched.insert_batch(&inserted));
+        let mut single_accesses = Accesses::default();
+        for &key in &inserted {
+            let (update, accesses) = count_accesses(|| single.insert(key));
+            if failure(&update).is_some() {
+                break 'batches;
+            }
+            single_accesses = single_accesses + accesses;
+        }
+        if updates
+            .updates
+            .iter()
+            .any(|update| failure(update).is_some())
+        {
             break;
-        };
+        }
+
+        let looked_up: Vec<u64> = (0..batch_size).map(|_| keys.existing()).collect();
+        let (probes, batched_lookups) = count_accesses(|| batched.probe_batch(&looked_up));
+        assert!(probes.probes.iter().all(|probe| probe.contained));
+        let mut single_lookups = Accesses::default();
+        for &key in &looked_up {
+            let (probe, accesses) = count_accesses(|| single.probe(key));
+            assert!(probe.contained);
+            single_lookups = single_lookups + accesses;
+        }
 
-        let record = churn(&mut *map, &mut key_set, 10_000);
-        record.write(&mut writers.churn, map_spec);
-        load += INCREMENT;
+        for (costs, accesses) in costs.iter_mut().zip([
+            &batched_accesses,
+            &single_accesses,
+            &batched_lookups,
+            &single_lookups,
+        ]) {
+            costs.record(accesses);
+        }
+        if let Some(weight) = sampler.sample() {
+            let lines = |accesses: &Accesses| (accesses.bucket_lines + accesses.meta_lines) as u64;
+            let page_reads = |accesses: &Accesses| {
+                (accesses.random_page_reads + accesses.sequential_page_reads) as u64
+            };
+            batched_insert_lines.record(lines(&batched_accesses), weight);
+            single_insert_lines.record(lines(&single_accesses), weight);
+            batched_dirty_pages.record(batched_accesses.dirty_pages as u64, weight);
+            single_dirty_pages.record(single_accesses.dirty_pages as u64, weight);
+            batched_lookup_lines.record(lines(&batched_lookups), weight);
+            single_lookup_lines.record(lines(&single_lookups), weight);
+            batched_page_reads.record(page_reads(&batched_lookups), weight);
+            single_page_reads.record(page_reads(&single_lookups), weight);
+        }
+        batches += 1;
+    }
+
+    // the batches made before the run ended, and the mean cost of a key under each
+    // cost model, of batched and then single inserts, and of batched and then single
+    // lookups.
+    Record {
+        load_factor,
+        series: vec![
+            batched_insert_lines,
+            single_insert_lines,
+            batched_dirty_pages,
+            single_dirty_pages,
+            batched_lookup_lines,
+            single_lookup_lines,
+            batched_page_reads,
+            single_page_reads,
+        ],
+        scalars: std::iter::once(batches as f64)
+            .chain(
+                costs
+                    .iter()
+                    .flat_map(|costs| costs.means())
+                    .map(|cost| cost / batch_size as f64),
+            )
+            .collect(),
+        bytes_per_key: batched.memory_usage().per_key(batched.len()),
     }
 }
 
-fn main() {
-    std::fs::create_dir_all("out").unwrap();
+// the total cost of the operations measured, under each cost model.
+struct Costs {
+    totals: Vec<f64>,
+    ops: usize,
+}
 
-    let mut writers = Writers::build(format!("robinhood"));
-    for meta_bits in [0, 1, 2, 4, 8] {
-        println!("robinhood {meta_bits}");
-        let map_spec = MapSpec::RobinHood(meta_bits);
-        grow_test(&mut writers, map_spec);
-        probe_test(&mut writers, map_spec);
-        churn_test(&mut writers, map_spec);
+impl Costs {
+    fn new() -> Self {
+        Costs {
+            totals: vec![0.0; cost_models().len()],
+            ops: 0,
+        }
+    }
+
+    fn record(&mut self, accesses: &Accesses) {
+        self.ops += 1;
+        for (total, model) in self.totals.iter_mut().zip(cost_models()) {
+            *total += model.cost(accesses);
+        }
     }
 
-    let mut writers = Writers::build(format!("cuckoo"));
-    for meta_bits in [0, 1, 2, 4, 8] {
-        println!("cuckoo {meta_bits}");
+    // the mean cost of an operation under each model, blank without operations.
+    fn means(&self) -> Vec<f64> {
+        self.totals
+            .iter()
+            .map(|&total| {
+                if self.ops == 0 {
+                    f64::NAN
+                } else {
+                    total / self.ops as f64
+                }
+            })
+            .collect()
+    }
+}
 
-        let map_spec = MapSpec::Cuckoo(meta_bits);
-        grow_test(&mut writers, map_spec);
-        probe_test(&mut writers, map_spec);
-        churn_test(&mut writers, map_spec);
+// the fraction of hashes served from the hash cache. 0 if the cache is disabled.
+fn hash_cache_hit_rate(stats: HashStats) -> f64 {
+    let total = stats.invocations + stats.cache_hits;
+    if total == 0 {
+        return 0.0;
     }
 
-    let mut writers = Writers::build(format!("3arycuckoo"));
-    for meta_bits in [0, 1, 2, 4, 8] {
-        println!("3arycuckoo {meta_bits}");
+    stats.cache_hits as f64 / total as f64
+}
+
+// which values the reservoirs keep, and the bootstrap resamples, don't depend on the
+// values themselves, so a fixed seed keeps runs reproducible without biasing either.
+const SAMPLING_SEED: u64 = 0;
 
-        let map_spec = MapSpec::ThreeAryCuckoo(meta_bits);
-        grow_test(&mut writers, map_spec);
-        probe_test(&mut writers, map_spec);
-        churn_test(&mut writers, map_spec);
+// a uniform random sample of up to `capacity` of the values recorded.
+struct Reservoir {
+    capacity: usize,
+    seen: u64,
+    samples: Vec<u64>,
+    rng: Xoshiro256PlusPlus,
+}
+
+impl Reservoir {
+    fn new(capacity: usize) -> Self {
+        Reservoir {
+            capacity,
+            seen: 0,
+            samples: Vec::new(),
+            rng: Xoshiro256PlusPlus::seed_from_u64(SAMPLING_SEED),
+        }
     }
 
-    let mut writers = Writers::build(format!("triaprob"));
-    for meta_bits in [0, 1, 2, 4, 8] {
-        println!("triangular_probing {meta_bits}");
+    fn record(&mut self, value: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.seen += 1;
+        if self.samples.len() < self.capacity {
+            self.samples.push(value);
+            return;
+        }
 
-        let map_spec = MapSpec::TriaProb(meta_bits);
-        grow_test(&mut writers, map_spec);
-        probe_test(&mut writers, map_spec);
-        churn_test(&mut writers, map_spec);
+        let index = self.rng.gen_range(0..self.seen) as usize;
+        if index < self.capacity {
+            self.samples[index] = value;
+        }
+    }
+}
+
+// the values recorded for a single metric: a histogram summarizing all of them,
+// plus a reservoir of raw samples.
+struct Series {
+    histogram: Histogram<u64>,
+    reservoir: Reservoir,
+    // the mean of each trial merged into the series. empty for a single trial.
+    trial_means: Vec<f64>,
+}
+
+impl Series {
+    fn new(histogram: Histogram<u64>) -> Self {
+        Series {
+            histogram,
+            reservoir: Reservoir::new(reservoir_size()),
+            trial_means: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, value: u64, weight: u64) {
+        self.histogram.record_n(value, weight).unwrap();
+        self.reservoir.record(value);
+    }
+
+    // pool another trial's values into this one's, keeping the mean of each.
+    fn merge(&mut self, other: Series) {
+        if self.trial_means.is_empty() {
+            self.trial_means.push(self.histogram.mean());
+        }
+        self.trial_means.push(other.histogram.mean());
+        self.histogram.add(&other.histogram).unwrap();
+        for value in other.reservoir.samples {
+            self.reservoir.record(value);
+        }
+    }
+}
+
+struct Record {
+    load_factor: f64,
+    series: Vec<Series>,
+    // single values describing the whole run, written after the histogram columns.
+    scalars: Vec<f64>,
+    // the map's bytes per key at the end of the run, written after the scalars.
+    bytes_per_key: [f64; 4],
+}
+
+impl Record {
+    // one record of the same measurement over several trials, pooling their series and
+    // averaging everything else.
+    fn merge(records: Vec<Record>) -> Record {
+        let trials = records.len() as f64;
+        let mut records = records.into_iter();
+        let mut merged = records.next().unwrap();
+        for record in records {
+            merged.load_factor += record.load_factor;
+            for (series, other) in merged.series.iter_mut().zip(record.series) {
+                series.merge(other);
+            }
+            for (scalar, other) in merged.scalars.iter_mut().zip(record.scalars) {
+                *scalar += other;
+            }
+            for (bytes, other) in merged.bytes_per_key.iter_mut().zip(record.bytes_per_key) {
+                *bytes += other;
+            }
+        }
+
+        merged.load_factor /= trials;
+        merged
+            .scalars
+            .iter_mut()
+            .for_each(|scalar| *scalar /= trials);
+        merged
+            .bytes_per_key
+            .iter_mut()
+            .for_each(|bytes| *bytes /= trials);
+        merged
+    }
+
+    fn write(&self, writer: &mut Writer<File>, map_spec: MapSpec) {
+        let mut csv_data = vec![
+            format!("{:.2}", self.load_factor),
+            format!("{}", map_spec.size()),
+            format!("{}", map_spec.meta_bits()),
+        ];
+        // per series: the mean, every percentile, the max and the standard deviation.
+        let histogram_data = self.series.iter().map(|s| &s.histogram).flat_map(|h| {
+            std::iter::once(h.mean())
+                .chain(
+                    percentiles()
+                        .iter()
+                        .map(|&p| h.value_at_percentile(p) as f64),
+                )
+                .chain([h.max() as f64, h.stdev()])
+                .map(|value| format!("{value:.2}"))
+        });
+
+        csv_data.extend(histogram_data);
+        // scalars without a value are left blank.
+        csv_data.extend(self.scalars.iter().map(|value| {
+            if value.is_nan() {
+                String::new()
+            } else {
+                format!("{value:.2}")
+            }
+        }));
+        csv_data.extend(self.bytes_per_key.iter().map(|value| format!("{value:.2}")));
+
+        // with `--bootstrap`, intervals of the mean and p99 per series, from the reservoir
+        // samples. left blank for series without samples.
+        if bootstrapping() {
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(SAMPLING_SEED);
+            for series in &self.series {
+                let samples = &series.reservoir.samples;
+                let mean = stats::bootstrap(
+                    samples,
+                    BOOTSTRAP_RESAMPLES,
+                    CONFIDENCE,
+                    |s| stats::mean(s),
+                    &mut rng,
+                );
+                let p99 = stats::bootstrap(
+                    samples,
+                    BOOTSTRAP_RESAMPLES,
+                    CONFIDENCE,
+                    |s| stats::percentile(s, 99.0),
+                    &mut rng,
+                );
+                for interval in [mean, p99] {
+                    csv_data.extend(match interval {
+                        Some((lo, hi)) => [format!("{lo:.2}"), format!("{hi:.2}")],
+                        None => [String::new(), String::new()],
+                    });
+                }
+            }
+        }
+
+        // the mean of the trials' means per series, with its 95% confidence interval.
+        // left blank for a single trial.
+        for series in &self.series {
+            csv_data.extend(match stats::trial_interval(&series.trial_means) {
+                Some((mean, lo, hi)) => [mean, lo, hi].map(|value| format!("{value:.2}")),
+                None => [String::new(), String::new(), String::new()],
+            });
+        }
+
+        writer.write_record(csv_data).unwrap();
+
+        writer.flush().unwrap();
+    }
+
+    // one row per raw sample: test, load factor, size, meta bits, series index, value.
+    fn write_samples(&self, writer: &mut Writer<File>, test: &str, map_spec: MapSpec) {
+        for (i, series) in self.series.iter().enumerate() {
+            for value in &series.reservoir.samples {
+                writer
+                    .write_record([
+                        test.to_string(),
+                        format!("{:.2}", self.load_factor),
+                        format!("{}", map_spec.size()),
+                        format!("{}", map_spec.meta_bits()),
+                        format!("{i}"),
+                        format!("{value}"),
+                    ])
+                    .unwrap();
+            }
+        }
+
+        writer.flush().unwrap();
+    }
+
+    // with `--hgrm`, every series' full histogram, as
+    // `out/hgrm/{test}_{name}_{meta bits}_{size}_{load factor}_{series index}.hgrm`.
+    fn write_histograms(&self, test: &str, map_spec: MapSpec) {
+        if !exporting_histograms() {
+            return;
+        }
+
+        for (i, series) in self.series.iter().enumerate() {
+            let (name, meta_bits, size) = (map_spec.name(), map_spec.meta_bits(), size());
+            let path = format!(
+                "{HGRM_DIR}/{test}_{name}_{meta_bits}_{size}_{:.2}_{i}.hgrm",
+                self.load_factor
+            );
+            std::fs::write(path, hgrm::format(&series.histogram)).unwrap();
+        }
+    }
+}
+
+struct Writers {
+    grow: Box<dyn OutputSink>,
+    probe: Box<dyn OutputSink>,
+    churn: Box<dyn OutputSink>,
+    bulk: Box<dyn OutputSink>,
+    amortized: Box<dyn OutputSink>,
+    clusters: Box<dyn OutputSink>,
+    displacements: Box<dyn OutputSink>,
+    failures: Box<dyn OutputSink>,
+    mixed: Box<dyn OutputSink>,
+    drain: Box<dyn OutputSink>,
+    hot: Box<dyn OutputSink>,
+    soak: Box<dyn OutputSink>,
+    batch: Box<dyn OutputSink>,
+    sawtooth: Box<dyn OutputSink>,
+    samples: Writer<File>,
+    ops: Writer<File>,
+}
+
+const OUTPUTS: [&str; 16] = [
+    "grow",
+    "probe",
+    "churn",
+    "bulk",
+    "amortized",
+    "clusters",
+    "displacements",
+    "failures",
+    "mixed",
+    "drain",
+    "hot",
+    "soak",
+    "batch",
+    "sawtooth",
+    "samples",
+    "ops",
+];
+
+const PARTS_DIR: &str = "out/parts";
+
+const HGRM_DIR: &str = "out/hgrm";
+
+impl Writers {
+    // each task writes into its own part files, which are merged once all tasks are done.
+    // records written as JSON lines go straight to the one file instead, and leave no parts.
+    fn build(map_spec: MapSpec) -> Self {
+        let part = |output: &str| {
+            let (name, meta_bits, size) = (map_spec.name(), map_spec.meta_bits(), size());
+            Writer::from_path(format!(
+                "{PARTS_DIR}/{output}_{name}_{meta_bits}_{size}.csv"
+            ))
+            .unwrap()
+        };
+        let sink = |output: &'static str| -> Box<dyn OutputSink> {
+            match output_format() {
+                OutputFormat::Csv => Box::new(part(output)),
+                OutputFormat::Jsonl => Box::new(JsonLines { output }),
+            }
+        };
+
+        Writers {
+            grow: sink("grow"),
+            probe: sink("probe"),
+            churn: sink("churn"),
+            bulk: sink("bulk"),
+            amortized: sink("amortized"),
+            clusters: sink("clusters"),
+            displacements: sink("displacements"),
+            failures: sink("failures"),
+            mixed: sink("mixed"),
+            drain: sink("drain"),
+            hot: sink("hot"),
+            soak: sink("soak"),
+            batch: sink("batch"),
+            sawtooth: sink("sawtooth"),
+            samples: part("samples"),
+            ops: part("ops"),
+        }
+    }
+
+    // the sink of a record output, by name.
+    fn sink(&mut self, output: &str) -> &mut dyn OutputSink {
+        match output {
+            "grow" => &mut *self.grow,
+            "probe" => &mut *self.probe,
+            "churn" => &mut *self.churn,
+            "bulk" => &mut *self.bulk,
+            "amortized" => &mut *self.amortized,
+            "clusters" => &mut *self.clusters,
+            "displacements" => &mut *self.displacements,
+            "failures" => &mut *self.failures,
+            "mixed" => &mut *self.mixed,
+            "drain" => &mut *self.drain,
+            "hot" => &mut *self.hot,
+            "soak" => &mut *self.soak,
+            "batch" => &mut *self.batch,
+            "sawtooth" => &mut *self.sawtooth,
+            _ => panic!("no record output {output}"),
+        }
+    }
+
+    fn op_log<'a>(&'a mut self, test: &'a str) -> OpLog<'a> {
+        OpLog {
+            writer: &mut self.ops,
+            test,
+        }
+    }
+
+    // concatenate the parts of every map kind, in size then task order, into
+    // `out/{output}_{name}.csv`.
+    fn merge(map_specs: &[MapSpec], sizes: &[usize]) {
+        let mut names: Vec<&str> = Vec::new();
+        for map_spec in map_specs {
+            if !names.contains(&map_spec.name()) {
+                names.push(map_spec.name());
+            }
+        }
+
+        for output in OUTPUTS {
+            for &name in &names {
+                let parts: Vec<String> = sizes
+                    .iter()
+                    .flat_map(|size| {
+                        let map_specs = map_specs.iter().filter(move |s| s.name() == name);
+                        map_specs.map(move |map_spec| {
+                            let meta_bits = map_spec.meta_bits();
+                            format!("{PARTS_DIR}/{output}_{name}_{meta_bits}_{size}.csv")
+                        })
+                    })
+                    .collect();
+                // records written as JSON lines leave no parts to merge.
+                if !parts.iter().all(|part| std::path::Path::new(part).exists()) {
+                    continue;
+                }
+
+                let mut out = File::create(format!("out/{output}_{name}.csv")).unwrap();
+                for part in parts {
+                    std::io::copy(&mut File::open(part).unwrap(), &mut out).unwrap();
+                }
+            }
+        }
+
+        std::fs::remove_dir_all(PARTS_DIR).unwrap();
+    }
+}
+
+// every operation a test makes, written to `out/ops_{name}.csv` when `--log-ops` is set.
+// one `test,op,key,probes,writes,completed,load_factor` row per operation, with the load
+// factor after it. for lookups, `completed` is whether the key was found.
+struct OpLog<'a> {
+    writer: &'a mut Writer<File>,
+    test: &'a str,
+}
+
+impl OpLog<'_> {
+    fn update(&mut self, op: Op, update: &Update, load_factor: f64) {
+        let (probes, writes) = (update.total_probes, update.total_writes);
+        self.record(op, probes, writes, update.completed, load_factor);
+    }
+
+    fn probe(&mut self, key: u64, probe: &Probe, load_factor: f64) {
+        self.record(
+            Op::Probe(key),
+            probe.probes,
+            0,
+            probe.contained,
+            load_factor,
+        );
+    }
+
+    fn record(&mut self, op: Op, probes: usize, writes: usize, completed: bool, load: f64) {
+        if !logging_ops() {
+            return;
+        }
+
+        self.writer
+            .write_record([
+                self.test.to_string(),
+                op.name().to_string(),
+                op.key().to_string(),
+                probes.to_string(),
+                writes.to_string(),
+                u8::from(completed).to_string(),
+                format!("{load:.4}"),
+            ])
+            .unwrap();
+    }
+}
+
+// buckets in every simulated map. set from `--size`, before any map is built, and again
+// before each size of `--sizes` is simulated.
+static SIZE: AtomicUsize = AtomicUsize::new(1 << 20);
+
+// log2 sizes from here on couldn't be allocated.
+const MAX_SIZE: u32 = 40;
+
+fn size() -> usize {
+    SIZE.load(Ordering::Relaxed)
+}
+
+// whether operations are timed. set once from `--timed`. the latency histograms are
+// left empty otherwise.
+static TIMING: AtomicBool = AtomicBool::new(false);
+
+fn timing() -> bool {
+    TIMING.load(Ordering::Relaxed)
+}
+
+// whether full histograms are written beside the summaries. set once from `--hgrm`.
+static EXPORT_HISTOGRAMS: AtomicBool = AtomicBool::new(false);
+
+fn exporting_histograms() -> bool {
+    EXPORT_HISTOGRAMS.load(Ordering::Relaxed)
+}
+
+// whether every operation is logged. set once from `--log-ops`.
+static LOG_OPS: AtomicBool = AtomicBool::new(false);
+
+fn logging_ops() -> bool {
+    LOG_OPS.load(Ordering::Relaxed)
+}
+
+// how many times every measurement is repeated. set once from `--trials`.
+static TRIALS: AtomicUsize = AtomicUsize::new(1);
+
+fn trials() -> usize {
+    TRIALS.load(Ordering::Relaxed)
+}
+
+// how keys are drawn. set once from `--keys`.
+static KEY_STRATEGY: OnceLock<KeyStrategy> = OnceLock::new();
+
+fn key_strategy() -> KeyStrategy {
+    KEY_STRATEGY.get().copied().unwrap_or_default()
+}
+
+// how every test's records are written. set once from `--output`.
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+fn output_format() -> OutputFormat {
+    OUTPUT_FORMAT.get().copied().unwrap_or_default()
+}
+
+// which live key every removal takes. set once from `--removals`.
+static REMOVAL_ORDER: OnceLock<RemovalOrder> = OnceLock::new();
+
+fn removal_order() -> RemovalOrder {
+    REMOVAL_ORDER.get().copied().unwrap_or_default()
+}
+
+// the percentiles written per series. set once from `--percentiles`.
+static PERCENTILES: OnceLock<Vec<f64>> = OnceLock::new();
+
+fn percentiles() -> &'static [f64] {
+    PERCENTILES
+        .get()
+        .map_or(&config::DEFAULT_PERCENTILES, Vec::as_slice)
+}
+
+// the cost models every operation is priced under. set once from `--cost-model`.
+static COST_MODELS: OnceLock<Vec<CostModel>> = OnceLock::new();
+
+fn cost_models() -> &'static [CostModel] {
+    COST_MODELS.get_or_init(cost::default_models)
+}
+
+// the columns written per series: the mean, the percentiles, the max and the standard
+// deviation.
+fn series_columns() -> usize {
+    percentiles().len() + 3
+}
+
+// record 1 in every this many operations into the histograms. set once from
+// `--sample-rate`.
+static SAMPLE_RATE: AtomicU64 = AtomicU64::new(1);
+
+fn sample_rate() -> u64 {
+    SAMPLE_RATE.load(Ordering::Relaxed)
+}
+
+// keep up to this many raw values per series, written to `out/samples_{name}.csv`. set
+// once from `--reservoir-size`.
+static RESERVOIR_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+fn reservoir_size() -> usize {
+    RESERVOIR_SIZE.load(Ordering::Relaxed)
+}
+
+// the reservoir kept for bootstrapping, unless a size is given.
+const DEFAULT_RESERVOIR_SIZE: usize = 10_000;
+
+// whether bootstrap intervals are written for every series. set once from `--bootstrap`.
+static BOOTSTRAP: AtomicBool = AtomicBool::new(false);
+
+fn bootstrapping() -> bool {
+    BOOTSTRAP.load(Ordering::Relaxed)
+}
+
+// resamples drawn for the bootstrap intervals of each series, and their confidence level.
+const BOOTSTRAP_RESAMPLES: usize = 1000;
+const CONFIDENCE: f64 = 0.95;
+
+// the settings a map is built with: where its hashers come from, and how its buckets
+// are allocated. the run's maps take theirs from the command line.
+#[derive(Clone, Copy, Debug)]
+struct MapOptions {
+    hashes: HashProvider,
+    // bytes per page of bucket arrays.
+    page_size: usize,
+    // advise the kernel to back bucket arrays with transparent hugepages.
+    hugepages: bool,
+    // remember the hash of every key, so that churn measurements aren't dominated by
+    // rehashing the same keys. the hit rate is reported per churn run.
+    hash_cache: bool,
+}
+
+impl MapOptions {
+    // hashers seeded from `seed`, with default allocation and no hash cache.
+    #[cfg(test)]
+    fn seeded(seed: u64) -> Self {
+        MapOptions {
+            hashes: HashProvider::Seeded(seed),
+            page_size: config::DEFAULT_PAGE_SIZE,
+            hugepages: false,
+            hash_cache: false,
+        }
+    }
+
+    // the options of the run. every random choice is drawn from `--seed` when it's
+    // given. with `--shared-hashes`, every map hashes with the same seeded hashers, so
+    // that comparisons between schemes aren't skewed by their hasher seeds.
+    fn of_run(config: &Config) -> Self {
+        let hashes = match config.seed {
+            Some(seed) => HashProvider::Seeded(seed),
+            None if config.shared_hashes => HashProvider::Seeded(thread_rng().gen()),
+            None => HashProvider::Random,
+        };
+        MapOptions {
+            hashes,
+            page_size: config.page_size,
+            hugepages: config.hugepages,
+            hash_cache: config.hash_cache,
+        }
+    }
+
+    // the `i`th hasher of a map.
+    fn hasher(&self, i: usize) -> CountingHasher {
+        let hasher = self.hashes.hasher(i);
+        if self.hash_cache {
+            hasher.cached()
+        } else {
+            hasher
+        }
+    }
+
+    // the options of a map nested in another, whose hashers are independent of the
+    // first `n` hashers of this one.
+    fn independent_of(&self, n: usize) -> Self {
+        MapOptions {
+            hashes: self.hashes.independent_of(n),
+            ..*self
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum MapSpec {
+    // (meta bits, psl source, lookup)
+    RobinHood(usize, PslSource, Lookup),
+    // robin hood with buckets in disk pages, probes counting pages read.
+    PagedRobinHood(usize),
+    // (meta bits, d, insertion)
+    Cuckoo(usize, usize, Insertion),
+    // cuckoo with an alternate bit per bucket beside the meta bits.
+    CuckooAlternate(usize),
+    // 3-ary cuckoo with a referenced bit per bucket, which evictions avoid.
+    CuckooAging(usize),
+    BucketizedCuckoo(usize),
+    TriaProb(usize),
+    // triangular probing with a tombstone bit per bucket beside the meta bits.
+    TriaProbTombstones(usize),
+    // triangular probing whose meta bits, starting from these, grow as needed.
+    AdaptiveTriaProb(usize),
+    // triangular probing whose meta bits count the longest probe run from each bucket.
+    MaxRunTriaProb(usize),
+    // triangular probing with its metadata fields arranged by the layout.
+    TriaProbLayout(usize, Layout),
+    // (meta bits, percentage of buckets holding tombstones to compact at)
+    CompactingTriaProb(usize, usize),
+    BlockedTriaProb(usize),
+    QuadProb(usize),
+    DoubleHashing(usize),
+    SwissTable(usize),
+    Graveyard(usize),
+    Coalesced(usize),
+    Iceberg(usize),
+    OverflowArea(usize),
+    MultiLevel(usize),
+    Bidirectional(usize),
+    Extendible(usize),
+    LinearHashing(usize),
+    // (meta bits, bloom filter bits per key)
+    LinearHashingBloom(usize, usize),
+    CacheLine,
+    Chaining,
+    TwoChoice,
+    CuckooFilter(usize),
+}
+
+impl MapSpec {
+    fn build(&self, options: MapOptions) -> Box<dyn Map> {
+        self.build_with_capacity(size(), options)
+    }
+
+    fn build_with_capacity(&self, capacity: usize, options: MapOptions) -> Box<dyn Map> {
+        match *self {
+            MapSpec::RobinHood(meta_bits, psl_source, lookup) => Box::new(RobinHood::new(
+                capacity, meta_bits, psl_source, lookup, 1, options,
+            )),
+            MapSpec::PagedRobinHood(meta_bits) => Box::new(RobinHood::new(
+                capacity,
+                meta_bits,
+                PslSource::Rehash,
+                Lookup::Linear,
+                robinhood::PAGE_SLOTS,
+                options,
+            )),
+            MapSpec::Cuckoo(meta_bits, d, insertion) => Box::new(DAryCuckoo::new(
+                capacity,
+                d,
+                meta_bits,
+                ExtraBit::None,
+                options,
+                insertion,
+                Evictions::Random(options.hashes.eviction_rng()),
+            )),
+            MapSpec::CuckooAlternate(meta_bits) => Box::new(DAryCuckoo::new(
+                capacity,
+                2,
+                meta_bits,
+                ExtraBit::Alternate,
+                options,
+                Insertion::RandomWalk,
+                Evictions::Random(options.hashes.eviction_rng()),
+            )),
+            MapSpec::CuckooAging(meta_bits) => Box::new(DAryCuckoo::new(
+                capacity,
+                3,
+                meta_bits,
+                ExtraBit::Aging,
+                options,
+                Insertion::RandomWalk,
+                Evictions::Random(options.hashes.eviction_rng()),
+            )),
+            MapSpec::BucketizedCuckoo(meta_bits) => {
+                Box::new(BucketizedCuckoo::new(capacity, meta_bits, options))
+            }
+            MapSpec::TriaProb(meta_bits) => Box::new(OpenAddressing::new(
+                capacity,
+                meta_bits,
+                ProbeSequence::Triangular,
+                MetaKind::Fixed,
+                Layout::Msb0,
+                None,
+                options,
+            )),
+            MapSpec::TriaProbTombstones(meta_bits) => Box::new(OpenAddressing::new(
+                capacity,
+                meta_bits,
+                ProbeSequence::Triangular,
+                MetaKind::Tombstones,
+                Layout::Msb0,
+                None,
+                options,
+            )),
+            MapSpec::AdaptiveTriaProb(meta_bits) => Box::new(OpenAddressing::new(
+                capacity,
+                meta_bits,
+                ProbeSequence::Triangular,
+                MetaKind::Adaptive,
+                Layout::Msb0,
+                None,
+                options,
+            )),
+            MapSpec::MaxRunTriaProb(meta_bits) => Box::new(OpenAddressing::new(
+                capacity,
+                meta_bits,
+                ProbeSequence::Triangular,
+                MetaKind::MaxRuns,
+                Layout::Msb0,
+                None,
+                options,
+            )),
+            MapSpec::TriaProbLayout(meta_bits, layout) => Box::new(OpenAddressing::new(
+                capacity,
+                meta_bits,
+                ProbeSequence::Triangular,
+                MetaKind::Fixed,
+                layout,
+                None,
+                options,
+            )),
+            MapSpec::CompactingTriaProb(meta_bits, percent) => Box::new(OpenAddressing::new(
+                capacity,
+                meta_bits,
+                ProbeSequence::Triangular,
+                MetaKind::Fixed,
+                Layout::Msb0,
+                Some(percent),
+                options,
+            )),
+            MapSpec::BlockedTriaProb(meta_bits) => {
+                Box::new(BlockedTriaProb::new(capacity, meta_bits, options))
+            }
+            MapSpec::QuadProb(meta_bits) => Box::new(OpenAddressing::new(
+                capacity,
+                meta_bits,
+                ProbeSequence::Quadratic,
+                MetaKind::Fixed,
+                Layout::Msb0,
+                None,
+                options,
+            )),
+            MapSpec::DoubleHashing(meta_bits) => Box::new(OpenAddressing::new(
+                capacity,
+                meta_bits,
+                ProbeSequence::Double,
+                MetaKind::Fixed,
+                Layout::Msb0,
+                None,
+                options,
+            )),
+            MapSpec::SwissTable(meta_bits) => {
+                Box::new(SwissTable::new(capacity, meta_bits, options))
+            }
+            MapSpec::Graveyard(meta_bits) => Box::new(Graveyard::new(capacity, meta_bits, options)),
+            MapSpec::Coalesced(meta_bits) => Box::new(Coalesced::new(capacity, meta_bits, options)),
+            MapSpec::Iceberg(meta_bits) => Box::new(Iceberg::new(capacity, meta_bits, options)),
+            MapSpec::OverflowArea(meta_bits) => {
+                Box::new(OverflowArea::new(capacity, meta_bits, options))
+            }
+            MapSpec::MultiLevel(meta_bits) => {
+                Box::new(MultiLevel::new(capacity, meta_bits, options))
+            }
+            MapSpec::Bidirectional(meta_bits) => {
+                Box::new(Bidirectional::new(capacity, meta_bits, options))
+            }
+            MapSpec::Extendible(meta_bits) => {
+                Box::new(Extendible::new(capacity, meta_bits, options))
+            }
+            MapSpec::LinearHashing(meta_bits) => {
+                Box::new(LinearHashing::new(capacity, meta_bits, None, options))
+            }
+            MapSpec::LinearHashingBloom(meta_bits, bloom_bits) => Box::new(LinearHashing::new(
+                capacity,
+                meta_bits,
+                Some(bloom_bits),
+                options,
+            )),
+            MapSpec::CacheLine => Box::new(CacheLine::new(capacity, options)),
+            MapSpec::Chaining => Box::new(Chaining::new(capacity, options)),
+            MapSpec::TwoChoice => Box::new(TwoChoice::new(capacity, options)),
+            MapSpec::CuckooFilter(fingerprint_bits) => {
+                Box::new(CuckooFilter::new(capacity, fingerprint_bits, options))
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            MapSpec::RobinHood(_, PslSource::Rehash, Lookup::Linear) => "robinhood",
+            MapSpec::RobinHood(_, PslSource::Metadata, Lookup::Linear) => "robinhoodmeta",
+            MapSpec::RobinHood(_, PslSource::CachedHome, Lookup::Linear) => "robinhoodcached",
+            MapSpec::RobinHood(_, PslSource::Rehash, Lookup::Smart) => "robinhoodsmart",
+            MapSpec::RobinHood(_, PslSource::Metadata, Lookup::Smart) => "robinhoodmetasmart",
+            MapSpec::RobinHood(_, PslSource::CachedHome, Lookup::Smart) => "robinhoodcachedsmart",
+            MapSpec::RobinHood(_, PslSource::Split(psl_bits), Lookup::Linear) => match psl_bits {
+                1 => "robinhoodsplit1",
+                2 => "robinhoodsplit2",
+                3 => "robinhoodsplit3",
+                4 => "robinhoodsplit4",
+                _ => unreachable!(),
+            },
+            MapSpec::RobinHood(_, PslSource::Split(psl_bits), Lookup::Smart) => match psl_bits {
+                1 => "robinhoodsplit1smart",
+                2 => "robinhoodsplit2smart",
+                3 => "robinhoodsplit3smart",
+                4 => "robinhoodsplit4smart",
+                _ => unreachable!(),
+            },
+            MapSpec::PagedRobinHood(_) => "robinhoodpaged",
+            MapSpec::Cuckoo(_, d, Insertion::RandomWalk) => match d {
+                2 => "cuckoo",
+                3 => "3arycuckoo",
+                4 => "4arycuckoo",
+                5 => "5arycuckoo",
+                6 => "6arycuckoo",
+                7 => "7arycuckoo",
+                8 => "8arycuckoo",
+                _ => unreachable!(),
+            },
+            MapSpec::Cuckoo(_, d, Insertion::Bfs) => match d {
+                2 => "cuckoobfs",
+                3 => "3arycuckoobfs",
+                4 => "4arycuckoobfs",
+                5 => "5arycuckoobfs",
+                6 => "6arycuckoobfs",
+                7 => "7arycuckoobfs",
+                8 => "8arycuckoobfs",
+                _ => unreachable!(),
+            },
+            MapSpec::CuckooAlternate(_) => "cuckooalternate",
+            MapSpec::CuckooAging(_) => "3arycuckooaging",
+            MapSpec::BucketizedCuckoo(_) => "bucketcuckoo",
+            MapSpec::TriaProb(_) => "triaprob",
+            MapSpec::TriaProbTombstones(_) => "triaprobtombstones",
+            MapSpec::AdaptiveTriaProb(_) => "triaprobadaptive",
+            MapSpec::MaxRunTriaProb(_) => "triaprobmaxrun",
+            MapSpec::TriaProbLayout(_, layout) => match layout {
+                Layout::Msb0 => "triaprob",
+                Layout::Lsb0 => "triaproblsb0",
+                Layout::Interleaved => "triaprobinterleaved",
+            },
+            MapSpec::CompactingTriaProb(_, percent) => match percent {
+                10 => "triaprobcompact10",
+                25 => "triaprobcompact25",
+                50 => "triaprobcompact50",
+                _ => unreachable!(),
+            },
+            MapSpec::BlockedTriaProb(_) => "blockedtriaprob",
+            MapSpec::QuadProb(_) => "quadprob",
+            MapSpec::DoubleHashing(_) => "doublehashing",
+            MapSpec::SwissTable(_) => "swisstable",
+            MapSpec::Graveyard(_) => "graveyard",
+            MapSpec::Coalesced(_) => "coalesced",
+            MapSpec::Iceberg(_) => "iceberg",
+            MapSpec::OverflowArea(_) => "overflowarea",
+            MapSpec::MultiLevel(_) => "multilevel",
+            MapSpec::Bidirectional(_) => "bidirectional",
+            MapSpec::Extendible(_) => "extendible",
+            MapSpec::LinearHashing(_) => "linearhashing",
+            MapSpec::LinearHashingBloom(_, bloom_bits) => match bloom_bits {
+                4 => "linearhashingbloom4",
+                8 => "linearhashingbloom8",
+                16 => "linearhashingbloom16",
+                _ => unreachable!(),
+            },
+            MapSpec::CacheLine => "cacheline",
+            MapSpec::Chaining => "chaining",
+            MapSpec::TwoChoice => "twochoice",
+            MapSpec::CuckooFilter(_) => "cuckoofilter",
+        }
+    }
+
+    fn size(&self) -> usize {
+        size()
+    }
+
+    // the fewest buckets a map can be built with: one per hash function for cuckoo
+    // hashing, else one.
+    fn min_capacity(&self) -> usize {
+        match *self {
+            MapSpec::Cuckoo(_, d, _) => d,
+            MapSpec::CuckooAlternate(_) => 2,
+            MapSpec::CuckooAging(_) => 3,
+            _ => 1,
+        }
+    }
+
+    fn meta_bits(&self) -> usize {
+        match *self {
+            MapSpec::RobinHood(meta_bits, _, _) => meta_bits,
+            MapSpec::PagedRobinHood(meta_bits) => meta_bits,
+            MapSpec::Cuckoo(meta_bits, _, _) => meta_bits,
+            MapSpec::CuckooAlternate(meta_bits) => meta_bits,
+            MapSpec::CuckooAging(meta_bits) => meta_bits,
+            MapSpec::BucketizedCuckoo(meta_bits) => meta_bits,
+            MapSpec::TriaProb(meta_bits) => meta_bits,
+            MapSpec::TriaProbTombstones(meta_bits) => meta_bits,
+            MapSpec::AdaptiveTriaProb(meta_bits) => meta_bits,
+            MapSpec::MaxRunTriaProb(meta_bits) => meta_bits,
+            MapSpec::TriaProbLayout(meta_bits, _) => meta_bits,
+            MapSpec::CompactingTriaProb(meta_bits, _) => meta_bits,
+            MapSpec::BlockedTriaProb(meta_bits) => meta_bits,
+            MapSpec::QuadProb(meta_bits) => meta_bits,
+            MapSpec::DoubleHashing(meta_bits) => meta_bits,
+            MapSpec::SwissTable(meta_bits) => meta_bits,
+            MapSpec::Graveyard(meta_bits) => meta_bits,
+            MapSpec::Coalesced(meta_bits) => meta_bits,
+            MapSpec::Iceberg(meta_bits) => meta_bits,
+            MapSpec::OverflowArea(meta_bits) => meta_bits,
+            MapSpec::MultiLevel(meta_bits) => meta_bits,
+            MapSpec::Bidirectional(meta_bits) => meta_bits,
+            MapSpec::Extendible(meta_bits) => meta_bits,
+            MapSpec::LinearHashing(meta_bits) => meta_bits,
+            MapSpec::LinearHashingBloom(meta_bits, _) => meta_bits,
+            MapSpec::CacheLine => cache_line::TAG_BITS,
+            MapSpec::Chaining | MapSpec::TwoChoice => 0,
+            // the fingerprint takes the place of metadata.
+            MapSpec::CuckooFilter(fingerprint_bits) => fingerprint_bits,
+        }
+    }
+}
+
+// specs are written `{name}:{meta_bits}`, e.g. `robinhood:4`. maps without a
+// meta-bits sweep are written by name alone.
+impl FromStr for MapSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, meta_bits) = match s.split_once(':') {
+            Some((name, meta_bits)) => {
+                let meta_bits = meta_bits
+                    .parse()
+                    .map_err(|_| format!("invalid meta bits in {s}"))?;
+                (name, Some(meta_bits))
+            }
+            None => (s, None),
+        };
+
+        // cuckoo variants are `cuckoo` or `{d}arycuckoo`, with a `bfs` suffix for
+        // breadth-first insertion.
+        let (cuckoo_name, insertion) = match name.strip_suffix("bfs") {
+            Some(name) => (name, Insertion::Bfs),
+            None => (name, Insertion::RandomWalk),
+        };
+        let d = match cuckoo_name.strip_suffix("arycuckoo").map(str::parse) {
+            _ if cuckoo_name == "cuckoo" => Some(2),
+            Some(Ok(d)) if (3..=cuckoo::MAX_D).contains(&d) => Some(d),
+            _ => None,
+        };
+        if let (Some(d), Some(bits)) = (d, meta_bits) {
+            return Ok(MapSpec::Cuckoo(bits, d, insertion));
+        }
+
+        // robin hood variants take a `smart` suffix for smart lookups.
+        let (robinhood_name, lookup) = match name.strip_suffix("smart") {
+            Some(name) => (name, Lookup::Smart),
+            None => (name, Lookup::Linear),
+        };
+        let psl_source = match robinhood_name {
+            "robinhood" => Some(PslSource::Rehash),
+            "robinhoodmeta" => Some(PslSource::Metadata),
+            "robinhoodcached" => Some(PslSource::CachedHome),
+            // `robinhoodsplit{psl bits}`, with fewer PSL bits than meta bits.
+            _ => match robinhood_name
+                .strip_prefix("robinhoodsplit")
+                .map(str::parse)
+            {
+                Some(Ok(psl_bits))
+                    if (1..=robinhood::MAX_SPLIT_PSL_BITS).contains(&psl_bits)
+                        && meta_bits.is_some_and(|bits| psl_bits < bits) =>
+                {
+                    Some(PslSource::Split(psl_bits))
+                }
+                _ => None,
+            },
+        };
+        if let (Some(psl_source), Some(bits)) = (psl_source, meta_bits) {
+            return Ok(MapSpec::RobinHood(bits, psl_source, lookup));
+        }
+
+        // `linearhashingbloom{bits per key}`.
+        let bloom_bits = name
+            .strip_prefix("linearhashingbloom")
+            .and_then(|bits| bits.parse().ok())
+            .filter(|bits| bloom::BITS_PER_KEY.contains(bits));
+        if let (Some(bloom_bits), Some(bits)) = (bloom_bits, meta_bits) {
+            return Ok(MapSpec::LinearHashingBloom(bits, bloom_bits));
+        }
+
+        // `triaprobcompact{percent}`.
+        let percent = name
+            .strip_prefix("triaprobcompact")
+            .and_then(|percent| percent.parse().ok())
+            .filter(|percent| open_addressing::COMPACTION_PERCENTS.contains(percent));
+        if let (Some(percent), Some(bits)) = (percent, meta_bits) {
+            return Ok(MapSpec::CompactingTriaProb(bits, percent));
+        }
+
+        match (name, meta_bits) {
+            ("robinhoodpaged", Some(bits)) => Ok(MapSpec::PagedRobinHood(bits)),
+            ("cuckooalternate", Some(bits)) => Ok(MapSpec::CuckooAlternate(bits)),
+            ("3arycuckooaging", Some(bits)) => Ok(MapSpec::CuckooAging(bits)),
+            ("bucketcuckoo", Some(bits)) => Ok(MapSpec::BucketizedCuckoo(bits)),
+            ("triaprob", Some(bits)) => Ok(MapSpec::TriaProb(bits)),
+            ("triaprobtombstones", Some(bits)) => Ok(MapSpec::TriaProbTombstones(bits)),
+            ("triaprobadaptive", Some(bits)) => Ok(MapSpec::AdaptiveTriaProb(bits)),
+            ("triaprobmaxrun", Some(bits)) => Ok(MapSpec::MaxRunTriaProb(bits)),
+            ("triaproblsb0", Some(bits)) => Ok(MapSpec::TriaProbLayout(bits, Layout::Lsb0)),
+            ("triaprobinterleaved", Some(bits)) => {
+                Ok(MapSpec::TriaProbLayout(bits, Layout::Interleaved))
+            }
+            ("blockedtriaprob", Some(bits)) => Ok(MapSpec::BlockedTriaProb(bits)),
+            ("quadprob", Some(bits)) => Ok(MapSpec::QuadProb(bits)),
+            ("doublehashing", Some(bits)) => Ok(MapSpec::DoubleHashing(bits)),
+            ("swisstable", Some(bits)) => Ok(MapSpec::SwissTable(bits)),
+            ("graveyard", Some(bits)) => Ok(MapSpec::Graveyard(bits)),
+            ("coalesced", Some(bits)) => Ok(MapSpec::Coalesced(bits)),
+            ("iceberg", Some(bits)) => Ok(MapSpec::Iceberg(bits)),
+            ("overflowarea", Some(bits)) => Ok(MapSpec::OverflowArea(bits)),
+            ("multilevel", Some(bits)) => Ok(MapSpec::MultiLevel(bits)),
+            ("bidirectional", Some(bits)) => Ok(MapSpec::Bidirectional(bits)),
+            ("extendible", Some(bits)) => Ok(MapSpec::Extendible(bits)),
+            ("linearhashing", Some(bits)) => Ok(MapSpec::LinearHashing(bits)),
+            ("cacheline", None) => Ok(MapSpec::CacheLine),
+            ("chaining", None) => Ok(MapSpec::Chaining),
+            ("twochoice", None) => Ok(MapSpec::TwoChoice),
+            ("cuckoofilter", Some(bits))
+                if (1..=cuckoo_filter::MAX_FINGERPRINT_BITS).contains(&bits) =>
+            {
+                Ok(MapSpec::CuckooFilter(bits))
+            }
+            _ => Err(format!("unknown map spec {s}")),
+        }
+    }
+}
+
+// the inverse of FromStr.
+impl std::fmt::Display for MapSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MapSpec::CacheLine | MapSpec::Chaining | MapSpec::TwoChoice => {
+                write!(f, "{}", self.name())
+            }
+            _ => write!(f, "{}:{}", self.name(), self.meta_bits()),
+        }
+    }
+}
+
+// the fraction of buckets holding tombstones, for maps which report occupancy.
+fn tombstone_fraction(map: &dyn Map) -> f64 {
+    map.occupied().map_or(f64::NAN, |occupied| {
+        let occupied = occupied.iter().filter(|&&occupied| occupied).count();
+        occupied.saturating_sub(map.len()) as f64 / map.capacity() as f64
+    })
+}
+
+// the mean displacement of the stored keys, for maps which report them.
+fn mean_displacement(map: &dyn Map) -> f64 {
+    map.displacements().map_or(f64::NAN, |displacements| {
+        displacements.iter().sum::<usize>() as f64 / displacements.len() as f64
+    })
+}
+
+// the meta-bits settings swept, for the schemes which keep metadata.
+const META_BITS: [usize; 7] = [0, 1, 2, 4, 8, 12, 16];
+
+// every scheme and meta-bits setting the simulation runs.
+fn enabled_map_specs() -> Vec<MapSpec> {
+    let mut map_specs = Vec::new();
+    for psl_source in [
+        PslSource::Rehash,
+        PslSource::Metadata,
+        PslSource::CachedHome,
+    ] {
+        for meta_bits in META_BITS {
+            map_specs.push(MapSpec::RobinHood(meta_bits, psl_source, Lookup::Linear));
+        }
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::RobinHood(
+            meta_bits,
+            PslSource::Rehash,
+            Lookup::Smart,
+        ));
+    }
+    for psl_bits in 1..=robinhood::MAX_SPLIT_PSL_BITS {
+        for meta_bits in META_BITS.into_iter().filter(|&bits| psl_bits < bits) {
+            map_specs.push(MapSpec::RobinHood(
+                meta_bits,
+                PslSource::Split(psl_bits),
+                Lookup::Linear,
+            ));
+        }
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::PagedRobinHood(meta_bits));
+    }
+    for d in 2..=6 {
+        for meta_bits in META_BITS {
+            map_specs.push(MapSpec::Cuckoo(meta_bits, d, Insertion::RandomWalk));
+        }
+    }
+    for d in 2..=4 {
+        for meta_bits in META_BITS {
+            map_specs.push(MapSpec::Cuckoo(meta_bits, d, Insertion::Bfs));
+        }
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::CuckooAlternate(meta_bits));
+        map_specs.push(MapSpec::CuckooAging(meta_bits));
+        map_specs.push(MapSpec::BucketizedCuckoo(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::TriaProb(meta_bits));
+        map_specs.push(MapSpec::TriaProbTombstones(meta_bits));
+        map_specs.push(MapSpec::AdaptiveTriaProb(meta_bits));
+        map_specs.push(MapSpec::MaxRunTriaProb(meta_bits));
+        map_specs.push(MapSpec::TriaProbLayout(meta_bits, Layout::Lsb0));
+        map_specs.push(MapSpec::TriaProbLayout(meta_bits, Layout::Interleaved));
+        for percent in open_addressing::COMPACTION_PERCENTS {
+            map_specs.push(MapSpec::CompactingTriaProb(meta_bits, percent));
+        }
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::BlockedTriaProb(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::QuadProb(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::DoubleHashing(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::SwissTable(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::Graveyard(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::Coalesced(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::Iceberg(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::OverflowArea(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::MultiLevel(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::Bidirectional(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::Extendible(meta_bits));
+    }
+    for meta_bits in META_BITS {
+        map_specs.push(MapSpec::LinearHashing(meta_bits));
+        for bloom_bits in bloom::BITS_PER_KEY {
+            map_specs.push(MapSpec::LinearHashingBloom(meta_bits, bloom_bits));
+        }
+    }
+    // tags are co-located with the keys, so there is no meta-bits sweep.
+    map_specs.push(MapSpec::CacheLine);
+    // chains have no metadata.
+    map_specs.push(MapSpec::Chaining);
+    map_specs.push(MapSpec::TwoChoice);
+    // fingerprints of no bits can't tell keys apart.
+    for fingerprint_bits in [1, 2, 4, 8] {
+        map_specs.push(MapSpec::CuckooFilter(fingerprint_bits));
+    }
+    map_specs
+}
+
+// a seed of its own for every spec in a seeded run. derived from the spec rather than
+// its position, so a spec replays the same way whichever others are run alongside it.
+fn spec_seed(seed: u64, map_spec: MapSpec) -> u64 {
+    map_spec
+        .to_string()
+        .bytes()
+        .fold(seed, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+// the options of a map of the run: hashers seeded per spec from `--seed` when it's
+// given, unless hashers are shared.
+fn spec_options(config: &Config, options: MapOptions, map_spec: MapSpec) -> MapOptions {
+    match config.seed {
+        Some(seed) if !config.shared_hashes => MapOptions {
+            hashes: HashProvider::Seeded(spec_seed(seed, map_spec)),
+            ..options
+        },
+        _ => options,
+    }
+}
+
+fn simulate(config: &Config, options: MapOptions) {
+    let map_specs = selected_map_specs(&config.maps, &config.meta_bits);
+    std::fs::create_dir_all(PARTS_DIR).unwrap();
+    if output_format() == OutputFormat::Jsonl {
+        sink::open_json_lines();
+    }
+    if exporting_histograms() {
+        std::fs::create_dir_all(HGRM_DIR).unwrap();
+    }
+
+    let sizes: Vec<usize> = match config.sizes.as_slice() {
+        [] => vec![size()],
+        sizes => sizes.iter().map(|&size| 1 << size).collect(),
+    };
+    for &size in &sizes {
+        SIZE.store(size, Ordering::Relaxed);
+        simulate_size(config, &map_specs, options);
+    }
+
+    Writers::merge(&map_specs, &sizes);
+    // the summary is read back from the CSVs.
+    if output_format() == OutputFormat::Csv {
+        summary::print(&map_specs, &sizes);
+    }
+}
+
+// every test of every map, at the current size.
+fn simulate_size(config: &Config, map_specs: &[MapSpec], options: MapOptions) {
+    // every (map, meta_bits) pair is independent.
+    map_specs.par_iter().for_each(|&map_spec| {
+        println!("{} {} {}", map_spec.name(), map_spec.meta_bits(), size());
+
+        let options = spec_options(config, options, map_spec);
+
+        let mut writers = Writers::build(map_spec);
+        for &test in &config.tests {
+            let mut workload = workload::for_test(test, config);
+            workload::run(&mut writers, map_spec, options, &mut *workload);
+        }
+    });
+}
+
+// a synthetic trace: fill to TRACE_LOAD, then churn, probing a present and an absent
+// key after every remove/insert pair.
+fn record_trace(path: &str, options: MapOptions) {
+    const TRACE_LOAD: f64 = 0.9;
+    const CHURN: usize = 100_000;
+
+    let mut keys = KeySet::new(options.hashes.key_rng());
+    let mut ops = Vec::new();
+    while (keys.max as f64) < TRACE_LOAD * size() as f64 {
+        ops.push(Op::Insert(keys.push()));
+    }
+    for _ in 0..CHURN {
+        ops.push(Op::Remove(keys.pop()));
+        ops.push(Op::Insert(keys.push()));
+        ops.push(Op::Probe(keys.existing()));
+        ops.push(Op::Probe(keys.nonexisting()));
+    }
+
+    trace::write(path, &ops);
+}
+
+// replay a trace against two maps at once, asserting that they agree on the
+// membership of the key after every operation. as in `replay`, inserts into a full map
+// are dropped, and updates which don't complete are counted. a map which turned a key
+// away may then disagree with the other, so membership is no longer compared after that.
+//
+// each record holds the probes of lookups, and the probes and writes of updates, along
+// with the inserts dropped and the updates which didn't complete.
+fn replay_diff(ops: &[Op], map_specs: [MapSpec; 2], options: MapOptions) -> [Record; 2] {
+    let mut maps = map_specs.map(|map_spec| map_spec.build(options));
+    let mut records = map_specs.map(|_| Record {
+        load_factor: 0.0,
+        series: vec![
+            Series::new(Histogram::new(3).unwrap()),
+            Series::new(Histogram::new(3).unwrap()),
+            Series::new(Histogram::new_with_bounds(1, u64::MAX, 3).unwrap()),
+        ],
+        scalars: vec![0.0, 0.0],
+        bytes_per_key: [0.0; 4],
+    });
+
+    let mut comparing = true;
+    for (i, op) in ops.iter().enumerate() {
+        for (map, record) in maps.iter_mut().zip(records.iter_mut()) {
+            if matches!(op, Op::Insert(_)) && map.len() == map.capacity() {
+                record.scalars[0] += 1.0;
+                comparing = false;
+                continue;
+            }
+
+            let update = match *op {
+                Op::Insert(key) => map.insert(key),
+                Op::Remove(key) => map.remove(key),
+                Op::Probe(key) => {
+                    record.series[0].record(map.probe(key).probes as u64, 1);
+                    continue;
+                }
+            };
+
+            if !update.completed {
+                record.scalars[1] += 1.0;
+                comparing &= !matches!(op, Op::Insert(_));
+            }
+            record.series[1].record(update.total_probes as u64, 1);
+            record.series[2].record(update.total_writes as u64, 1);
+        }
+
+        if !comparing {
+            continue;
+        }
+        let [a, b] = &maps;
+        assert_eq!(
+            a.probe(op.key()).contained,
+            b.probe(op.key()).contained,
+            "{} and {} disagree on the membership of {} after op {i} ({op:?})",
+            map_specs[0].name(),
+            map_specs[1].name(),
+            op.key(),
+        );
+    }
+
+    for (map, record) in maps.iter().zip(records.iter_mut()) {
+        record.load_factor = map.load_factor();
+        record.bytes_per_key = map.memory_usage().per_key(map.len());
+    }
+
+    records
+}
+
+// drive a map through a trace. lookups are split by whether they found their key, and
+// inserts into a full map are dropped rather than made.
+fn replay(map: &mut dyn Map, ops: &[Op]) -> Record {
+    let mut hits = Series::new(Histogram::new(3).unwrap());
+    let mut misses = Series::new(Histogram::new(3).unwrap());
+    let mut probes = Series::new(Histogram::new(3).unwrap());
+    let mut writes = Series::new(Histogram::new_with_bounds(1, u64::MAX, 3).unwrap());
+    let mut evictions = Series::new(Histogram::new(3).unwrap());
+    let mut bucket_lines = Series::new(Histogram::new(3).unwrap());
+    let mut meta_probes = Series::new(Histogram::new(3).unwrap());
+    let mut dirty_pages = Series::new(Histogram::new(3).unwrap());
+    let mut latency = Series::new(Histogram::new(3).unwrap());
+    let mut sampler = Sampler::new(sample_rate());
+
+    let (mut dropped, mut incomplete) = (0, 0);
+    let mut costs = Costs::new();
+    for &op in ops {
+        if matches!(op, Op::Insert(_)) && map.len() == map.capacity() {
+            dropped += 1;
+            continue;
+        }
+
+        let weight = sampler.sample();
+        let (accesses, nanos) = match op {
+            Op::Probe(key) => {
+                let ((probe, accesses), nanos) = timed(|| count_accesses(|| map.probe(key)));
+                if let Some(weight) = weight {
+                    let probes = if probe.contained {
+                        &mut hits
+                    } else {
+                        &mut misses
+                    };
+                    probes.record(probe.probes as u64, weight);
+                }
+                (accesses, nanos)
+            }
+            Op::Insert(key) | Op::Remove(key) => {
+                let ((update, accesses), nanos) = timed(|| {
+                    count_accesses(|| match op {
+                        Op::Insert(_) => map.insert(key),
+                        _ => map.remove(key),
+                    })
+                });
+                incomplete += usize::from(!update.completed);
+                if let Some(weight) = weight {
+                    probes.record(update.total_probes as u64, weight);
+                    writes.record(update.total_writes as u64, weight);
+                    evictions.record(update.evictions as u64, weight);
+                    dirty_pages.record(accesses.dirty_pages as u64, weight);
+                }
+                (accesses, nanos)
+            }
+        };
+
+        costs.record(&accesses);
+        if let Some(weight) = weight {
+            bucket_lines.record(accesses.bucket_lines as u64, weight);
+            meta_probes.record(accesses.meta_probes as u64, weight);
+            if timing() {
+                latency.record(nanos, weight);
+            }
+        }
+    }
+
+    // the inserts dropped for want of room, the updates which didn't complete, and the
+    // mean cost of an operation under each cost model.
+    Record {
+        load_factor: map.load_factor(),
+        series: vec![
+            hits,
+            misses,
+            probes,
+            writes,
+            evictions,
+            bucket_lines,
+            meta_probes,
+            dirty_pages,
+            latency,
+        ],
+        scalars: vec![dropped as f64, incomplete as f64]
+            .into_iter()
+            .chain(costs.means())
+            .collect(),
+        bytes_per_key: map.memory_usage().per_key(map.len()),
+    }
+}
+
+// the ops of a trace, or else the error reading it, after which the run stops.
+fn read_trace(trace_path: &str) -> Vec<Op> {
+    trace::read(trace_path).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    })
+}
+
+// replay a trace against every selected map, writing a record per map, at the load
+// factor the trace left it at, to `out/replay_{name}.csv`.
+fn replay_trace(trace_path: &str, config: &Config, options: MapOptions) {
+    let ops = read_trace(trace_path);
+    let map_specs = selected_map_specs(&config.maps, &config.meta_bits);
+    let records: Vec<Record> = map_specs
+        .par_iter()
+        .map(|&map_spec| {
+            let mut map = map_spec.build(spec_options(config, options, map_spec));
+            replay(&mut *map, &ops)
+        })
+        .collect();
+
+    let mut writers: BTreeMap<&str, Writer<File>> = BTreeMap::new();
+    for (&map_spec, record) in map_specs.iter().zip(&records) {
+        let writer = writers.entry(map_spec.name()).or_insert_with(|| {
+            Writer::from_path(format!("out/replay_{}.csv", map_spec.name())).unwrap()
+        });
+        record.write(writer, map_spec);
+    }
+}
+
+fn diff(a: &str, b: &str, trace_path: &str, options: MapOptions) {
+    let map_specs = [a, b].map(|s| {
+        MapSpec::from_str(s).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        })
+    });
+    let ops = read_trace(trace_path);
+    let records = replay_diff(&ops, map_specs, options);
+
+    let mut writer = Writer::from_path(format!(
+        "out/diff_{}_{}.csv",
+        a.replace(':', "-"),
+        b.replace(':', "-")
+    ))
+    .unwrap();
+    for (map_spec, record) in map_specs.into_iter().zip(&records) {
+        let [lookups, updates, writes] = [0, 1, 2].map(|i| record.series[i].histogram.mean());
+        let [dropped, incomplete] = [0, 1].map(|i| record.scalars[i]);
+        println!(
+            "{s}: mean lookup probes {lookups:.2}, update probes {updates:.2}, writes {writes:.2}, \
+             {dropped} inserts dropped, {incomplete} updates incomplete",
+            s = map_spec.name(),
+        );
+        record.write(&mut writer, map_spec);
+    }
+}
+
+const VALIDATION_CAPACITY: usize = 1 << 12;
+const VALIDATION_OPS: usize = 1_000_000;
+
+// a seeded mix of inserts, removes and probes over a key range which settles at
+// around 60% load.
+fn validation_ops(seed: u64) -> Vec<Op> {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let key_range = (0.9 * VALIDATION_CAPACITY as f64) as u64;
+    (0..VALIDATION_OPS)
+        .map(|_| {
+            let key = rng.gen_range(0..key_range);
+            match rng.gen_range(0..4) {
+                0 | 1 => Op::Insert(key),
+                2 => Op::Remove(key),
+                _ => Op::Probe(key),
+            }
+        })
+        .collect()
+}
+
+// run every enabled scheme on the same seeded ops and hashers, checking after every
+// op that they all agree with a HashSet on the membership of the key. on the first
+// divergence the state of every map is dumped to `out/validate_{spec}.txt`.
+//
+// a map whose insert fails may have dropped a key, so it is retired from the run.
+fn validate(seed: u64, options: MapOptions) {
+    let options = MapOptions {
+        hashes: HashProvider::Seeded(seed),
+        ..options
+    };
+    // approximate maps disagree with a HashSet by design.
+    let map_specs: Vec<MapSpec> = enabled_map_specs()
+        .into_iter()
+        .filter(|map_spec| !matches!(map_spec, MapSpec::CuckooFilter(_)))
+        .collect();
+    let mut maps: Vec<Option<Box<dyn Map>>> = map_specs
+        .iter()
+        .map(|map_spec| Some(map_spec.build_with_capacity(VALIDATION_CAPACITY, options)))
+        .collect();
+    let mut oracle = HashSet::new();
+
+    for (i, op) in validation_ops(seed).into_iter().enumerate() {
+        for (map_spec, slot) in map_specs.iter().zip(maps.iter_mut()) {
+            let Some(map) = slot else {
+                continue;
+            };
+
+            match op {
+                Op::Insert(key) => {
+                    if !map.insert(key).completed {
+                        println!("{map_spec}: retired at op {i}, insert of {key} failed");
+                        *slot = None;
+                    }
+                }
+                Op::Remove(key) => {
+                    map.remove(key);
+                }
+                Op::Probe(_) => {}
+            }
+        }
+
+        match op {
+            Op::Insert(key) => oracle.insert(key),
+            Op::Remove(key) => oracle.remove(&key),
+            Op::Probe(_) => false,
+        };
+
+        let key = op.key();
+        let expected = oracle.contains(&key);
+        let diverged: Vec<MapSpec> = map_specs
+            .iter()
+            .zip(&maps)
+            .filter_map(|(&map_spec, map)| {
+                let map = map.as_ref()?;
+                (map.probe(key).contained != expected).then_some(map_spec)
+            })
+            .collect();
+        if diverged.is_empty() {
+            continue;
+        }
+
+        println!("divergence at op {i} ({op:?}): a HashSet has contained={expected} for key {key}, but these maps disagree:");
+        for map_spec in &diverged {
+            println!("  {map_spec}");
+        }
+        for (map_spec, map) in map_specs.iter().zip(&maps) {
+            if let Some(map) = map {
+                let path = format!(
+                    "out/validate_{}.txt",
+                    map_spec.to_string().replace(':', "-")
+                );
+                std::fs::write(&path, map.dump().join("\n")).unwrap();
+            }
+        }
+        println!("map states dumped to out/validate_*.txt");
+        std::process::exit(1);
+    }
+
+    let remaining = maps.iter().filter(|map| map.is_some()).count();
+    println!("{remaining} maps agreed on all {VALIDATION_OPS} ops (seed {seed})");
+}
+
+// p-values below this are reported as significant.
+const SIGNIFICANCE: f64 = 0.05;
+
+// (test, load factor, size, meta bits, series) -> recorded samples
+type Samples = BTreeMap<(String, String, String, String, String), Vec<u64>>;
+
+// the samples kept by a run, or else the error reading them. errors name the line
+// they're on.
+fn read_samples(path: &str) -> Result<Samples, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(path)
+        .map_err(|e| format!("can't read samples {path}: {e}"))?;
+
+    let mut samples = Samples::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| {
+            let line = e.position().map_or(0, |position| position.line());
+            format!("{path}:{line}: {e}")
+        })?;
+        let line = record.position().map_or(0, |position| position.line());
+        let value = record
+            .get(5)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("{path}:{line}: expected a sampled value"))?;
+        let key = (
+            record[0].to_string(),
+            record[1].to_string(),
+            record[2].to_string(),
+            record[3].to_string(),
+            record[4].to_string(),
+        );
+        samples.entry(key).or_default().push(value);
+    }
+
+    Ok(samples)
+}
+
+// compare the sampled metrics of two schemes, at every test, load factor, size and meta-bits
+// setting they share, with a Mann-Whitney U test. this tells whether a difference in
+// the means is real or sampling noise.
+fn compare(a: &str, b: &str) {
+    let [samples_a, samples_b] = [a, b].map(|name| {
+        read_samples(&format!("out/samples_{name}.csv")).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        })
+    });
+
+    let mut writer = Writer::from_path(format!("out/compare_{a}_{b}.csv")).unwrap();
+    let mut compared = 0;
+    let mut significant = 0;
+    for (key, values_a) in &samples_a {
+        let Some(values_b) = samples_b.get(key) else {
+            continue;
+        };
+
+        let (u, p) = stats::mann_whitney(values_a, values_b);
+        compared += 1;
+        if p < SIGNIFICANCE {
+            significant += 1;
+        }
+
+        let (test, load_factor, size, meta_bits, series) = key;
+        writer
+            .write_record([
+                test.clone(),
+                load_factor.clone(),
+                size.clone(),
+                meta_bits.clone(),
+                series.clone(),
+                format!("{:.2}", stats::mean(values_a)),
+                format!("{:.2}", stats::mean(values_b)),
+                format!("{u}"),
+                format!("{p:.4}"),
+                format!("{}", (p < SIGNIFICANCE) as u8),
+            ])
+            .unwrap();
+    }
+
+    writer.flush().unwrap();
+    if compared == 0 {
+        println!("no shared samples to compare; rerun the simulation with --reservoir-size or --bootstrap");
+    } else {
+        println!("{significant} of {compared} differences between {a} and {b} significant at p < {SIGNIFICANCE}");
+    }
+}
+
+// maps explained are small enough to print whole.
+const EXPLAIN_CAPACITY: usize = 32;
+const EXPLAIN_LOAD: f64 = 0.75;
+
+// run a single op on a small map, filled with keys `0..`, narrating every step.
+fn explain_op(map_spec: MapSpec, op: Op, load_factor: f64, options: MapOptions) {
+    let mut map = map_spec.build_with_capacity(EXPLAIN_CAPACITY, options);
+    let mut keys = KeySet::new(options.hashes.key_rng());
+    while map.load_factor() < load_factor && map.insert(keys.push()).completed {}
+
+    let print_map = |map: &dyn Map| {
+        for line in map.dump() {
+            println!("  {line}");
+        }
+    };
+    println!("{map_spec} holding {} keys:", map.len());
+    print_map(&*map);
+
+    println!("{} {}:", op.name(), op.key());
+    explain::set_enabled(true);
+    let update = match op {
+        Op::Insert(key) => map.insert(key),
+        Op::Remove(key) => map.remove(key),
+        Op::Probe(key) => {
+            let probe = map.probe(key);
+            explain::set_enabled(false);
+            println!("contained: {}, probes: {}", probe.contained, probe.probes);
+            return;
+        }
+    };
+    explain::set_enabled(false);
+
+    println!(
+        "completed: {}, probes: {}, writes: {}, evictions: {}, splits: {}",
+        update.completed, update.total_probes, update.total_writes, update.evictions, update.splits
+    );
+    println!("afterwards:");
+    print_map(&*map);
+}
+
+// the enabled specs, narrowed to the given map names and meta-bits settings. empty
+// lists select everything. maps without a meta-bits sweep ignore `meta_bits`.
+fn selected_map_specs(maps: &[String], meta_bits: &[usize]) -> Vec<MapSpec> {
+    let map_specs = enabled_map_specs();
+    for name in maps {
+        if !map_specs.iter().any(|s| s.name() == name) {
+            eprintln!("unknown map {name}");
+            std::process::exit(1);
+        }
+    }
+
+    map_specs
+        .into_iter()
+        .filter(|s| maps.is_empty() || maps.iter().any(|name| name == s.name()))
+        .filter(|s| {
+            meta_bits.is_empty()
+                || matches!(
+                    s,
+                    MapSpec::CacheLine | MapSpec::Chaining | MapSpec::TwoChoice
+                )
+                || meta_bits.contains(&s.meta_bits())
+        })
+        .collect()
+}
+
+/// Simulate hash-set schemes and write their probe, write and eviction statistics to out/.
+#[derive(Parser)]
+struct Cli {
+    /// Maps to simulate, e.g. `robinhood,cuckoo`. All of them by default.
+    #[arg(long, value_delimiter = ',')]
+    maps: Vec<String>,
+    /// Meta-bits settings to simulate, e.g. `0,2,8`. All of them by default.
+    #[arg(long, value_delimiter = ',')]
+    meta_bits: Vec<usize>,
+    /// Log2 of the number of buckets in every map. 20 by default.
+    #[arg(long)]
+    size: Option<u32>,
+    /// Log2 sizes to simulate one after another, e.g. `16,20,24`, in place of `--size`.
+    /// Every row is written with its map's size.
+    #[arg(long, value_delimiter = ',')]
+    sizes: Vec<u32>,
+    /// Seed every random choice of the simulation, so that it can be replayed exactly.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// How the keys inserted into every map are drawn. Sequential by default, which some
+    /// hashers find unusually easy.
+    #[arg(long, value_enum)]
+    keys: Option<KeyStrategy>,
+    /// Write every test's records as CSVs, merged per map once the run is done, or as
+    /// lines of out/results.jsonl, appended as they're taken. CSVs by default.
+    #[arg(long, value_enum)]
+    output: Option<OutputFormat>,
+    /// Which live key every removal takes: the oldest, the newest or any. The oldest by
+    /// default.
+    #[arg(long, value_enum)]
+    removals: Option<RemovalOrder>,
+    /// Bytes per page of bucket arrays, for counting the pages every insert and removal
+    /// dirties. 4096 by default.
+    #[arg(long)]
+    page_size: Option<usize>,
+    /// Split every churn run into windows of this many remove/insert pairs, writing a
+    /// row per window, to show drift over the run. Whole runs by default.
+    #[arg(long)]
+    churn_windows: Option<usize>,
+    /// Repeat every measurement this many times with different hashers and keys, and
+    /// write the mean of the trials' means per series with its 95% confidence interval.
+    /// Once by default.
+    #[arg(long)]
+    trials: Option<usize>,
+    /// Proportions of lookups, inserts and removes for the mixed test, as
+    /// `lookups/inserts/removes`, e.g. `80/10/10`. 90/5/5 by default.
+    #[arg(long)]
+    mix: Option<Mix>,
+    /// The fraction of the probe test's lookups which are for absent keys, e.g. 0.99 for
+    /// a workload of mostly misses. Half by default.
+    #[arg(long)]
+    misses: Option<f64>,
+    /// Keys per batch in the batch test, which compares batched inserts and lookups
+    /// against the same ones made a key at a time. 64 by default.
+    #[arg(long)]
+    batch_size: Option<usize>,
+    /// Record one in every this many operations into the histograms, weighted so that
+    /// counts still stand for every operation, to speed up very long runs. Every
+    /// operation by default.
+    #[arg(long)]
+    sample_rate: Option<u64>,
+    /// Keep up to this many raw values per series, written to out/samples_{map}.csv for
+    /// `compare`. None by default, or 10000 with `--bootstrap`.
+    #[arg(long)]
+    reservoir_size: Option<usize>,
+    /// Write bootstrap 95% confidence intervals of the mean and p99 of every series, from
+    /// the raw values kept.
+    #[arg(long)]
+    bootstrap: bool,
+    /// Time every operation, and write latency histograms in nanoseconds alongside the
+    /// others. Other tasks run in parallel, so compare latencies within a run.
+    #[arg(long)]
+    timed: bool,
+    /// Also write every histogram's full percentile distribution to out/hgrm, in
+    /// HdrHistogram's .hgrm format.
+    #[arg(long)]
+    hgrm: bool,
+    /// Advise the kernel to back bucket arrays with transparent hugepages, to control for
+    /// TLB effects in timed runs at large sizes. Linux only.
+    #[arg(long)]
+    hugepages: bool,
+    /// Remember the hash of every key, so that churn measurements aren't dominated by
+    /// rehashing the same keys. The churn test reports the cache's hit rate.
+    #[arg(long)]
+    hash_cache: bool,
+    /// Hash every map in the run with the same seeded hashers, so that comparisons
+    /// between schemes aren't skewed by lucky or unlucky hasher seeds.
+    #[arg(long)]
+    shared_hashes: bool,
+    /// Log every operation of the grow, probe, churn and mixed tests, and of the fills
+    /// before them, to out/ops_{map}.csv: its test, op, key, probes, writes, whether it
+    /// completed and the load factor after it. Large, so off by default.
+    #[arg(long)]
+    log_ops: bool,
+    /// Percentiles to write for every series, e.g. `50,99,99.9`. 50, 95, 99 and 99.9 by
+    /// default. Rank results with the percentiles they were written with.
+    #[arg(long, value_delimiter = ',')]
+    percentiles: Vec<f64>,
+    /// A backend to price every operation on, as
+    /// `name:random_read,sequential_read,page_write,meta_access`, e.g.
+    /// `hdd:8000,100,8000,0.005`. Repeat for several. The costs are in any one unit, and
+    /// reads and writes are of whole pages. RAM, SSD and NVMe estimates by default.
+    #[arg(long = "cost-model")]
+    cost_models: Vec<CostModel>,
+    /// A TOML experiment description. Options given on the command line override it.
+    #[arg(long)]
+    config: Option<String>,
+    /// Run a single op on a small map, narrating every step.
+    #[arg(long, num_args = 3..=4, value_names = ["SPEC", "OP", "KEY", "LOAD"])]
+    explain: Option<Vec<String>>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write a synthetic fill-and-churn trace.
+    Trace { path: String },
+    /// Replay a trace against every selected map and write a record of each.
+    Replay { trace_path: String },
+    /// Replay a trace against two specs and write their per-op differences.
+    Diff {
+        a: String,
+        b: String,
+        trace_path: String,
+    },
+    /// Compare the sampled metrics of two maps with a Mann-Whitney U test.
+    Compare { a: String, b: String },
+    /// Cross-check every map against a HashSet on random ops, seeded from `--seed` unless
+    /// given a seed of its own.
+    Validate { seed: Option<u64> },
+    /// Rank configurations in a results directory by a cost profile.
+    Rank {
+        dir: String,
+        meta_read: f64,
+        bucket_read: f64,
+        bucket_write: f64,
+        page_io: f64,
+    },
+    /// Explore a map interactively.
+    Repl,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let mut config = cli
+        .config
+        .as_deref()
+        .map(|path| {
+            config::read(path).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(1);
+            })
+        })
+        .unwrap_or_default();
+    if !cli.maps.is_empty() {
+        config.maps = cli.maps;
+    }
+    if !cli.meta_bits.is_empty() {
+        config.meta_bits = cli.meta_bits;
+    }
+    config.size = cli.size.unwrap_or(config.size);
+    if !cli.sizes.is_empty() {
+        config.sizes = cli.sizes;
+    }
+    config.seed = cli.seed.or(config.seed);
+    config.keys = cli.keys.unwrap_or(config.keys);
+    config.output = cli.output.unwrap_or(config.output);
+    config.removals = cli.removals.unwrap_or(config.removals);
+    config.page_size = cli.page_size.unwrap_or(config.page_size);
+    config.churn.window = cli.churn_windows.unwrap_or(config.churn.window);
+    config.trials = cli.trials.unwrap_or(config.trials);
+    config.mix = cli.mix.unwrap_or(config.mix);
+    config.misses = cli.misses.unwrap_or(config.misses);
+    config.batch_size = cli.batch_size.unwrap_or(config.batch_size);
+    config.sample_rate = cli.sample_rate.unwrap_or(config.sample_rate);
+    config.reservoir_size = cli.reservoir_size.or(config.reservoir_size);
+    config.bootstrap |= cli.bootstrap;
+    let reservoir_size = match config.reservoir_size {
+        Some(size) => size,
+        None if config.bootstrap => DEFAULT_RESERVOIR_SIZE,
+        None => 0,
+    };
+    if config.bootstrap && reservoir_size == 0 {
+        eprintln!("bootstrapping needs a reservoir size above 0");
+        std::process::exit(1);
+    }
+    if let Some(size) = std::iter::once(&config.size)
+        .chain(&config.sizes)
+        .find(|&&size| size >= MAX_SIZE)
+    {
+        eprintln!("invalid size {size}");
+        std::process::exit(1);
+    }
+    if config.page_size == 0 {
+        eprintln!("invalid page size 0");
+        std::process::exit(1);
+    }
+    if config.trials == 0 {
+        eprintln!("invalid trials 0");
+        std::process::exit(1);
+    }
+    if config.sample_rate == 0 {
+        eprintln!("invalid sample rate 0");
+        std::process::exit(1);
+    }
+    if config.batch_size == 0 {
+        eprintln!("invalid batch size 0");
+        std::process::exit(1);
+    }
+    if !(0.0..=1.0).contains(&config.misses) {
+        eprintln!("invalid misses {}", config.misses);
+        std::process::exit(1);
+    }
+    if !config.hot_set.is_valid() {
+        eprintln!("invalid hot set {:?}", config.hot_set);
+        std::process::exit(1);
+    }
+    if !config.sawtooth.is_valid() {
+        eprintln!("invalid sawtooth {:?}", config.sawtooth);
+        std::process::exit(1);
+    }
+    if !config.mix.is_valid() {
+        eprintln!("invalid mix {:?}", config.mix);
+        std::process::exit(1);
+    }
+    config.timed |= cli.timed;
+    config.hgrm |= cli.hgrm;
+    config.log_ops |= cli.log_ops;
+    config.hugepages |= cli.hugepages;
+    config.hash_cache |= cli.hash_cache;
+    config.shared_hashes |= cli.shared_hashes;
+    if !cli.percentiles.is_empty() {
+        config.percentiles = cli.percentiles;
+    }
+    if let Some(p) = config
+        .percentiles
+        .iter()
+        .find(|&&p| !(p > 0.0 && p <= 100.0))
+    {
+        eprintln!("invalid percentile {p}");
+        std::process::exit(1);
+    }
+    if !cli.cost_models.is_empty() {
+        config.cost_models = cli.cost_models;
+    }
+    KEY_STRATEGY.set(config.keys).unwrap();
+    OUTPUT_FORMAT.set(config.output).unwrap();
+    REMOVAL_ORDER.set(config.removals).unwrap();
+    PERCENTILES.set(config.percentiles.clone()).unwrap();
+    COST_MODELS.set(config.cost_models.clone()).unwrap();
+    SIZE.store(1 << config.size, Ordering::Relaxed);
+    TIMING.store(config.timed, Ordering::Relaxed);
+    EXPORT_HISTOGRAMS.store(config.hgrm, Ordering::Relaxed);
+    LOG_OPS.store(config.log_ops, Ordering::Relaxed);
+    TRIALS.store(config.trials, Ordering::Relaxed);
+    SAMPLE_RATE.store(config.sample_rate, Ordering::Relaxed);
+    RESERVOIR_SIZE.store(reservoir_size, Ordering::Relaxed);
+    BOOTSTRAP.store(config.bootstrap, Ordering::Relaxed);
+    let options = MapOptions::of_run(&config);
+
+    if let Some(explain) = cli.explain {
+        let map_spec = MapSpec::from_str(&explain[0]).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+        let Ok(key) = explain[2].parse() else {
+            eprintln!("invalid key {}", explain[2]);
+            std::process::exit(1);
+        };
+        let Some(op) = Op::new(&explain[1], key) else {
+            eprintln!("unknown op {}", explain[1]);
+            std::process::exit(1);
+        };
+        let load_factor = match explain.get(3).map(|l| l.parse()) {
+            None => EXPLAIN_LOAD,
+            Some(Ok(load_factor)) if (0.0..=1.0).contains(&load_factor) => load_factor,
+            Some(_) => {
+                eprintln!("invalid load {}", explain[3]);
+                std::process::exit(1);
+            }
+        };
+        explain_op(map_spec, op, load_factor, options);
+        return;
+    }
+
+    match cli.command {
+        None => simulate(&config, options),
+        Some(Command::Trace { path }) => record_trace(&path, options),
+        Some(Command::Diff { a, b, trace_path }) => {
+            std::fs::create_dir_all("out").unwrap();
+            diff(&a, &b, &trace_path, options)
+        }
+        Some(Command::Replay { trace_path }) => {
+            std::fs::create_dir_all("out").unwrap();
+            replay_trace(&trace_path, &config, options)
+        }
+        Some(Command::Compare { a, b }) => compare(&a, &b),
+        Some(Command::Validate { seed }) => {
+            std::fs::create_dir_all("out").unwrap();
+            let seed = seed.or(config.seed).unwrap_or_else(|| thread_rng().gen());
+            validate(seed, options)
+        }
+        Some(Command::Rank {
+            dir,
+            meta_read,
+            bucket_read,
+            bucket_write,
+            page_io,
+        }) => rank::run(
+            &dir,
+            CostProfile {
+                meta_read,
+                bucket_read,
+                bucket_write,
+                page_io,
+            },
+        ),
+        Some(Command::Repl) => repl::run(options),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const CAPACITY: usize = 128;
+    const KEYS: u64 = 100;
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (0..KEYS).prop_map(Op::Insert),
+            (0..KEYS).prop_map(Op::Remove),
+            (0..KEYS).prop_map(Op::Probe),
+        ]
+    }
+
+    fn map_specs() -> Vec<MapSpec> {
+        let mut map_specs = Vec::new();
+        for meta_bits in [0, 1, 2, 3, 4, 8, 16] {
+            for psl_source in [
+                PslSource::Rehash,
+                PslSource::Metadata,
+                PslSource::CachedHome,
+            ] {
+                map_specs.push(MapSpec::RobinHood(meta_bits, psl_source, Lookup::Linear));
+                map_specs.push(MapSpec::RobinHood(meta_bits, psl_source, Lookup::Smart));
+            }
+            for psl_bits in (1..=robinhood::MAX_SPLIT_PSL_BITS).filter(|&p| p < meta_bits) {
+                let psl_source = PslSource::Split(psl_bits);
+                map_specs.push(MapSpec::RobinHood(meta_bits, psl_source, Lookup::Linear));
+                map_specs.push(MapSpec::RobinHood(meta_bits, psl_source, Lookup::Smart));
+            }
+            map_specs.push(MapSpec::PagedRobinHood(meta_bits));
+            for d in [2, 3, 6] {
+                map_specs.push(MapSpec::Cuckoo(meta_bits, d, Insertion::RandomWalk));
+                map_specs.push(MapSpec::Cuckoo(meta_bits, d, Insertion::Bfs));
+            }
+            map_specs.push(MapSpec::CuckooAlternate(meta_bits));
+            map_specs.push(MapSpec::CuckooAging(meta_bits));
+            map_specs.push(MapSpec::BucketizedCuckoo(meta_bits));
+            map_specs.push(MapSpec::TriaProb(meta_bits));
+            map_specs.push(MapSpec::TriaProbTombstones(meta_bits));
+            map_specs.push(MapSpec::AdaptiveTriaProb(meta_bits));
+            map_specs.push(MapSpec::MaxRunTriaProb(meta_bits));
+            map_specs.push(MapSpec::TriaProbLayout(meta_bits, Layout::Lsb0));
+            map_specs.push(MapSpec::TriaProbLayout(meta_bits, Layout::Interleaved));
+            for percent in open_addressing::COMPACTION_PERCENTS {
+                map_specs.push(MapSpec::CompactingTriaProb(meta_bits, percent));
+            }
+            map_specs.push(MapSpec::BlockedTriaProb(meta_bits));
+            map_specs.push(MapSpec::QuadProb(meta_bits));
+            map_specs.push(MapSpec::DoubleHashing(meta_bits));
+            map_specs.push(MapSpec::SwissTable(meta_bits));
+            map_specs.push(MapSpec::Graveyard(meta_bits));
+            map_specs.push(MapSpec::Coalesced(meta_bits));
+            map_specs.push(MapSpec::Iceberg(meta_bits));
+            map_specs.push(MapSpec::OverflowArea(meta_bits));
+            map_specs.push(MapSpec::MultiLevel(meta_bits));
+            map_specs.push(MapSpec::Bidirectional(meta_bits));
+            map_specs.push(MapSpec::Extendible(meta_bits));
+            map_specs.push(MapSpec::LinearHashing(meta_bits));
+            for bloom_bits in bloom::BITS_PER_KEY {
+                map_specs.push(MapSpec::LinearHashingBloom(meta_bits, bloom_bits));
+            }
+        }
+        map_specs.push(MapSpec::CacheLine);
+        map_specs.push(MapSpec::Chaining);
+        map_specs.push(MapSpec::TwoChoice);
+        map_specs
+    }
+
+    // apply the ops to a fresh map, seeded with `seed`, and to a HashSet, checking that
+    // they agree after every op. stops at the first insert which doesn't complete, as the
+    // map may have dropped a key.
+    fn check_against_hash_set(
+        map_spec: MapSpec,
+        ops: &[Op],
+        seed: u64,
+    ) -> Result<(), TestCaseError> {
+        let name = map_spec.name();
+        let meta_bits = map_spec.meta_bits();
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(seed));
+        let mut oracle = HashSet::new();
+
+        for (i, op) in ops.iter().enumerate() {
+            let key = match *op {
+                Op::Insert(key) => {
+                    if !map.insert(key).completed {
+                        return Ok(());
+                    }
+                    oracle.insert(key);
+                    key
+                }
+                Op::Remove(key) => {
+                    map.remove(key);
+                    oracle.remove(&key);
+                    key
+                }
+                Op::Probe(key) => key,
+            };
+
+            prop_assert_eq!(
+                map.probe(key).contained,
+                oracle.contains(&key),
+                "{} ({} bits): membership of {} after op {}",
+                name,
+                meta_bits,
+                key,
+                i
+            );
+            prop_assert_eq!(
+                map.len(),
+                oracle.len(),
+                "{} ({} bits): len after op {}",
+                name,
+                meta_bits,
+                i
+            );
+        }
+
+        for key in 0..KEYS {
+            prop_assert_eq!(
+                map.probe(key).contained,
+                oracle.contains(&key),
+                "{} ({} bits): membership of {} at the end",
+                name,
+                meta_bits,
+                key
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn key_set_partitions_live_and_absent_keys() {
+        let mut keys = KeySet::default();
+        assert_eq!(keys.push(), 0);
+        assert_eq!(keys.existing(), 0);
+
+        keys.push();
+        assert_eq!(keys.pop(), 0);
+        assert_eq!(keys.existing(), 1);
+        assert!(keys.nonexisting() >= ABSENT_TAG);
+    }
+
+    #[test]
+    fn removal_orders_pop_live_keys_once() {
+        for order in [RemovalOrder::Fifo, RemovalOrder::Lifo, RemovalOrder::Random] {
+            let mut keys = KeySet {
+                order,
+                ..KeySet::default()
+            };
+            let mut live: HashSet<u64> = (0..100).map(|_| keys.push()).collect();
+            let first = keys.pop();
+            match order {
+                RemovalOrder::Fifo => assert_eq!(first, 0),
+                RemovalOrder::Lifo => assert_eq!(first, 99),
+                RemovalOrder::Random => {}
+            }
+            assert!(live.remove(&first));
+
+            // churn, then drain, popping every live key exactly once.
+            for _ in 0..1000 {
+                assert!(live.remove(&keys.pop()));
+                assert!(live.insert(keys.push()));
+            }
+            while !keys.is_empty() {
+                assert!(live.contains(&keys.existing()));
+                assert!(live.remove(&keys.pop()));
+            }
+            assert!(live.is_empty());
+        }
+    }
+
+    #[test]
+    fn trials_merge_by_position() {
+        let record = |load_factor: f64, values: &[u64]| {
+            let mut series = Series::new(Histogram::new(3).unwrap());
+            for &value in values {
+                series.record(value, 1);
+            }
+            Record {
+                load_factor,
+                series: vec![series],
+                scalars: vec![load_factor, f64::NAN],
+                bytes_per_key: [load_factor; 4],
+            }
+        };
+        let runs = vec![
+            vec![record(0.1, &[1, 3]), record(0.2, &[5])],
+            vec![record(0.3, &[4])],
+        ];
+
+        let merged = merge_trials(runs);
+        assert_eq!(merged.len(), 1);
+        let merged = &merged[0];
+        assert!((merged.load_factor - 0.2).abs() < 1e-9);
+        assert!((merged.scalars[0] - 0.2).abs() < 1e-9);
+        assert!(merged.scalars[1].is_nan());
+        assert_eq!(merged.series[0].histogram.len(), 3);
+        assert_eq!(merged.series[0].trial_means, [2.0, 4.0]);
+
+        // a single trial is left as it was.
+        let single = merge_trials(vec![vec![record(0.5, &[7])]]);
+        assert!(single[0].series[0].trial_means.is_empty());
+    }
+
+    #[test]
+    fn key_strategies_draw_distinct_present_keys() {
+        for strategy in [
+            KeyStrategy::Sequential,
+            KeyStrategy::RandomUnique,
+            KeyStrategy::Clustered,
+        ] {
+            let rng = Xoshiro256PlusPlus::seed_from_u64(1);
+            let mut keys = KeySet::with_strategy(rng, strategy);
+            let drawn: Vec<u64> = (0..10_000).map(|_| keys.push()).collect();
+            assert!(drawn.iter().all(|&key| key < ABSENT_TAG));
+            assert_eq!(drawn.iter().collect::<HashSet<_>>().len(), drawn.len());
+            assert_eq!(keys.pop(), drawn[0]);
+        }
+
+        let rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        let mut keys = KeySet::with_strategy(rng, KeyStrategy::Clustered);
+        let run: Vec<u64> = (0..1 << CLUSTER_BITS).map(|_| keys.push()).collect();
+        assert!(run.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        assert_ne!(keys.push(), run[run.len() - 1] + 1);
+    }
+
+    #[test]
+    fn clusters_wrap_around() {
+        let (o, x) = (true, false);
+        assert_eq!(cluster_lengths(&[o, x, o, o, x, x, o]), [2, 2]);
+        assert_eq!(cluster_lengths(&[x, o, x, o, o, o]), [1, 3]);
+        assert_eq!(cluster_lengths(&[o, o, o]), [3]);
+        assert!(cluster_lengths(&[x, x]).is_empty());
+    }
+
+    #[test]
+    fn displacements_cover_every_key() {
+        for map_spec in map_specs() {
+            let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+            for key in 0..KEYS / 2 {
+                map.insert(key);
+            }
+            if let Some(displacements) = map.displacements() {
+                assert_eq!(displacements.len(), map.len(), "{}", map_spec.name());
+            }
+        }
+    }
+
+    #[test]
+    fn buckets_hold_every_key() {
+        for map_spec in map_specs() {
+            let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+            for key in 0..KEYS / 2 {
+                map.insert(key);
+            }
+            if map.approximate() {
+                continue;
+            }
+
+            let [buckets, ..] = map.memory_usage().per_key(map.len());
+            assert!(
+                buckets >= 8.0,
+                "{}: {buckets} bucket bytes per key",
+                map_spec.name()
+            );
+        }
+    }
+
+    #[test]
+    fn fill_reports_the_first_failure() {
+        // two-choice cuckoo gives up well before it is full.
+        let map_spec = MapSpec::Cuckoo(0, 2, Insertion::RandomWalk);
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        let (first, max_load) = fill(&mut *map, &mut KeySet::default());
+        let first = first.unwrap();
+        assert_eq!(first.failure, Failure::Incomplete);
+        assert!(first.evictions > 0);
+        assert!(first.load_factor < 1.0);
+        assert_eq!(first.load_factor, max_load);
+
+        // linear probing never gives up, and no insert into so few buckets can pass
+        // the probe limit.
+        let map_spec = MapSpec::RobinHood(0, PslSource::Rehash, Lookup::Linear);
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        let (first, max_load) = fill(&mut *map, &mut KeySet::default());
+        assert!(first.is_none());
+        assert_eq!(max_load, 1.0);
+    }
+
+    #[test]
+    fn samples_of_each_size_are_kept_apart() {
+        let path = std::env::temp_dir().join("samples_sizes.csv");
+        std::fs::write(
+            &path,
+            "grow,0.50,10,4,0,3\ngrow,0.50,12,4,0,5\ngrow,0.50,12,4,0,6\n",
+        )
+        .unwrap();
+        let samples = read_samples(path.to_str().unwrap()).unwrap();
+        let values: Vec<&Vec<u64>> = samples.values().collect();
+        assert_eq!(values, [&vec![3], &vec![5, 6]]);
+
+        std::fs::write(&path, "grow,0.50,10,4,0,three\n").unwrap();
+        let error = read_samples(path.to_str().unwrap()).unwrap_err();
+        assert!(error.ends_with(":1: expected a sampled value"), "{error}");
+        assert!(read_samples("no/such/samples.csv").is_err());
+    }
+
+    #[test]
+    fn diff_counts_removes_of_absent_keys() {
+        let ops = [Op::Insert(1), Op::Remove(2), Op::Remove(1), Op::Probe(1)];
+        let map_specs = [MapSpec::TriaProb(4), MapSpec::DoubleHashing(4)];
+        let records = replay_diff(&ops, map_specs, MapOptions::seeded(1));
+        for record in records {
+            assert_eq!(record.scalars, [0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn replay_splits_lookups_and_drops_inserts_past_capacity() {
+        let map_spec = MapSpec::RobinHood(0, PslSource::Rehash, Lookup::Linear);
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        let mut ops: Vec<Op> = (0..CAPACITY as u64 + 5).map(Op::Insert).collect();
+        ops.extend([
+            Op::Probe(0),
+            Op::Probe(1),
+            Op::Probe(ABSENT_TAG),
+            Op::Remove(0),
+        ]);
+
+        let record = replay(&mut *map, &ops);
+        assert_eq!(record.series[0].histogram.len(), 2);
+        assert_eq!(record.series[1].histogram.len(), 1);
+        assert_eq!(record.series[2].histogram.len(), CAPACITY as u64 + 1);
+        assert_eq!(record.scalars[..2], [5.0, 0.0]);
+        assert_eq!(map.len(), CAPACITY - 1);
+    }
+
+    #[test]
+    fn batched_lookups_share_lines() {
+        let map_spec = MapSpec::RobinHood(0, PslSource::Rehash, Lookup::Linear);
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        let keys: Vec<u64> = (0..KEYS).collect();
+        map.insert_batch(&keys);
+
+        let (batch, batched) = count_accesses(|| map.probe_batch(&keys));
+        assert!(batch.probes.iter().all(|probe| probe.contained));
+        let single = keys
+            .iter()
+            .map(|&key| count_accesses(|| map.probe(key)).1)
+            .fold(Accesses::default(), |a, b| a + b);
+        assert!(batched.bucket_lines < single.bucket_lines);
+    }
+
+    #[test]
+    fn maps_build_at_their_min_capacity() {
+        for map_spec in enabled_map_specs() {
+            let mut map =
+                map_spec.build_with_capacity(map_spec.min_capacity(), MapOptions::seeded(1));
+            assert!(map.capacity() >= map_spec.min_capacity(), "{map_spec}");
+            assert!(!map.probe(1).contained, "{map_spec}");
+            map.insert(1);
+            map.remove(1);
+        }
+    }
+
+    // a map built with the hash cache hashes every key once, and one built without it
+    // on every lookup.
+    #[test]
+    fn hash_caches_are_set_per_map() {
+        let cached = MapOptions {
+            hash_cache: true,
+            ..MapOptions::seeded(1)
+        };
+        for map_spec in [MapSpec::Chaining, MapSpec::TriaProb(4)] {
+            let mut maps = [cached, MapOptions::seeded(1)]
+                .map(|options| map_spec.build_with_capacity(CAPACITY, options));
+            for map in &mut maps {
+                map.insert(1);
+                map.probe(1);
+                map.probe(1);
+            }
+            let [cached, uncached] = maps.map(|map| map.hash_stats());
+            assert_eq!(cached.invocations, 1, "{map_spec}");
+            assert!(cached.cache_hits >= 2, "{map_spec}");
+            assert_eq!(uncached.cache_hits, 0, "{map_spec}");
+            assert!(uncached.invocations > 1, "{map_spec}");
+        }
+    }
+
+    #[test]
+    fn growing_maps_count_bucket_lines() {
+        for map_spec in [MapSpec::Extendible(0), MapSpec::LinearHashing(0)] {
+            let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+            for key in 0..KEYS {
+                map.insert(key);
+            }
+            let (probe, accesses) = count_accesses(|| map.probe(0));
+            assert!(probe.contained);
+            assert!(accesses.bucket_lines > 0, "{map_spec}");
+        }
+    }
+
+    #[test]
+    fn linear_hashing_inserts_dirty_pages() {
+        let map_spec = MapSpec::from_str("linearhashing:0").unwrap();
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        for key in 0..KEYS {
+            let (update, accesses) = count_accesses(|| map.insert(key));
+            assert!(update.completed);
+            assert!(accesses.dirty_pages > 0);
+        }
+    }
+
+    #[test]
+    fn growing_maps_pay_for_their_pages() {
+        for map_spec in [MapSpec::Extendible(4), MapSpec::LinearHashing(4)] {
+            let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+            let mut costs = Costs::new();
+            for key in 0..KEYS {
+                costs.record(&count_accesses(|| map.insert(key)).1);
+            }
+            for (model, mean) in cost_models().iter().zip(costs.means()) {
+                assert!(mean >= model.random_read + model.page_write, "{map_spec}");
+            }
+        }
+    }
+
+    #[test]
+    fn batches_stop_short_of_a_full_map() {
+        let map_spec = MapSpec::RobinHood(8, PslSource::Rehash, Lookup::Linear);
+        let build = || map_spec.build_with_capacity(1 << 10, MapOptions::seeded(1));
+        let (mut batched, mut single) = (build(), build());
+        let rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        let mut keys = KeySet::with_strategy(rng, KeyStrategy::Sequential);
+        while batched.load_factor() < 0.9 {
+            let key = keys.push();
+            batched.insert(key);
+            single.insert(key);
+        }
+
+        let record = batch(&mut *batched, &mut *single, &mut keys, 64, 10_000, 0.98);
+        assert!(batched.load_factor() <= 0.98);
+        assert!(single.load_factor() <= 0.98);
+        assert!(record.scalars[0] <= 1.0);
+
+        // a batch past what a map can hold stops at its first failure.
+        let mut map = MapSpec::Cuckoo(0, 2, Insertion::RandomWalk)
+            .build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        let update = map.insert_batch(&(0..2 * CAPACITY as u64).collect::<Vec<_>>());
+        assert!(update.updates.len() < 2 * CAPACITY);
+        assert!(failure(update.updates.last().unwrap()).is_some());
+    }
+
+    #[test]
+    fn mixed_holds_the_load_short_of_a_full_map() {
+        let map_spec = MapSpec::RobinHood(1, PslSource::Rehash, Lookup::Linear);
+        let mut map = map_spec.build_with_capacity(1 << 8, MapOptions::seeded(1));
+        let rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        let mut keys = KeySet::with_strategy(rng, KeyStrategy::Sequential);
+        while map.load_factor() < 0.98 {
+            map.insert(keys.push());
+        }
+        let start = map.len();
+
+        let mut ops = Writer::from_path(std::env::temp_dir().join("mixed_ops.csv")).unwrap();
+        let mut log = OpLog {
+            writer: &mut ops,
+            test: "mixed",
+        };
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        let mix = Mix {
+            lookups: 0.0,
+            inserts: 1.0,
+            removes: 0.0,
+        };
+        mixed(&mut *map, &mut keys, &mut rng, mix, 10_000, &mut log);
+        assert!(map.len() < map.capacity());
+        assert!(map.len().abs_diff(start) <= 1);
+    }
+
+    #[test]
+    fn removes_of_absent_keys_dont_complete() {
+        for map_spec in [MapSpec::TriaProb(4), MapSpec::CacheLine] {
+            let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+            map.insert(1);
+            assert!(!map.remove(2).completed, "{map_spec}");
+            assert!(map.remove(1).completed, "{map_spec}");
+            assert!(!map.remove(1).completed, "{map_spec}");
+        }
+    }
+
+    #[test]
+    fn coalesced_counts_its_rebuilds() {
+        let mut map = MapSpec::Coalesced(4).build_with_capacity(16, MapOptions::seeded(1));
+        let mut keys = 0..;
+        let mut removed = 0..;
+        loop {
+            let len = map.len();
+            let update = map.insert(keys.next().unwrap());
+            assert!(update.completed);
+            if update.rebuilds > 0 {
+                assert_eq!(update.rebuilds, 1);
+                assert_eq!(update.rebuild_moves, len);
+                break;
+            }
+            if map.len() > 8 {
+                assert!(map.remove(removed.next().unwrap()).completed);
+            }
+        }
+    }
+
+    #[test]
+    fn removals_leave_tombstones_behind() {
+        let map_spec = MapSpec::TriaProbTombstones(4);
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        for key in 0..KEYS / 2 {
+            map.insert(key);
+        }
+        assert_eq!(tombstone_fraction(&*map), 0.0);
+        for key in 0..KEYS / 4 {
+            map.remove(key);
+        }
+        assert_eq!(
+            tombstone_fraction(&*map),
+            (KEYS / 4) as f64 / CAPACITY as f64
+        );
+
+        let map_spec = MapSpec::RobinHood(0, PslSource::Rehash, Lookup::Linear);
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        assert!(mean_displacement(&*map).is_nan());
+        for key in 0..KEYS {
+            map.insert(key);
+        }
+        assert!(mean_displacement(&*map) > 0.0);
+
+        let map_spec = MapSpec::Chaining;
+        let map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        assert!(tombstone_fraction(&*map).is_nan());
+        assert!(mean_displacement(&*map).is_nan());
+    }
+
+    #[test]
+    fn compaction_clears_tombstones() {
+        let map_spec = MapSpec::CompactingTriaProb(4, 10);
+        let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(1));
+        let mut keys = KeySet::default();
+        while map.load_factor() < 0.5 {
+            map.insert(keys.push());
+        }
+
+        // a tenth of the buckets hold tombstones after every 13 removals.
+        let (mut rebuilds, mut rebuild_moves) = (0, 0);
+        for _ in 0..CAPACITY / 4 {
+            let update = map.remove(keys.pop());
+            rebuilds += update.rebuilds;
+            rebuild_moves += update.rebuild_moves;
+        }
+        // each moves the keys left, 64 - 13 and then 64 - 26.
+        assert_eq!(rebuilds, 2);
+        assert_eq!(rebuild_moves, 51 + 38);
+        for _ in 0..CAPACITY / 4 {
+            assert!(map.probe(keys.existing()).contained);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn maps_agree_with_hash_set(
+            ops in prop::collection::vec(op(), 1..400),
+            seed in any::<u64>(),
+        ) {
+            for map_spec in map_specs() {
+                check_against_hash_set(map_spec, &ops, seed)?;
+            }
+        }
+    }
+
+    // measured probe counts against closed-form expectations, at moderate load on
+    // maps large enough for the asymptotics to hold.
+    mod theory {
+        use super::*;
+
+        const CAPACITY: usize = 1 << 14;
+        const TOLERANCE: f64 = 0.1;
+        // every map is built with the same hashers, so that the measurements are the same
+        // from run to run.
+        const SEED: u64 = 1;
+
+        // mean probes over every present key, and over as many absent keys.
+        fn mean_probes(map_spec: MapSpec, load_factor: f64) -> (f64, f64) {
+            let mut map = map_spec.build_with_capacity(CAPACITY, MapOptions::seeded(SEED));
+            let len = (load_factor * CAPACITY as f64) as u64;
+            for key in 0..len {
+                assert!(map.insert(key).completed);
+            }
+
+            let mean = |keys: std::ops::Range<u64>| {
+                keys.map(|key| map.probe(key).probes as f64).sum::<f64>() / len as f64
+            };
+            (mean(0..len), mean(len..2 * len))
+        }
+
+        fn assert_close(name: &str, measured: f64, expected: f64, tolerance: f64) {
+            assert!(
+                (measured - expected).abs() <= tolerance * expected,
+                "{name}: measured {measured:.3}, expected {expected:.3}"
+            );
+        }
+
+        // robin hood reorders keys but not their total displacement, so successful
+        // searches cost the same as under linear probing: (1 + 1/(1-a)) / 2.
+        #[test]
+        fn robin_hood_matches_linear_probing() {
+            for load_factor in [0.5, 0.7, 0.8] {
+                let (present, _) = mean_probes(
+                    MapSpec::RobinHood(0, PslSource::Rehash, Lookup::Linear),
+                    load_factor,
+                );
+                let expected = (1.0 + 1.0 / (1.0 - load_factor)) / 2.0;
+                assert_close("robinhood present", present, expected, TOLERANCE);
+            }
+        }
+
+        // triangular probing suffers secondary clustering only, for which Knuth gives
+        // 1 - ln(1-a) - a/2 probes for hits and 1/(1-a) - a - ln(1-a) for misses.
+        #[test]
+        fn triangular_probing_matches_secondary_clustering() {
+            for load_factor in [0.5, 0.7, 0.8] {
+                let (present, absent) = mean_probes(MapSpec::TriaProb(0), load_factor);
+                let ln = (1.0 - load_factor).ln();
+                assert_close(
+                    "triaprob present",
+                    present,
+                    1.0 - ln - load_factor / 2.0,
+                    TOLERANCE,
+                );
+                assert_close(
+                    "triaprob absent",
+                    absent,
+                    1.0 / (1.0 - load_factor) - load_factor - ln,
+                    TOLERANCE,
+                );
+            }
+        }
+
+        // double hashing behaves like uniform hashing: -ln(1-a)/a probes for hits and
+        // 1/(1-a) for misses.
+        #[test]
+        fn double_hashing_matches_uniform_hashing() {
+            for load_factor in [0.5, 0.7, 0.8] {
+                let (present, absent) = mean_probes(MapSpec::DoubleHashing(0), load_factor);
+                let ln = (1.0 - load_factor).ln();
+                assert_close(
+                    "doublehashing present",
+                    present,
+                    -ln / load_factor,
+                    TOLERANCE,
+                );
+                assert_close(
+                    "doublehashing absent",
+                    absent,
+                    1.0 / (1.0 - load_factor),
+                    TOLERANCE,
+                );
+            }
+        }
+
+        // the maps' own predictions account for the buckets fingerprints rule out.
+        #[test]
+        fn predictions_match_with_metadata() {
+            for map_spec in [
+                MapSpec::TriaProb(2),
+                MapSpec::TriaProb(4),
+                MapSpec::DoubleHashing(4),
+            ] {
+                for load_factor in [0.5, 0.7] {
+                    let (present, absent) = mean_probes(map_spec, load_factor);
+                    let expected = map_spec
+                        .build_with_capacity(CAPACITY, MapOptions::seeded(SEED))
+                        .expected_probes(load_factor);
+                    let name = map_spec.name();
+                    assert_close(name, present, expected.present.unwrap(), TOLERANCE);
+                    assert_close(name, absent, expected.absent.unwrap(), 2.0 * TOLERANCE);
+                }
+            }
+        }
+
+        // without metadata a miss reads every candidate bucket. with `b` bits each
+        // candidate is read only when it is full (probability a) and its b-1 hash bits
+        // collide, which under uniform hashing happens with probability 2^-(b-1).
+        #[test]
+        fn cuckoo_misses_match_uniform_hashing() {
+            let load_factor = 0.4;
+            for (map_spec, candidates) in [
+                (MapSpec::Cuckoo(0, 2, Insertion::RandomWalk), 2.0),
+                (MapSpec::Cuckoo(0, 3, Insertion::RandomWalk), 3.0),
+            ] {
+                let (_, absent) = mean_probes(map_spec, load_factor);
+                assert_eq!(absent, candidates, "{}", map_spec.name());
+            }
+
+            for meta_bits in [2, 4] {
+                for (map_spec, candidates) in [
+                    (MapSpec::Cuckoo(meta_bits, 2, Insertion::RandomWalk), 2.0),
+                    (MapSpec::Cuckoo(meta_bits, 3, Insertion::RandomWalk), 3.0),
+                ] {
+                    let (_, absent) = mean_probes(map_spec, load_factor);
+                    let expected = candidates * load_factor / (1 << (meta_bits - 1)) as f64;
+                    assert_close(map_spec.name(), absent, expected, 2.0 * TOLERANCE);
+                }
+            }
+        }
     }
 }
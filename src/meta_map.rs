@@ -1,89 +1,383 @@
 use bitvec::prelude::*;
+use std::cell::Cell;
+use std::iter::Sum;
+use std::ops::{Add, Range, Sub};
+
+// buckets scanned together by the group hints.
+pub const GROUP_SLOTS: usize = 16;
+
+// metadata is laid out in 64-byte cache lines, each holding the fields of a run of
+// consecutive buckets. a field never straddles two lines: widths which don't divide
+// the line leave its last bits unused.
+pub const CACHE_LINE_BITS: usize = 512;
+
+// the widest field a bucket's metadata may have. fields are loaded as single words.
+pub const MAX_BITS: usize = 32;
+
+thread_local! {
+    // the operation being counted by `count_accesses`, or 0 outside of it. lines are
+    // stamped with the operation which touched them last, so that each is counted once
+    // per operation.
+    static OPERATION: Cell<u64> = const { Cell::new(0) };
+    static LAST_OPERATION: Cell<u64> = const { Cell::new(0) };
+    static ACCESSES: Cell<Accesses> = const {
+        Cell::new(Accesses {
+            bucket_lines: 0,
+            meta_lines: 0,
+            meta_probes: 0,
+            dirty_pages: 0,
+            random_page_reads: 0,
+            sequential_page_reads: 0,
+            recent_lines: 0,
+        })
+    };
+}
+
+// memory accessed by an operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Accesses {
+    // distinct cache lines of bucket arrays.
+    pub bucket_lines: usize,
+    // distinct cache lines of metamaps.
+    pub meta_lines: usize,
+    // reads of metamap fields, or of whole groups of them at once. reading the same
+    // fields again straight after counts once, as they'd be kept in a register.
+    pub meta_probes: usize,
+    // distinct pages of bucket arrays written to.
+    pub dirty_pages: usize,
+    // distinct pages of bucket arrays read, split by whether the page before or after
+    // was the last one the operation read in the same array.
+    pub random_page_reads: usize,
+    pub sequential_page_reads: usize,
+    // distinct cache lines, of bucket arrays and metamaps, which one of the last
+    // RECENT_OPERATIONS operations touched too, so would likely still be cached.
+    pub recent_lines: usize,
+}
+
+// the accesses of two operations, counted separately.
+impl Add for Accesses {
+    type Output = Accesses;
+
+    fn add(self, other: Accesses) -> Accesses {
+        Accesses {
+            bucket_lines: self.bucket_lines + other.bucket_lines,
+            meta_lines: self.meta_lines + other.meta_lines,
+            meta_probes: self.meta_probes + other.meta_probes,
+            dirty_pages: self.dirty_pages + other.dirty_pages,
+            random_page_reads: self.random_page_reads + other.random_page_reads,
+            sequential_page_reads: self.sequential_page_reads + other.sequential_page_reads,
+            recent_lines: self.recent_lines + other.recent_lines,
+        }
+    }
+}
+
+// roughly how many operations' lines a small cache keeps, at a few lines each.
+pub const RECENT_OPERATIONS: u64 = 256;
+
+// the operation being counted, or 0 outside of `count_accesses`.
+pub fn operation() -> u64 {
+    OPERATION.get()
+}
+
+// run `f`, along with the memory it accessed across every bucket array and metamap.
+pub fn count_accesses<T>(f: impl FnOnce() -> T) -> (T, Accesses) {
+    let operation = LAST_OPERATION.get() + 1;
+    LAST_OPERATION.set(operation);
+    OPERATION.set(operation);
+    ACCESSES.set(Accesses::default());
+    let result = f();
+    OPERATION.set(0);
+    (result, ACCESSES.get())
+}
+
+// note a cache line or page as touched, given the stamp of the operation which touched
+// it last. `tally` counts it, the first time the operation being counted touches it.
+pub fn touch_line(stamp: &Cell<u64>, tally: fn(&mut Accesses)) {
+    touch(stamp, tally, false);
+}
+
+// as `touch_line`, for cache lines, which also count as recent when a recent operation
+// touched them.
+pub fn touch_cache_line(stamp: &Cell<u64>, tally: fn(&mut Accesses)) {
+    touch(stamp, tally, true);
+}
+
+fn touch(stamp: &Cell<u64>, tally: fn(&mut Accesses), cache_line: bool) {
+    let operation = OPERATION.get();
+    if operation == 0 {
+        return;
+    }
+    let last = stamp.replace(operation);
+    if last == operation {
+        return;
+    }
+
+    let mut accesses = ACCESSES.get();
+    tally(&mut accesses);
+    if cache_line && last != 0 && operation - last <= RECENT_OPERATIONS {
+        accesses.recent_lines += 1;
+    }
+    ACCESSES.set(accesses);
+}
+
+// how the fields of a line are arranged in its words. every layout keeps a bucket's
+// field in the same cache line, so they differ only in the work of reading fields.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    // fields in bucket order from the most significant bit of each word down.
+    Msb0,
+    // fields in bucket order from the least significant bit of each word up.
+    Lsb0,
+    // the FULL bits of a line's buckets packed densely at its start, and the rest of
+    // their fields after them, so that emptiness is read from a single word.
+    Interleaved,
+}
 
 pub struct MetaMap {
+    buckets: usize,
     bits: usize,
     bitvec: BitVec<u64, Msb0>,
+    layout: Layout,
+    // with a split layout, the bits after the FULL bit hold a truncated PSL of this
+    // many bits followed by the high bits of the hash.
+    psl_bits: Option<usize>,
+    // an optional bit per bucket marking tombstones, beside the metadata bits, so that
+    // tombstones are visible at any width.
+    tombstones: Option<BitVec<u64, Msb0>>,
+    // an optional bit per bucket, beside the metadata bits, set once a key whose primary
+    // bucket it is was placed in one of its alternate buckets. never cleared, as other
+    // keys may have been displaced from the same bucket.
+    alternates: Option<BitVec<u64, Msb0>>,
+    // an optional referenced bit per bucket, beside the metadata bits, set when a lookup
+    // finds the bucket's key.
+    aging: Option<Aging>,
+    // with a counting layout, every field is a saturating counter of the longest run of
+    // probes made by a key whose home is the bucket, and gives no other hints.
+    counting: bool,
+    // whether word-aligned widths take the word path. only cleared by tests, to check
+    // it against the bitvec path.
+    word_path: bool,
+    hint_stats: Cell<HintStats>,
+    // the last operation to touch each cache line.
+    line_stamps: Vec<Cell<u64>>,
+    // the operation and buckets of the last read of fields.
+    last_read: Cell<(u64, usize, usize)>,
+}
+
+// referenced bits, and the references made since they were last cleared.
+struct Aging {
+    referenced: Vec<Cell<bool>>,
+    references: Cell<usize>,
+}
+
+// how well metadata filtered the buckets a map checked for a key.
+#[derive(Clone, Copy, Default)]
+pub struct HintStats {
+    // buckets which metadata didn't rule out, so were read.
+    pub matches: usize,
+    // of those, the buckets which didn't hold the key. reported by the maps.
+    pub false_matches: usize,
+    // buckets which metadata ruled out.
+    pub rejections: usize,
+}
+
+impl HintStats {
+    // the fraction of buckets not holding the key which metadata failed to rule out.
+    // 0 if no bucket was checked.
+    pub fn false_positive_rate(&self) -> f64 {
+        let negatives = self.false_matches + self.rejections;
+        if negatives == 0 {
+            return 0.0;
+        }
+
+        self.false_matches as f64 / negatives as f64
+    }
+}
+
+impl Sub for HintStats {
+    type Output = HintStats;
+
+    fn sub(self, other: HintStats) -> HintStats {
+        HintStats {
+            matches: self.matches - other.matches,
+            false_matches: self.false_matches - other.false_matches,
+            rejections: self.rejections - other.rejections,
+        }
+    }
+}
+
+impl Sum for HintStats {
+    fn sum<I: Iterator<Item = HintStats>>(iter: I) -> HintStats {
+        iter.fold(HintStats::default(), |a, b| HintStats {
+            matches: a.matches + b.matches,
+            false_matches: a.false_matches + b.false_matches,
+            rejections: a.rejections + b.rejections,
+        })
+    }
 }
 
 pub enum Metadata {
     Hash(u64),
     Psl(usize),
+    // only with a split layout.
+    PslAndHash { psl: usize, hash: u64 },
+}
+
+// what metadata says about a bucket, for a key with some hash.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hint {
+    Empty,
+    Tombstone,
+    // full, but not with the key.
+    NotMatch,
+    // may hold the key.
+    Candidate,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum PslHint {
     Exact(usize),
     AtLeast(usize),
 }
 
+// with the `check-hints` feature, and in tests, assert that a metadata hint used to
+// skip a bucket was right. `hint_correct` is only evaluated when checking.
+//
+// a wrong hint silently produces wrong membership answers, which the statistics
+// would never reveal.
+pub fn check_hint(hint_correct: impl FnOnce() -> bool, bucket: usize) {
+    if cfg!(any(test, feature = "check-hints")) {
+        assert!(hint_correct(), "wrong metadata hint for bucket {bucket}");
+    }
+}
+
 // EMPTY: `0 | 0s`
 // TOMBSTONE: `0 | 1s` except if bits == 1 - then `1`.
-// FULL: `1 | metadata bits`
+// FULL: `1 | metadata bits`, or `1 | psl bits | hash bits` with a split layout.
 
 impl MetaMap {
     pub fn new(buckets: usize, bits_per_bucket: usize) -> Self {
+        assert!(bits_per_bucket <= MAX_BITS);
         MetaMap {
+            buckets,
             bits: bits_per_bucket,
-            bitvec: bitvec![u64, Msb0; 0; buckets * bits_per_bucket],
+            bitvec: bitvec![u64, Msb0; 0; lines(buckets, bits_per_bucket) * CACHE_LINE_BITS],
+            layout: Layout::Msb0,
+            psl_bits: None,
+            tombstones: None,
+            alternates: None,
+            aging: None,
+            counting: false,
+            word_path: true,
+            hint_stats: Cell::new(HintStats::default()),
+            line_stamps: vec![Cell::new(0); lines(buckets, bits_per_bucket)],
+            last_read: Cell::new((0, 0, 0)),
         }
     }
 
-    pub fn set_full(&mut self, bucket: usize, metadata: Metadata) {
-        if self.bits == 0 {
-            return;
+    // a metamap whose fields are arranged by `layout`.
+    pub fn with_layout(buckets: usize, bits_per_bucket: usize, layout: Layout) -> Self {
+        MetaMap {
+            layout,
+            ..MetaMap::new(buckets, bits_per_bucket)
         }
-        if self.bits == 1 {
-            self.bitvec.set(bucket, true);
-            return;
+    }
+
+    // a metamap with a tombstone bit per bucket on top of `bits_per_bucket`.
+    pub fn with_tombstones(buckets: usize, bits_per_bucket: usize) -> Self {
+        MetaMap {
+            tombstones: Some(bitvec![u64, Msb0; 0; buckets]),
+            ..MetaMap::new(buckets, bits_per_bucket)
         }
+    }
 
-        let bits_remaining = self.bits - 1;
-        let start = bucket * self.bits;
-        let end = start + self.bits;
+    // a metamap with an alternate bit per bucket on top of `bits_per_bucket`.
+    pub fn with_alternates(buckets: usize, bits_per_bucket: usize) -> Self {
+        MetaMap {
+            alternates: Some(bitvec![u64, Msb0; 0; buckets]),
+            ..MetaMap::new(buckets, bits_per_bucket)
+        }
+    }
 
-        self.bitvec.set(start, true);
+    // a metamap with a referenced bit per bucket on top of `bits_per_bucket`.
+    pub fn with_aging(buckets: usize, bits_per_bucket: usize) -> Self {
+        MetaMap {
+            aging: Some(Aging {
+                referenced: vec![Cell::new(false); buckets],
+                references: Cell::new(0),
+            }),
+            ..MetaMap::new(buckets, bits_per_bucket)
+        }
+    }
 
-        match metadata {
-            Metadata::Hash(raw_hash) => {
-                let high_bits = &raw_hash.view_bits::<Msb0>()[..bits_remaining];
-                self.bitvec[start + 1..end].copy_from_bitslice(high_bits);
-            }
-            Metadata::Psl(psl) => {
-                let truncated = std::cmp::min(psl, 1 << bits_remaining) - 1;
-                let psl_bits = truncated.view_bits::<Msb0>();
-                let psl_start = psl_bits.len() - bits_remaining;
-                self.bitvec[start + 1..end].clone_from_bitslice(&psl_bits[psl_start..]);
-            }
+    // a metamap whose fields count the longest probe runs from each home bucket.
+    pub fn counting(buckets: usize, bits_per_bucket: usize) -> Self {
+        MetaMap {
+            counting: true,
+            ..MetaMap::new(buckets, bits_per_bucket)
         }
     }
 
-    pub fn set_empty(&mut self, bucket: usize) {
-        if self.bits == 0 {
-            return;
+    // a metamap whose full buckets hold both a PSL of `psl_bits` bits and a hash
+    // fingerprint in the bits left over.
+    pub fn split(buckets: usize, bits_per_bucket: usize, psl_bits: usize) -> Self {
+        assert!(psl_bits < std::cmp::max(1, bits_per_bucket));
+        MetaMap {
+            psl_bits: Some(psl_bits),
+            ..MetaMap::new(buckets, bits_per_bucket)
         }
-        if self.bits == 1 {
-            self.bitvec.set(bucket, false);
+    }
+
+    pub fn set_full(&mut self, bucket: usize, metadata: Metadata) {
+        self.set_tombstone_bit(bucket, false);
+        if self.bits == 0 || self.counting {
             return;
         }
+        self.touch(bucket..bucket + 1);
+        let bits_remaining = self.bits - 1;
+        let rest = match metadata {
+            Metadata::Hash(raw_hash) => self.hash_field(raw_hash),
+            Metadata::Psl(psl) => encode_psl(psl, bits_remaining),
+            Metadata::PslAndHash { psl, hash } => {
+                let psl_bits = self
+                    .psl_bits
+                    .expect("psl and hash metadata needs a split layout");
+                let hash_bits = bits_remaining - psl_bits;
+                let hash_field = hash.checked_shr(64 - hash_bits as u32).unwrap_or(0);
+                (encode_psl(psl, psl_bits) << hash_bits) | hash_field
+            }
+        };
+        self.store_any(bucket, (1 << bits_remaining) | rest);
+    }
 
-        self.bitvec.set(bucket * self.bits, false);
-        for i in 1..self.bits {
-            self.bitvec.set(bucket * self.bits + i, false);
+    pub fn set_empty(&mut self, bucket: usize) {
+        self.set_tombstone_bit(bucket, false);
+        if self.bits == 0 || self.counting {
+            return;
         }
+        self.touch(bucket..bucket + 1);
+        self.store_any(bucket, 0);
     }
 
     pub fn set_tombstone(&mut self, bucket: usize) {
-        if self.bits == 0 {
+        self.set_tombstone_bit(bucket, true);
+        if self.bits == 0 || self.counting {
             return;
         }
+        self.touch(bucket..bucket + 1);
         if self.bits == 1 {
-            self.bitvec.set(bucket, true);
+            self.store_any(bucket, 1);
             return;
         }
+        self.store_any(bucket, (1 << (self.bits - 1)) - 1);
+    }
 
-        self.bitvec.set(bucket * self.bits, false);
-        for i in 1..self.bits {
-            self.bitvec.set(bucket * self.bits + i, true);
+    // the bucket's metadata as a bit string, for debugging dumps.
+    pub fn describe(&self, bucket: usize) -> String {
+        if self.bits == 0 {
+            return String::new();
         }
+        format!("{:0width$b}", self.load_any(bucket), width = self.bits)
     }
 
     // Get the number of bits in the meta-map.
@@ -91,74 +385,993 @@ impl MetaMap {
         self.bits
     }
 
-    // true means definitely empty.
-    pub fn hint_empty(&self, bucket: usize) -> bool {
+    // the bits per bucket kept beside the metadata bits.
+    pub fn extra_bits(&self) -> usize {
+        usize::from(self.tombstones.is_some())
+            + usize::from(self.alternates.is_some())
+            + usize::from(self.aging.is_some())
+    }
+
+    // the chance that the metadata of a full bucket holding another key fails to rule a
+    // key out, under uniform hashing, when full buckets hold hash fingerprints.
+    pub fn expected_false_positive_rate(&self) -> f64 {
+        if self.bits == 0 || self.counting {
+            return 1.0;
+        }
+        let hash_bits = self.bits - 1 - self.psl_bits.unwrap_or(0);
+        0.5f64.powi(hash_bits as i32)
+    }
+
+    // the bucket reads expected of a lookup which probes `probes` buckets, the last of
+    // them holding the key if `found` and empty otherwise. with any bits the empty
+    // bucket is seen in the metadata, and the full buckets passed are only read at the
+    // false positive rate.
+    pub fn expected_reads(&self, probes: f64, found: bool) -> f64 {
         if self.bits == 0 {
-            return false;
+            return probes;
         }
-        if self.bits == 1 {
-            return !self.bitvec.get(bucket).unwrap();
+        let passed = probes - 1.0;
+        self.expected_false_positive_rate() * passed + if found { 1.0 } else { 0.0 }
+    }
+
+    // the bytes the metadata takes, including padding to whole cache lines and the
+    // bits kept beside it.
+    pub fn memory_usage(&self) -> usize {
+        (self.bitvec.len() + self.extra_bits() * self.buckets).div_ceil(8)
+    }
+
+    // note that a key whose primary bucket is `primary` was placed elsewhere.
+    pub fn set_alternate(&mut self, primary: usize) {
+        if let Some(alternates) = &mut self.alternates {
+            alternates.set(primary, true);
         }
+    }
 
-        let start = bucket * self.bits;
-        let end = start + self.bits;
-        self.bitvec[start..end].not_any()
+    // true means every key whose primary bucket is `primary` is in it, if anywhere.
+    pub fn hint_primary_only(&self, primary: usize) -> bool {
+        self.alternates.as_ref().is_some_and(|a| !a[primary])
+    }
+
+    // note that a lookup found the bucket's key. every bit is cleared first once there
+    // have been as many references as buckets since the last clearing, so that keys
+    // which stop being looked up turn cold again.
+    pub fn reference(&self, bucket: usize) {
+        if let Some(aging) = &self.aging {
+            if aging.references.get() == self.buckets {
+                aging.referenced.iter().for_each(|bit| bit.set(false));
+                aging.references.set(0);
+            }
+            aging.references.set(aging.references.get() + 1);
+            aging.referenced[bucket].set(true);
+        }
+    }
+
+    // whether the bucket's key was looked up since the last clearing. keys carry the
+    // bit with them when they move.
+    pub fn referenced(&self, bucket: usize) -> bool {
+        self.aging
+            .as_ref()
+            .is_some_and(|a| a.referenced[bucket].get())
+    }
+
+    pub fn set_referenced(&self, bucket: usize, referenced: bool) {
+        if let Some(aging) = &self.aging {
+            aging.referenced[bucket].set(referenced);
+        }
+    }
+
+    // true means definitely empty.
+    pub fn hint_empty(&self, bucket: usize) -> bool {
+        if self.bits == 0 || self.counting {
+            return false;
+        }
+        self.read(bucket..bucket + 1);
+        self.load_any(bucket) == 0
     }
 
     // true means definitely a tombstone.
     pub fn hint_tombstone(&self, bucket: usize) -> bool {
-        if self.bits <= 1 {
+        if let Some(tombstones) = &self.tombstones {
+            return tombstones[bucket];
+        }
+        if self.bits <= 1 || self.counting {
             return false;
         }
+        self.read(bucket..bucket + 1);
+        self.load_any(bucket) == (1 << (self.bits - 1)) - 1
+    }
 
-        let start = bucket * self.bits;
-        let end = start + self.bits;
+    pub fn hint_psl(&self, bucket: usize) -> Option<PslHint> {
+        if self.bits == 0 || self.counting || self.is_tombstone(bucket) {
+            return None;
+        }
+        self.read(bucket..bucket + 1);
+        let bits_remaining = self.bits - 1;
+        let field = self.load_any(bucket);
+        if field >> bits_remaining == 0 {
+            return None;
+        }
 
-        !self.bitvec.get(start).unwrap() && self.bitvec[start + 1..end].all()
+        Some(match self.psl_bits {
+            Some(psl_bits) => decode_psl(field >> (bits_remaining - psl_bits), psl_bits),
+            None => decode_psl(field, bits_remaining),
+        })
     }
 
-    pub fn hint_psl(&self, bucket: usize) -> Option<PslHint> {
-        if self.bits == 0 {
+    // with a counting layout, the longest run of probes past `bucket` any key whose home
+    // it is has needed, so that lookups may stop after it. None once the counter
+    // saturates.
+    pub fn hint_max_run(&self, bucket: usize) -> Option<usize> {
+        if self.bits == 0 || !self.counting {
             return None;
         }
-        if self.bits == 1 {
-            return if *self.bitvec.get(bucket).unwrap() {
-                Some(PslHint::AtLeast(1))
+        self.read(bucket..bucket + 1);
+        let counter = self.load_any(bucket);
+        (counter < (1 << self.bits) - 1).then_some(counter as usize)
+    }
+
+    // note that a key whose home is `bucket` was placed `run` probes past it. counters
+    // only grow, as the other keys of the run may still need them.
+    pub fn raise_max_run(&mut self, bucket: usize, run: usize) {
+        if self.bits == 0 || !self.counting {
+            return;
+        }
+        self.touch(bucket..bucket + 1);
+        let saturated = (1 << self.bits) - 1;
+        let counter = std::cmp::max(self.load_any(bucket), std::cmp::min(run as u64, saturated));
+        self.store_any(bucket, counter);
+    }
+
+    // returns true if it's definitely not a match.
+    pub fn hint_not_match(&self, bucket: usize, raw_hash: u64) -> bool {
+        let not_match = self.not_match(bucket, raw_hash);
+        self.count_hints(usize::from(!not_match), usize::from(not_match));
+        not_match
+    }
+
+    // the metadata filtered so far.
+    pub fn hint_stats(&self) -> HintStats {
+        self.hint_stats.get()
+    }
+
+    // a bucket read because its metadata matched, and whether it held the key.
+    pub fn record_read(&self, held_key: bool) {
+        if !held_key {
+            let mut stats = self.hint_stats.get();
+            stats.false_matches += 1;
+            self.hint_stats.set(stats);
+        }
+    }
+
+    fn count_hints(&self, matches: usize, rejections: usize) {
+        let mut stats = self.hint_stats.get();
+        stats.matches += matches;
+        stats.rejections += rejections;
+        self.hint_stats.set(stats);
+    }
+
+    fn not_match(&self, bucket: usize, raw_hash: u64) -> bool {
+        if self.is_tombstone(bucket) {
+            return true;
+        }
+        if self.bits == 0 || self.counting {
+            return false;
+        }
+        self.read(bucket..bucket + 1);
+        if let Some(psl_bits) = self.psl_bits {
+            let hash_bits = self.bits - 1 - psl_bits;
+            let field = self.load_any(bucket);
+            let hash_field = raw_hash.checked_shr(64 - hash_bits as u32).unwrap_or(0);
+            return field >> (self.bits - 1) == 0 || field & ((1 << hash_bits) - 1) != hash_field;
+        }
+        self.load_any(bucket) != (1 << (self.bits - 1)) | self.hash_field(raw_hash)
+    }
+
+    // the slots of group `group` (buckets `GROUP_SLOTS * group..`) which may hold a key
+    // with this hash, as a mask with the group's first slot in bit 0.
+    pub fn hint_match_group(&self, group: usize, raw_hash: u64) -> u16 {
+        let mask = self.match_group(group, raw_hash);
+        let matches = mask.count_ones() as usize;
+        self.count_hints(matches, GROUP_SLOTS - matches);
+        mask
+    }
+
+    fn match_group(&self, group: usize, raw_hash: u64) -> u16 {
+        if self.bits == 8 && self.word_aligned() && self.psl_bits.is_none() && !self.counting {
+            self.read(group * GROUP_SLOTS..(group + 1) * GROUP_SLOTS);
+            return self.scan_group(group, 0x80 | self.hash_field(raw_hash));
+        }
+
+        self.group_mask(group, |bucket| !self.not_match(bucket, raw_hash))
+    }
+
+    // the slots of group `group` which are definitely empty, as a mask.
+    pub fn hint_empty_group(&self, group: usize) -> u16 {
+        if self.bits == 8 && self.word_aligned() && !self.counting {
+            self.read(group * GROUP_SLOTS..(group + 1) * GROUP_SLOTS);
+            return self.scan_group(group, 0);
+        }
+
+        self.group_mask(group, |bucket| self.hint_empty(bucket))
+    }
+
+    // the buckets of `range`, at most 64 long, which may hold a key with this hash, as
+    // a mask with the range's first bucket in bit 0. whole groups are scanned at once.
+    pub fn match_mask(&self, range: Range<usize>, raw_hash: u64) -> u64 {
+        assert!(range.len() <= 64);
+        let mut mask = 0;
+        let mut bucket = range.start;
+        while bucket < range.end {
+            let group = bucket / GROUP_SLOTS;
+            let first = group * GROUP_SLOTS;
+            let end = std::cmp::min(range.end, first + GROUP_SLOTS);
+            let group_mask = if first + GROUP_SLOTS <= self.buckets {
+                self.match_group(group, raw_hash)
             } else {
-                None
+                // a trailing partial group.
+                self.group_mask(group, |b| {
+                    (bucket..end).contains(&b) && !self.not_match(b, raw_hash)
+                })
             };
+
+            let in_range = (group_mask as u64 >> (bucket - first)) & ((1 << (end - bucket)) - 1);
+            mask |= in_range << (bucket - range.start);
+            bucket = end;
         }
 
-        let start = bucket * self.bits;
-        let end = start + self.bits;
-        if *self.bitvec.get(start).unwrap() {
-            let psl_bits = &self.bitvec[start + 1..end];
-            if psl_bits.all() {
-                Some(PslHint::AtLeast(1 << self.bits - 1))
+        let matches = mask.count_ones() as usize;
+        self.count_hints(matches, range.len() - matches);
+        mask
+    }
+
+    // the buckets of `range` which may hold a key with this hash, in order.
+    pub fn match_candidates(
+        &self,
+        range: Range<usize>,
+        raw_hash: u64,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let end = range.end;
+        range.step_by(64).flat_map(move |start| {
+            let chunk_end = std::cmp::min(start + 64, end);
+            let mask = self.match_mask(start..chunk_end, raw_hash);
+            (start..chunk_end).filter(move |&bucket| (mask >> (bucket - start)) & 1 == 1)
+        })
+    }
+
+    // what metadata says about every bucket of a block of consecutive buckets, such as
+    // the buckets of a cache line, for a key with this hash. buckets which may hold the
+    // key count as matches and full buckets ruled out as rejections.
+    pub fn hint_block(
+        &self,
+        block: Range<usize>,
+        raw_hash: u64,
+    ) -> impl Iterator<Item = (usize, Hint)> + '_ {
+        block.map(move |bucket| {
+            let hint = if self.hint_empty(bucket) {
+                Hint::Empty
+            } else if self.hint_tombstone(bucket) {
+                Hint::Tombstone
+            } else if self.hint_not_match(bucket, raw_hash) {
+                Hint::NotMatch
             } else {
-                Some(PslHint::Exact(psl_bits.load::<usize>() + 1))
+                Hint::Candidate
+            };
+            (bucket, hint)
+        })
+    }
+
+    // the cache line holding the bucket's metadata.
+    pub fn line_of(&self, bucket: usize) -> usize {
+        bucket / (CACHE_LINE_BITS / self.bits)
+    }
+
+    fn field_start(&self, bucket: usize) -> usize {
+        self.line_of(bucket) * CACHE_LINE_BITS + bucket % (CACHE_LINE_BITS / self.bits) * self.bits
+    }
+
+    // note the cache lines holding the metadata of `buckets` as touched.
+    fn touch(&self, buckets: Range<usize>) {
+        for line in self.line_of(buckets.start)..=self.line_of(buckets.end - 1) {
+            touch_cache_line(&self.line_stamps[line], |accesses| accesses.meta_lines += 1);
+        }
+    }
+
+    // note the fields of `buckets` as read, and their cache lines as touched.
+    fn read(&self, buckets: Range<usize>) {
+        let operation = OPERATION.get();
+        let read = (operation, buckets.start, buckets.end);
+        if operation != 0 && self.last_read.replace(read) != read {
+            let mut accesses = ACCESSES.get();
+            accesses.meta_probes += 1;
+            ACCESSES.set(accesses);
+        }
+        self.touch(buckets);
+    }
+
+    fn group_mask(&self, group: usize, hint: impl Fn(usize) -> bool) -> u16 {
+        (0..GROUP_SLOTS)
+            .filter(|&slot| hint(group * GROUP_SLOTS + slot))
+            .fold(0, |mask, slot| mask | 1 << slot)
+    }
+
+    // with 8 bits a group is two whole words of control bytes. every byte of a word is
+    // compared against `byte` at once, SWAR style.
+    fn scan_group(&self, group: usize, byte: u64) -> u16 {
+        const LOW_BITS: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+
+        let words = &self.bitvec.as_raw_slice()[group * 2..group * 2 + 2];
+        let mut mask = 0;
+        for (w, word) in words.iter().enumerate() {
+            // the top bit of every byte equal to `byte` is set, and no other bit.
+            let diff = word ^ (byte * 0x0101_0101_0101_0101);
+            let equal = !(((diff & LOW_BITS) + LOW_BITS) | diff | LOW_BITS);
+            for slot in 0..8 {
+                let bit = (equal >> (self.word_shift(8 * slot) + 7)) & 1;
+                mask |= (bit as u16) << (8 * w + slot);
+            }
+        }
+
+        mask
+    }
+
+    // widths which tile a u64 word exactly are read and written with plain word
+    // arithmetic rather than through bitvec slices. they tile a cache line too, so the
+    // layout is the same either way. interleaved fields are split in two, so never are.
+    fn word_aligned(&self) -> bool {
+        self.word_path
+            && self.layout != Layout::Interleaved
+            && matches!(self.bits, 1 | 2 | 4 | 8 | 16)
+    }
+
+    // the shift of a field starting `bit` bits into its word.
+    fn word_shift(&self, bit: usize) -> usize {
+        match self.layout {
+            Layout::Msb0 => 64 - self.bits - bit % 64,
+            Layout::Lsb0 => bit % 64,
+            Layout::Interleaved => unreachable!(),
+        }
+    }
+
+    // (word index, shift of the field within the word, field mask)
+    fn word_field(&self, bucket: usize) -> (usize, usize, u64) {
+        let bit = bucket * self.bits;
+        (bit / 64, self.word_shift(bit), (1 << self.bits) - 1)
+    }
+
+    fn load_field(&self, bucket: usize) -> u64 {
+        let (word, shift, mask) = self.word_field(bucket);
+        (self.bitvec.as_raw_slice()[word] >> shift) & mask
+    }
+
+    fn store_field(&mut self, bucket: usize, value: u64) {
+        let (word, shift, mask) = self.word_field(bucket);
+        let raw = &mut self.bitvec.as_raw_mut_slice()[word];
+        *raw = (*raw & !(mask << shift)) | (value << shift);
+    }
+
+    fn is_tombstone(&self, bucket: usize) -> bool {
+        self.tombstones.as_ref().is_some_and(|t| t[bucket])
+    }
+
+    fn set_tombstone_bit(&mut self, bucket: usize, tombstone: bool) {
+        if let Some(tombstones) = &mut self.tombstones {
+            tombstones.set(bucket, tombstone);
+        }
+    }
+
+    // the bucket's whole field, at any width.
+    fn load_any(&self, bucket: usize) -> u64 {
+        if self.word_aligned() {
+            return self.load_field(bucket);
+        }
+        let start = self.field_start(bucket);
+        match self.layout {
+            Layout::Msb0 => self.bitvec[start..start + self.bits].load_be(),
+            Layout::Lsb0 => self.lsb0()[start..start + self.bits].load_le(),
+            Layout::Interleaved => {
+                let (full, rest) = self.interleaved_field(bucket);
+                let rest: u64 = match self.bits {
+                    1 => 0,
+                    _ => self.bitvec[rest..rest + self.bits - 1].load_be(),
+                };
+                (u64::from(self.bitvec[full]) << (self.bits - 1)) | rest
+            }
+        }
+    }
+
+    fn store_any(&mut self, bucket: usize, value: u64) {
+        if self.word_aligned() {
+            self.store_field(bucket, value);
+            return;
+        }
+        let start = self.field_start(bucket);
+        match self.layout {
+            Layout::Msb0 => self.bitvec[start..start + self.bits].store_be(value),
+            Layout::Lsb0 => BitSlice::<u64, Lsb0>::from_slice_mut(self.bitvec.as_raw_mut_slice())
+                [start..start + self.bits]
+                .store_le(value),
+            Layout::Interleaved => {
+                let (full, rest) = self.interleaved_field(bucket);
+                self.bitvec.set(full, value >> (self.bits - 1) == 1);
+                if self.bits > 1 {
+                    self.bitvec[rest..rest + self.bits - 1].store_be(value);
+                }
+            }
+        }
+    }
+
+    // the words of the metamap, with bits counted from the least significant up.
+    fn lsb0(&self) -> &BitSlice<u64, Lsb0> {
+        BitSlice::from_slice(self.bitvec.as_raw_slice())
+    }
+
+    // with an interleaved layout, the bucket's FULL bit and the start of the rest of
+    // its field.
+    fn interleaved_field(&self, bucket: usize) -> (usize, usize) {
+        let per_line = CACHE_LINE_BITS / self.bits;
+        let line = self.line_of(bucket) * CACHE_LINE_BITS;
+        let slot = bucket % per_line;
+        (line + slot, line + per_line + slot * (self.bits - 1))
+    }
+
+    // the high bits of the hash stored after the FULL bit.
+    fn hash_field(&self, raw_hash: u64) -> u64 {
+        match self.bits - 1 {
+            0 => 0,
+            bits_remaining => raw_hash >> (64 - bits_remaining),
+        }
+    }
+}
+
+// a PSL field of `bits` bits holds the PSLs 1 to 2^bits - 1 exactly, stored minus one.
+// all ones means the PSL is at least 2^bits, and no bits at all mean at least 1.
+fn encode_psl(psl: usize, bits: usize) -> u64 {
+    assert!(psl > 0, "PSLs start at 1");
+    (std::cmp::min(psl, 1 << bits) - 1) as u64
+}
+
+// the PSL hint of the `bits` low bits of `field`.
+fn decode_psl(field: u64, bits: usize) -> PslHint {
+    let saturated = (1 << bits) - 1;
+    match field & saturated {
+        psl if psl == saturated => PslHint::AtLeast(1 << bits),
+        psl => PslHint::Exact(psl as usize + 1),
+    }
+}
+
+// the cache lines needed for the metadata of `buckets` buckets.
+fn lines(buckets: usize, bits: usize) -> usize {
+    match bits {
+        0 => 0,
+        bits => buckets.div_ceil(CACHE_LINE_BITS / bits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const BUCKETS: usize = 8;
+
+    // hashes which agree on the first 0, 1, 2, .. bits with HASHES[0].
+    const HASHES: [u64; 8] = [
+        0xDEAD_BEEF_0123_4567,
+        0xDEAD_BEEF_0123_4567 ^ (1 << 63),
+        0xDEAD_BEEF_0123_4567 ^ (1 << 62),
+        0xDEAD_BEEF_0123_4567 ^ (1 << 60),
+        0xDEAD_BEEF_0123_4567 ^ (1 << 56),
+        0xDEAD_BEEF_0123_4567 ^ (1 << 49),
+        0xDEAD_BEEF_0123_4567 ^ (1 << 48),
+        0xDEAD_BEEF_0123_4567 ^ 1,
+    ];
+
+    #[derive(Clone, Copy, Debug)]
+    enum State {
+        Empty,
+        Tombstone,
+        Hash(u64),
+        Psl(usize),
+        PslAndHash(usize, u64),
+    }
+
+    fn state() -> impl Strategy<Value = State> {
+        prop_oneof![
+            Just(State::Empty),
+            Just(State::Tombstone),
+            prop::sample::select(&HASHES[..]).prop_map(State::Hash),
+            (1usize..70_000).prop_map(State::Psl),
+        ]
+    }
+
+    fn set(meta: &mut MetaMap, bucket: usize, state: State) {
+        match state {
+            State::Empty => meta.set_empty(bucket),
+            State::Tombstone => meta.set_tombstone(bucket),
+            State::Hash(hash) => meta.set_full(bucket, Metadata::Hash(hash)),
+            State::Psl(psl) => meta.set_full(bucket, Metadata::Psl(psl)),
+            State::PslAndHash(psl, hash) => {
+                meta.set_full(bucket, Metadata::PslAndHash { psl, hash })
+            }
+        }
+    }
+
+    fn layout() -> impl Strategy<Value = Layout> {
+        prop::sample::select(&[Layout::Msb0, Layout::Lsb0, Layout::Interleaved][..])
+    }
+
+    fn split_state() -> impl Strategy<Value = State> {
+        prop_oneof![
+            Just(State::Empty),
+            Just(State::Tombstone),
+            (1usize..70_000, prop::sample::select(&HASHES[..]))
+                .prop_map(|(psl, hash)| State::PslAndHash(psl, hash)),
+        ]
+    }
+
+    // what the PSL and hash hints should say about a bucket of a split layout.
+    fn check_split(
+        meta: &MetaMap,
+        psl_bits: usize,
+        bucket: usize,
+        state: State,
+    ) -> Result<(), TestCaseError> {
+        let bits = meta.bits();
+        let hash_bits = bits.saturating_sub(1 + psl_bits);
+        let aliased_tombstone = bits == 1 && matches!(state, State::Tombstone);
+
+        let expected_psl = match state {
+            _ if bits == 0 => None,
+            State::Tombstone if aliased_tombstone => Some(PslHint::AtLeast(1)),
+            State::PslAndHash(psl, _) if psl < 1 << psl_bits => Some(PslHint::Exact(psl)),
+            State::PslAndHash(..) => Some(PslHint::AtLeast(1 << psl_bits)),
+            _ => None,
+        };
+        prop_assert_eq!(meta.hint_psl(bucket), expected_psl);
+
+        for hash in HASHES {
+            let prefix = |hash: u64| hash.checked_shr(64 - hash_bits as u32).unwrap_or(0);
+            let expected_not_match = match state {
+                _ if bits == 0 => false,
+                State::PslAndHash(_, stored) => prefix(stored) != prefix(hash),
+                _ => !aliased_tombstone,
+            };
+            prop_assert_eq!(meta.hint_not_match(bucket, hash), expected_not_match);
+        }
+
+        Ok(())
+    }
+
+    // what every hint should say about a bucket in the given state. with one bit a
+    // tombstone is stored as `1`, and so reads as a full bucket.
+    fn check(meta: &MetaMap, bucket: usize, state: State) -> Result<(), TestCaseError> {
+        let bits = meta.bits();
+        let hash_bits = bits.saturating_sub(1);
+        let tombstone_bit = meta.tombstones.is_some();
+        let aliased_tombstone = bits == 1 && !tombstone_bit && matches!(state, State::Tombstone);
+
+        prop_assert_eq!(
+            meta.hint_empty(bucket),
+            bits > 0 && matches!(state, State::Empty)
+        );
+        prop_assert_eq!(
+            meta.hint_tombstone(bucket),
+            (bits > 1 || tombstone_bit) && matches!(state, State::Tombstone)
+        );
+
+        let expected_psl = match state {
+            _ if bits == 0 => None,
+            State::Empty => None,
+            State::Tombstone if aliased_tombstone => Some(PslHint::AtLeast(1)),
+            State::Tombstone => None,
+            State::Psl(psl) if psl < 1 << hash_bits => Some(PslHint::Exact(psl)),
+            State::Psl(_) => Some(PslHint::AtLeast(1 << hash_bits)),
+            // hash-mode buckets have no meaningful PSL.
+            State::Hash(_) => meta.hint_psl(bucket),
+            State::PslAndHash(..) => unreachable!(),
+        };
+        prop_assert_eq!(meta.hint_psl(bucket), expected_psl);
+
+        for hash in HASHES {
+            let prefix = |hash: u64| hash.checked_shr(64 - hash_bits as u32).unwrap_or(0);
+            let expected_not_match = match state {
+                State::Tombstone if tombstone_bit => true,
+                _ if bits == 0 => false,
+                State::Empty => true,
+                State::Tombstone => !aliased_tombstone,
+                State::Hash(stored) => prefix(stored) != prefix(hash),
+                // psl-mode buckets may say anything, as long as they are full.
+                State::Psl(_) => meta.hint_not_match(bucket, hash),
+                State::PslAndHash(..) => unreachable!(),
+            };
+            prop_assert_eq!(meta.hint_not_match(bucket, hash), expected_not_match);
+        }
+
+        Ok(())
+    }
+
+    // the same random sets and clears, through the word path and the bitvec path,
+    // leave every field giving the same hints.
+    #[test]
+    fn word_path_matches_bitvec_path() {
+        use rand::{Rng, SeedableRng};
+        use rand_xoshiro::Xoshiro256PlusPlus;
+
+        const BUCKETS: usize = 320;
+        for bits in [1, 2, 4, 8, 16] {
+            for layout in [Layout::Msb0, Layout::Lsb0, Layout::Interleaved] {
+                let mut words = MetaMap::with_layout(BUCKETS, bits, layout);
+                let mut bitvec = MetaMap {
+                    word_path: false,
+                    ..MetaMap::with_layout(BUCKETS, bits, layout)
+                };
+                let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+                for _ in 0..2000 {
+                    let bucket = rng.gen_range(0..BUCKETS);
+                    let state = match rng.gen_range(0..3) {
+                        0 => State::Empty,
+                        1 => State::Tombstone,
+                        _ => State::Hash(HASHES[rng.gen_range(0..HASHES.len())]),
+                    };
+                    set(&mut words, bucket, state);
+                    set(&mut bitvec, bucket, state);
+                }
+
+                // the paths lay the fields out alike, as well as reading them back alike.
+                assert_eq!(words.bitvec, bitvec.bitvec, "{bits} bits, {layout:?}");
+                for bucket in 0..BUCKETS {
+                    let context = format!("{bits} bits, {layout:?}, bucket {bucket}");
+                    assert_eq!(words.load_any(bucket), bitvec.load_any(bucket), "{context}");
+                    assert_eq!(
+                        words.hint_empty(bucket),
+                        bitvec.hint_empty(bucket),
+                        "{context}"
+                    );
+                    assert_eq!(
+                        words.hint_tombstone(bucket),
+                        bitvec.hint_tombstone(bucket),
+                        "{context}"
+                    );
+                    for hash in HASHES {
+                        assert_eq!(
+                            words.hint_not_match(bucket, hash),
+                            bitvec.hint_not_match(bucket, hash),
+                            "{context}"
+                        );
+                    }
+                }
+                for group in 0..BUCKETS / GROUP_SLOTS {
+                    assert_eq!(
+                        words.hint_empty_group(group),
+                        bitvec.hint_empty_group(group)
+                    );
+                    for hash in HASHES {
+                        assert_eq!(
+                            words.hint_match_group(group, hash),
+                            bitvec.hint_match_group(group, hash)
+                        );
+                    }
+                }
             }
+        }
+    }
+
+    #[test]
+    fn lines_touched_are_distinct() {
+        // 3 bits leave 170 buckets per line.
+        let mut meta = MetaMap::new(400, 3);
+        let ((), lines) = count_accesses(|| {
+            meta.set_full(0, Metadata::Hash(HASHES[0]));
+            meta.set_full(169, Metadata::Hash(HASHES[1]));
+        });
+        assert_eq!(lines.meta_lines, 1);
+
+        let ((), lines) = count_accesses(|| {
+            meta.hint_empty(169);
+            meta.hint_not_match(170, HASHES[0]);
+            meta.hint_tombstone(399);
+        });
+        assert_eq!(lines.meta_lines, 3);
+        assert_eq!(lines.bucket_lines, 0);
+        assert_eq!(count_accesses(|| ()).1, Accesses::default());
+    }
+
+    #[test]
+    fn meta_probes_count_reads() {
+        let mut meta = MetaMap::new(400, 3);
+        let ((), accesses) = count_accesses(|| {
+            meta.set_full(0, Metadata::Hash(HASHES[0]));
+            meta.set_empty(1);
+        });
+        assert_eq!(accesses.meta_probes, 0);
+
+        // checking the same field twice in a row reads it once.
+        let ((), accesses) = count_accesses(|| {
+            meta.hint_empty(0);
+            meta.hint_not_match(0, HASHES[0]);
+            meta.hint_empty(1);
+            meta.hint_empty(0);
+        });
+        assert_eq!(accesses.meta_probes, 3);
+        assert_eq!(accesses.meta_lines, 1);
+
+        // without metadata there is nothing to read.
+        let meta = MetaMap::new(400, 0);
+        let (_, accesses) = count_accesses(|| meta.hint_not_match(0, HASHES[0]));
+        assert_eq!(accesses.meta_probes, 0);
+    }
+
+    // every PSL up to just past saturation for fields of up to 16 bits, those around
+    // saturation for wider ones, and the largest PSL.
+    fn psls(psl_bits: usize) -> impl Iterator<Item = usize> {
+        let saturated = 1 << psl_bits;
+        let start = if psl_bits <= 16 { 1 } else { saturated - 2 };
+        (start..saturated + 2).chain([usize::MAX])
+    }
+
+    fn expected_psl(psl: usize, psl_bits: usize) -> PslHint {
+        if psl < 1 << psl_bits {
+            PslHint::Exact(psl)
         } else {
-            None
+            PslHint::AtLeast(1 << psl_bits)
         }
     }
 
-    // returns true if it's definitely not a match.
-    pub fn hint_not_match(&self, bucket: usize, raw_hash: u64) -> bool {
-        if self.bits == 0 {
-            return false;
+    #[test]
+    fn psls_and_hashes_round_trip_at_every_width() {
+        for bits in 1..=MAX_BITS {
+            let mut meta = MetaMap::new(2, bits);
+            for psl in psls(bits - 1) {
+                meta.set_full(1, Metadata::Psl(psl));
+                assert_eq!(
+                    meta.hint_psl(1),
+                    Some(expected_psl(psl, bits - 1)),
+                    "{bits} bits, psl {psl}"
+                );
+            }
+            for hash in HASHES {
+                meta.set_full(1, Metadata::Hash(hash));
+                assert!(!meta.hint_not_match(1, hash), "{bits} bits, hash {hash:x}");
+                assert!(meta.hint_empty(0));
+            }
         }
-        if self.bits == 1 {
-            return !*self.bitvec.get(bucket).unwrap();
+    }
+
+    #[test]
+    fn split_psls_round_trip_at_every_width() {
+        for bits in 1..=MAX_BITS {
+            for psl_bits in 0..bits {
+                let mut meta = MetaMap::split(2, bits, psl_bits);
+                for (psl, hash) in psls(psl_bits).zip(HASHES.iter().cycle()) {
+                    meta.set_full(1, Metadata::PslAndHash { psl, hash: *hash });
+                    assert_eq!(
+                        meta.hint_psl(1),
+                        Some(expected_psl(psl, psl_bits)),
+                        "{bits} bits, {psl_bits} psl bits, psl {psl}"
+                    );
+                    assert!(!meta.hint_not_match(1, *hash));
+                    assert!(meta.hint_empty(0));
+                }
+            }
         }
+    }
 
-        let bits_remaining = self.bits - 1;
-        let start = bucket * self.bits;
-        let end = start + self.bits;
+    #[test]
+    fn false_positive_rates_halve_per_hash_bit() {
+        assert_eq!(MetaMap::new(BUCKETS, 0).expected_false_positive_rate(), 1.0);
+        assert_eq!(MetaMap::new(BUCKETS, 1).expected_false_positive_rate(), 1.0);
+        assert_eq!(
+            MetaMap::new(BUCKETS, 4).expected_false_positive_rate(),
+            0.125
+        );
+        assert_eq!(
+            MetaMap::split(BUCKETS, 8, 3).expected_false_positive_rate(),
+            0.0625
+        );
+        assert_eq!(
+            MetaMap::counting(BUCKETS, 8).expected_false_positive_rate(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn layouts_place_fields_differently() {
+        let words = |layout| {
+            let mut meta = MetaMap::with_layout(BUCKETS, 4, layout);
+            meta.set_tombstone(0);
+            meta.bitvec.as_raw_slice()[..3].to_vec()
+        };
+        assert_eq!(words(Layout::Msb0), [0x7 << 60, 0, 0]);
+        assert_eq!(words(Layout::Lsb0), [0x7, 0, 0]);
+        // 128 FULL bits lead the line, then the rest of each field.
+        assert_eq!(words(Layout::Interleaved), [0, 0, 0x7 << 61]);
+    }
 
-        !*self.bitvec.get(start).unwrap() || {
-            let high_bits = &raw_hash.view_bits::<Msb0>()[..bits_remaining];
-            &self.bitvec[start + 1..end] != high_bits
+    #[test]
+    fn references_clear_periodically() {
+        let meta = MetaMap::with_aging(BUCKETS, 4);
+        for bucket in 0..BUCKETS {
+            meta.reference(bucket);
+        }
+        assert!((0..BUCKETS).all(|bucket| meta.referenced(bucket)));
+
+        meta.reference(1);
+        assert!(meta.referenced(1));
+        assert!(!meta.referenced(0));
+        assert!(!MetaMap::new(BUCKETS, 4).referenced(0));
+    }
+
+    #[test]
+    fn memory_usage_counts_whole_lines() {
+        assert_eq!(MetaMap::new(400, 0).memory_usage(), 0);
+        // 3 lines of 64 bytes, the last mostly padding.
+        assert_eq!(MetaMap::new(400, 3).memory_usage(), 192);
+        assert_eq!(MetaMap::with_tombstones(400, 3).memory_usage(), 192 + 50);
+    }
+
+    #[test]
+    fn max_runs_only_grow_and_saturate() {
+        let mut meta = MetaMap::counting(BUCKETS, 3);
+        assert_eq!(meta.hint_max_run(0), Some(0));
+        meta.raise_max_run(0, 4);
+        meta.raise_max_run(0, 2);
+        assert_eq!(meta.hint_max_run(0), Some(4));
+        meta.raise_max_run(1, 7);
+        assert_eq!(meta.hint_max_run(1), None);
+
+        // counters aren't fingerprints.
+        meta.set_full(2, Metadata::Hash(HASHES[0]));
+        assert!(!meta.hint_empty(3));
+        assert!(!meta.hint_not_match(0, HASHES[0]));
+        assert_eq!(meta.hint_max_run(2), Some(0));
+        assert_eq!(MetaMap::new(BUCKETS, 3).hint_max_run(0), None);
+    }
+
+    proptest! {
+        // enough buckets to span several cache lines at any width.
+        #[test]
+        fn hints_agree_with_model_across_lines(
+            bits in 1usize..=16,
+            layout in layout(),
+            ops in prop::collection::vec((0..300usize, state()), 1..128),
+        ) {
+            let mut meta = MetaMap::with_layout(300, bits, layout);
+            let mut model = vec![State::Empty; 300];
+
+            for (bucket, state) in ops {
+                set(&mut meta, bucket, state);
+                model[bucket] = state;
+            }
+            for (bucket, &state) in model.iter().enumerate() {
+                check(&meta, bucket, state)?;
+            }
+        }
+
+        #[test]
+        fn block_hints_agree_with_bucket_hints(
+            bits in 0usize..=MAX_BITS,
+            tombstones in any::<bool>(),
+            ops in prop::collection::vec((0..BUCKETS, state()), 1..64),
+        ) {
+            let mut meta = if tombstones {
+                MetaMap::with_tombstones(BUCKETS, bits)
+            } else {
+                MetaMap::new(BUCKETS, bits)
+            };
+            for (bucket, state) in ops {
+                set(&mut meta, bucket, state);
+            }
+
+            for hash in HASHES {
+                for (bucket, hint) in meta.hint_block(0..BUCKETS, hash) {
+                    let expected = if meta.hint_empty(bucket) {
+                        Hint::Empty
+                    } else if meta.hint_tombstone(bucket) {
+                        Hint::Tombstone
+                    } else if meta.hint_not_match(bucket, hash) {
+                        Hint::NotMatch
+                    } else {
+                        Hint::Candidate
+                    };
+                    prop_assert_eq!(hint, expected);
+                }
+            }
+        }
+
+        #[test]
+        fn group_hints_agree_with_bucket_hints(
+            bits in 0usize..=MAX_BITS,
+            layout in layout(),
+            ops in prop::collection::vec((0..2 * GROUP_SLOTS, state()), 1..64),
+        ) {
+            let mut meta = MetaMap::with_layout(2 * GROUP_SLOTS, bits, layout);
+            for (bucket, state) in ops {
+                set(&mut meta, bucket, state);
+            }
+
+            for group in 0..2 {
+                let bucket_mask = |hint: &dyn Fn(usize) -> bool| {
+                    (0..GROUP_SLOTS)
+                        .filter(|&slot| hint(group * GROUP_SLOTS + slot))
+                        .fold(0u16, |mask, slot| mask | 1 << slot)
+                };
+                prop_assert_eq!(
+                    meta.hint_empty_group(group),
+                    bucket_mask(&|bucket| meta.hint_empty(bucket))
+                );
+                for hash in HASHES {
+                    prop_assert_eq!(
+                        meta.hint_match_group(group, hash),
+                        bucket_mask(&|bucket| !meta.hint_not_match(bucket, hash))
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn match_candidates_agree_with_bucket_hints(
+            bits in 0usize..=MAX_BITS,
+            ops in prop::collection::vec((0..3 * GROUP_SLOTS + 5, state()), 1..96),
+            start in 0..3 * GROUP_SLOTS + 5,
+            len in 0..3 * GROUP_SLOTS + 5,
+        ) {
+            let buckets = 3 * GROUP_SLOTS + 5;
+            let mut meta = MetaMap::new(buckets, bits);
+            for (bucket, state) in ops {
+                set(&mut meta, bucket, state);
+            }
+
+            let range = start..std::cmp::min(buckets, start + len);
+            for hash in HASHES {
+                let expected: Vec<usize> = range
+                    .clone()
+                    .filter(|&bucket| !meta.hint_not_match(bucket, hash))
+                    .collect();
+                let candidates: Vec<usize> = meta.match_candidates(range.clone(), hash).collect();
+                prop_assert_eq!(candidates, expected);
+            }
+        }
+
+        #[test]
+        fn split_hints_agree_with_model(
+            (bits, psl_bits) in (0usize..=MAX_BITS).prop_flat_map(|bits| (Just(bits), 0..std::cmp::max(1, bits))),
+            ops in prop::collection::vec((0..BUCKETS, split_state()), 1..64),
+        ) {
+            let mut meta = MetaMap::split(BUCKETS, bits, psl_bits);
+            let mut model = [State::Empty; BUCKETS];
+
+            for (bucket, state) in ops {
+                set(&mut meta, bucket, state);
+                model[bucket] = state;
+
+                for (bucket, &state) in model.iter().enumerate() {
+                    check_split(&meta, psl_bits, bucket, state)?;
+                }
+            }
+        }
+
+        #[test]
+        fn hints_agree_with_model(
+            bits in 0usize..=MAX_BITS,
+            tombstones in any::<bool>(),
+            layout in layout(),
+            ops in prop::collection::vec((0..BUCKETS, state()), 1..64),
+        ) {
+            let mut meta = if tombstones {
+                MetaMap::with_tombstones(BUCKETS, bits)
+            } else {
+                MetaMap::with_layout(BUCKETS, bits, layout)
+            };
+            let mut model = [State::Empty; BUCKETS];
+
+            for (bucket, state) in ops {
+                set(&mut meta, bucket, state);
+                model[bucket] = state;
+
+                for (bucket, &state) in model.iter().enumerate() {
+                    check(&meta, bucket, state)?;
+                }
+            }
         }
     }
 }
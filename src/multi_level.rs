@@ -0,0 +1,240 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::candidates;
+use crate::cuckoo::MAX_D;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata};
+use crate::robinhood::{Lookup, PslSource, RobinHood};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+use std::cell::Cell;
+
+pub const SLOTS_PER_BUCKET: usize = 8;
+
+// the fraction of buckets in the secondary table.
+const SECONDARY_FACTOR: f64 = 0.1;
+
+// dummy hash-set for u64 keys.
+//
+// implements a two-level table: every key hashes to one bucket of SLOTS_PER_BUCKET
+// slots in the primary table, and keys which find it full fall back to a small robin
+// hood table, hashed independently of the primary. lookups only search the secondary
+// table when it holds keys, and can be counted as spills.
+//
+// metadata is kept per slot in both tables, and every slot read counts as one probe.
+pub struct MultiLevel {
+    hasher: CountingHasher,
+    slots: BucketArray<Option<u64>>,
+    meta: MetaMap,
+    primary_len: usize,
+    secondary: RobinHood,
+    spills: Cell<usize>,
+    spill_probes: Cell<usize>,
+}
+
+impl MultiLevel {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        let secondary_capacity = std::cmp::max(1, (capacity as f64 * SECONDARY_FACTOR) as usize);
+        let primary_capacity =
+            std::cmp::max(1, (capacity - secondary_capacity) / SLOTS_PER_BUCKET) * SLOTS_PER_BUCKET;
+        MultiLevel {
+            hasher: options.hasher(0),
+            slots: bucket_array(primary_capacity, None, options),
+            meta: MetaMap::new(primary_capacity, meta_bits),
+            primary_len: 0,
+            secondary: RobinHood::new(
+                secondary_capacity,
+                meta_bits,
+                PslSource::Rehash,
+                Lookup::Linear,
+                1,
+                options.independent_of(MAX_D),
+            ),
+            spills: Cell::new(0),
+            spill_probes: Cell::new(0),
+        }
+    }
+
+    // (hash, slots of the key's primary bucket)
+    fn bucket_slots(&self, key: u64) -> (u64, std::ops::Range<usize>) {
+        let hash = self.hasher.hash_one(key);
+        let bucket = (hash % (self.slots.len() / SLOTS_PER_BUCKET) as u64) as usize;
+        explain!("primary bucket {bucket}");
+        (
+            hash,
+            bucket * SLOTS_PER_BUCKET..(bucket + 1) * SLOTS_PER_BUCKET,
+        )
+    }
+
+    // look in the primary table, then in the secondary one if it holds any keys.
+    //
+    // return a tuple containing:
+    //  + whether the key is contained
+    //  + option containing the probes made in the secondary table, if it was searched
+    //  + number of probes
+    fn search(&self, key: u64) -> (bool, Option<usize>, usize) {
+        let (hash, slots) = self.bucket_slots(key);
+        let (found, probes) = candidates::find_range(&self.meta, &self.slots, slots, key, hash);
+        if found.is_some() || self.secondary.len() == 0 {
+            return (found.is_some(), None, probes);
+        }
+
+        explain!("secondary table");
+        let secondary = self.secondary.probe(key);
+        (
+            secondary.contained,
+            Some(secondary.probes),
+            probes + secondary.probes,
+        )
+    }
+}
+
+impl Map for MultiLevel {
+    fn len(&self) -> usize {
+        self.primary_len + self.secondary.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len() + self.secondary.capacity()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.bucket_slots(key).1.start
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        [self.hasher.stats(), self.secondary.hash_stats()]
+            .into_iter()
+            .sum()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let primary = MemoryUsage {
+            buckets: std::mem::size_of_val(&self.slots[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
+        };
+        primary + self.secondary.memory_usage()
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        [self.meta.hint_stats(), self.secondary.hint_stats()]
+            .into_iter()
+            .sum()
+    }
+
+    fn spills(&self) -> usize {
+        self.spills.get()
+    }
+
+    fn spill_probes(&self) -> usize {
+        self.spill_probes.get()
+    }
+
+    fn overflow_occupancy(&self) -> f64 {
+        self.secondary.load_factor()
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (contained, spilled, probes) = self.search(key);
+        if let Some(spill_probes) = spilled {
+            self.spills.set(self.spills.get() + 1);
+            self.spill_probes
+                .set(self.spill_probes.get() + spill_probes);
+        }
+
+        Probe { contained, probes }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let (contained, _, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if contained {
+            return update;
+        }
+
+        let (hash, mut slots) = self.bucket_slots(key);
+        let empty = slots.find(|&slot| {
+            if self.meta.hint_empty(slot) {
+                check_hint(|| self.slots[slot].is_none(), slot);
+                true
+            } else if self.meta.bits() == 0 {
+                update.total_probes += 1;
+                self.slots[slot].is_none()
+            } else {
+                false
+            }
+        });
+        if let Some(slot) = empty {
+            explain!("place the key in primary slot {slot}");
+            self.slots[slot] = Some(key);
+            self.meta.set_full(slot, Metadata::Hash(hash));
+            self.primary_len += 1;
+            return update;
+        }
+
+        if self.secondary.len() == self.secondary.capacity() {
+            explain!("the primary bucket and the secondary table are full, give up");
+            update.completed = false;
+            return update;
+        }
+
+        explain!("the primary bucket is full, fall back to the secondary table");
+        let secondary = self.secondary.insert(key);
+        update.total_probes += secondary.total_probes;
+        update.total_writes += secondary.total_writes - 1;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        let primary = (0..self.slots.len()).map(|slot| {
+            let meta = self.meta.describe(slot);
+            match self.slots[slot] {
+                Some(key) => format!("primary {slot} {key} meta={meta}"),
+                None => format!("primary {slot} - meta={meta}"),
+            }
+        });
+        let secondary = self
+            .secondary
+            .dump()
+            .into_iter()
+            .map(|line| format!("secondary {line}"));
+        primary.chain(secondary).collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (hash, slots) = self.bucket_slots(key);
+        let (found, total_probes) =
+            candidates::find_range(&self.meta, &self.slots, slots, key, hash);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if let Some(slot) = found {
+            explain!("clear primary slot {slot}");
+            self.slots[slot] = None;
+            self.meta.set_empty(slot);
+            self.primary_len -= 1;
+            update.total_writes += 1;
+        } else if self.secondary.len() > 0 {
+            explain!("secondary table");
+            let secondary = self.secondary.remove(key);
+            update.total_probes += secondary.total_probes;
+            update.total_writes += secondary.total_writes;
+        }
+        update
+    }
+}
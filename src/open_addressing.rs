@@ -0,0 +1,617 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, Layout, MetaMap, Metadata};
+use crate::{ExpectedProbes, Map, MapOptions, MemoryUsage, Probe, Update};
+
+#[derive(Clone, Debug)]
+enum BucketItem {
+    // While searching for a key, only finding the key itself
+    // or an empty bucket can stop the search
+    Value(u64),
+    Empty,
+    // When a new value needs to be inserted,
+    // tombstones can be used
+    Tombstone,
+}
+
+// adaptive metamaps double their bits once the false positive rate since the last
+// upgrade passes ADAPTIVE_FP_RATE, measured over at least ADAPTIVE_WINDOW buckets.
+pub const ADAPTIVE_FP_RATE: f64 = 0.1;
+const ADAPTIVE_WINDOW: usize = 256;
+const ADAPTIVE_MAX_BITS: usize = 16;
+
+// the percentages of buckets holding tombstones a compacting map may be rebuilt at.
+pub const COMPACTION_PERCENTS: [usize; 3] = [10, 25, 50];
+
+// the buckets probed for a key, as offsets from its home bucket.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProbeSequence {
+    // the `i`th bucket probed is the `i`th triangular number after the home bucket,
+    // which visits every bucket of a power-of-two table.
+    Triangular,
+    // the `i`th bucket probed is `i * i` after the home bucket. with a power-of-two
+    // capacity only some of the buckets are reachable from any one home, so inserts can
+    // fail well before the map is full.
+    Quadratic,
+    // the `i`th bucket probed is `i * stride` after the home bucket, where the stride
+    // comes from a second hash. keys sharing a home bucket almost never share a probe
+    // sequence.
+    Double,
+}
+
+impl ProbeSequence {
+    // the offset of the `i`th bucket probed from the home bucket, given the key's stride.
+    fn offset(self, i: usize, stride: usize) -> usize {
+        match self {
+            ProbeSequence::Triangular => i * (i + 1) / 2,
+            ProbeSequence::Quadratic => i * i,
+            ProbeSequence::Double => i * stride,
+        }
+    }
+}
+
+// what the meta bits of each bucket hold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MetaKind {
+    // fingerprints of a fixed width.
+    Fixed,
+    // fingerprints of a fixed width, beside a tombstone bit per bucket, so that
+    // tombstones are skipped without a read at any width.
+    Tombstones,
+    // fingerprints, widened as needed.
+    Adaptive,
+    // the longest probe run from the bucket.
+    MaxRuns,
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements open addressing, probing buckets in the given sequence from a key's home
+// bucket. removals leave tombstones, which inserts reuse.
+//
+// with max runs, metadata holds no fingerprints. every bucket instead counts the most
+// probes a key whose home it is needed, so that lookups stop once past it.
+//
+// an adaptive map starts with few meta bits, and rebuilds its metamap with twice as
+// many, rehashing every stored key, when they filter too poorly. rewriting a key's
+// metadata counts as one write.
+//
+// a compacting map is rebuilt once removals have left tombstones in the given percentage
+// of its buckets. a rebuild places every key again, from its home bucket, into a table
+// without tombstones. it costs a write per key, charged to the removal which triggers it,
+// and its reads are one sequential scan, so aren't counted as probes.
+pub struct OpenAddressing {
+    hasher: CountingHasher,
+    // the hasher of strides, with double hashing.
+    stride_hasher: Option<CountingHasher>,
+    buckets: BucketArray<BucketItem>,
+    meta: MetaMap,
+    len: usize,
+    sequence: ProbeSequence,
+    tombstone_bit: bool,
+    adaptive: bool,
+    max_runs: bool,
+    layout: Layout,
+    upgrades: usize,
+    // the hint stats of metamaps replaced by upgrades and compactions.
+    retired_hint_stats: HintStats,
+    tombstones: usize,
+    compact_at: Option<usize>,
+    // the options compaction reallocates the buckets with.
+    options: MapOptions,
+}
+
+impl OpenAddressing {
+    pub fn new(
+        capacity: usize,
+        meta_bits: usize,
+        sequence: ProbeSequence,
+        meta_kind: MetaKind,
+        layout: Layout,
+        compact_at: Option<usize>,
+        options: MapOptions,
+    ) -> Self {
+        let max_runs = meta_kind == MetaKind::MaxRuns;
+        let tombstone_bit = meta_kind == MetaKind::Tombstones;
+        OpenAddressing {
+            hasher: options.hasher(0),
+            stride_hasher: (sequence == ProbeSequence::Double).then(|| options.hasher(1)),
+            buckets: bucket_array(capacity, BucketItem::Empty, options),
+            meta: if max_runs {
+                MetaMap::counting(capacity, meta_bits)
+            } else {
+                Self::meta_map(capacity, meta_bits, tombstone_bit, layout)
+            },
+            len: 0,
+            sequence,
+            tombstone_bit,
+            adaptive: meta_kind == MetaKind::Adaptive,
+            max_runs,
+            layout,
+            upgrades: 0,
+            retired_hint_stats: HintStats::default(),
+            tombstones: 0,
+            compact_at,
+            options,
+        }
+    }
+
+    // tombstone bits come with the default layout.
+    fn meta_map(capacity: usize, meta_bits: usize, tombstone_bit: bool, layout: Layout) -> MetaMap {
+        if tombstone_bit {
+            MetaMap::with_tombstones(capacity, meta_bits)
+        } else {
+            MetaMap::with_layout(capacity, meta_bits, layout)
+        }
+    }
+
+    fn should_upgrade(&self) -> bool {
+        let stats = self.meta.hint_stats();
+        self.adaptive
+            && self.meta.bits() < ADAPTIVE_MAX_BITS
+            && stats.false_matches + stats.rejections >= ADAPTIVE_WINDOW
+            && stats.false_positive_rate() > ADAPTIVE_FP_RATE
+    }
+
+    // rebuild the metamap with twice the bits, rehashing every stored key.
+    fn upgrade(&mut self, update: &mut Update) {
+        let bits = (2 * self.meta.bits()).clamp(1, ADAPTIVE_MAX_BITS);
+        explain!(
+            "false positive rate {:.3}, upgrade metadata to {bits} bits",
+            self.meta.hint_stats().false_positive_rate()
+        );
+
+        let mut meta = Self::meta_map(self.buckets.len(), bits, self.tombstone_bit, self.layout);
+        for (bucket, item) in self.buckets.iter().enumerate() {
+            match item {
+                BucketItem::Value(key) => {
+                    meta.set_full(bucket, Metadata::Hash(self.hasher.hash_one(*key)));
+                    update.total_writes += 1;
+                }
+                BucketItem::Tombstone => meta.set_tombstone(bucket),
+                BucketItem::Empty => {}
+            }
+        }
+
+        self.retire_meta(meta);
+        self.upgrades += 1;
+    }
+
+    fn retire_meta(&mut self, meta: MetaMap) {
+        let old = std::mem::replace(&mut self.meta, meta);
+        self.retired_hint_stats = [self.retired_hint_stats, old.hint_stats()]
+            .into_iter()
+            .sum();
+    }
+
+    fn should_compact(&self) -> bool {
+        self.compact_at
+            .is_some_and(|percent| self.tombstones * 100 >= percent * self.buckets.len())
+    }
+
+    // place every key again from its home bucket, into a table without tombstones.
+    fn compact(&mut self, update: &mut Update) {
+        explain!("{} tombstones, compact", self.tombstones);
+        let capacity = self.buckets.len();
+        let bits = self.meta.bits();
+        let meta = if self.max_runs {
+            MetaMap::counting(capacity, bits)
+        } else {
+            Self::meta_map(capacity, bits, self.tombstone_bit, self.layout)
+        };
+        self.retire_meta(meta);
+        let old = std::mem::replace(
+            &mut self.buckets,
+            bucket_array(capacity, BucketItem::Empty, self.options),
+        );
+
+        for item in old.iter() {
+            let &BucketItem::Value(key) = item else {
+                continue;
+            };
+
+            // with no tombstones left, the key goes to the first empty bucket.
+            let home = self.home_bucket(key);
+            let stride = self.stride(key);
+            for i in 0..capacity {
+                let bucket = self.bucket(home, stride, i);
+                if self.is_empty(bucket) {
+                    self.set_bucket(bucket, BucketItem::Value(key));
+                    if self.max_runs {
+                        self.meta.raise_max_run(home, i);
+                    }
+                    break;
+                }
+            }
+            update.total_writes += 1;
+            update.rebuild_moves += 1;
+        }
+
+        self.tombstones = 0;
+        update.rebuilds += 1;
+    }
+
+    // the stride between the buckets probed for a key, with double hashing. it is coprime
+    // with the capacity, so that the probe sequence visits every bucket.
+    fn stride(&self, key: u64) -> usize {
+        let capacity = self.buckets.len();
+        let Some(stride_hasher) = &self.stride_hasher else {
+            return 1;
+        };
+        if capacity == 1 {
+            return 1;
+        }
+
+        let mut stride = 1 + (stride_hasher.hash_one(key) % (capacity as u64 - 1)) as usize;
+        while gcd(stride, capacity) != 1 {
+            stride = stride % (capacity - 1) + 1;
+        }
+        stride
+    }
+
+    // the `i`th bucket probed from `home`, for a key with the given stride.
+    fn bucket(&self, home: usize, stride: usize, i: usize) -> usize {
+        (home + self.sequence.offset(i, stride)) % self.buckets.len()
+    }
+
+    fn explain_home(&self, home: usize, stride: usize) {
+        if self.stride_hasher.is_some() {
+            explain!("home bucket {home}, stride {stride}");
+        } else {
+            explain!("home bucket {home}");
+        }
+    }
+
+    // To search a key the probe sequence is followed,
+    // it ends only if the searched key is found or an empty bucket is found
+    //
+    // return a tuple containing:
+    //  + option containing the index of the slot
+    //  + number of probes
+    fn probe_search(&self, key: u64) -> (Option<usize>, usize) {
+        let hash = self.hasher.hash_one(key);
+        let bucket = (hash % (self.buckets.len() as u64)) as usize;
+        let stride = self.stride(key);
+        self.explain_home(bucket, stride);
+        let max_run = self.meta.hint_max_run(bucket);
+        let mut probes = 0;
+
+        // Every bucket may be probed
+        for i in 0..self.buckets.len() {
+            let bucket_index = self.bucket(bucket, stride, i);
+
+            if max_run.is_some_and(|max_run| i > max_run) {
+                check_hint(|| !self.holds_from(bucket, stride, i, key), bucket);
+                explain!("no key homed at bucket {bucket} went this far, the key is absent");
+                return (None, probes);
+            }
+
+            // The probing will be performed on the metamap and only if needed the check will be propagated
+
+            if self.meta.hint_empty(bucket_index) {
+                // If an empty is found in the metamap we're sure it is empty also in the buckets
+                check_hint(|| self.is_empty(bucket_index), bucket_index);
+                explain!("bucket {bucket_index}: metadata says empty, the key is absent");
+                return (None, probes);
+            }
+
+            // We will check the value stored in storage only when there is a match in the metamap
+            if !self.meta.hint_not_match(bucket_index, hash) {
+                probes += 1;
+                self.meta.record_read(!self.doesnt_hold(bucket_index, key));
+                match &self.buckets[bucket_index] {
+                    // we want to be sure this is the correct bucket_index
+                    BucketItem::Value(found_key) if key == *found_key => {
+                        explain!("bucket {bucket_index}: read, holds the key");
+                        return (Some(bucket_index), probes);
+                    }
+                    BucketItem::Empty => {
+                        // This should be reached ONLY if the map uses 0 metabits
+                        assert!(self.meta.bits() == 0 || self.max_runs);
+                        explain!("bucket {bucket_index}: read, empty, the key is absent");
+                        return (None, probes);
+                    }
+                    BucketItem::Tombstone => {
+                        // This should be reached ONLY if the map uses less than 2 metabits
+                        assert!(self.meta.bits() < 2 || self.max_runs);
+                        explain!("bucket {bucket_index}: read, tombstone");
+                    }
+                    BucketItem::Value(found_key) => {
+                        explain!("bucket {bucket_index}: read, holds {found_key}")
+                    }
+                };
+            } else {
+                check_hint(|| self.doesnt_hold(bucket_index, key), bucket_index);
+                explain!("bucket {bucket_index}: metadata rules the key out, skip");
+            }
+
+            // If it does not match in the metamap, then we can proceed with the probing
+        }
+
+        (None, self.buckets.len())
+    }
+
+    // In order to insert a value into the hash map,
+    // we need to search for the key we intend to insert
+    // until an empty bucket is found. The first tombstone
+    // on the way is reused, if there is one.
+    //
+    // return a tuple containing:
+    //  + option containing the index of the slot to use, the number of steps it is from
+    //    the home bucket, and whether it already holds the key
+    //  + number of probes
+    fn probe_insert(&self, key: u64) -> (Option<(usize, usize, bool)>, usize) {
+        let hash = self.hasher.hash_one(key);
+        let bucket = (hash % (self.buckets.len() as u64)) as usize;
+        let stride = self.stride(key);
+        self.explain_home(bucket, stride);
+        let mut probes = 0;
+        let mut tombstone = None;
+
+        // Every bucket may be probed
+        for i in 0..self.buckets.len() {
+            let bucket_index = self.bucket(bucket, stride, i);
+
+            if self.meta.hint_empty(bucket_index) {
+                check_hint(|| self.is_empty(bucket_index), bucket_index);
+                explain!("bucket {bucket_index}: metadata says empty, the key is absent");
+                let (slot, run) = tombstone.unwrap_or((bucket_index, i));
+                return (Some((slot, run, false)), probes);
+            }
+
+            if self.meta.hint_tombstone(bucket_index) {
+                check_hint(
+                    || matches!(self.buckets[bucket_index], BucketItem::Tombstone),
+                    bucket_index,
+                );
+                explain!("bucket {bucket_index}: metadata says tombstone, remember it for reuse");
+                tombstone = tombstone.or(Some((bucket_index, i)));
+                continue;
+            }
+
+            if !self.meta.hint_not_match(bucket_index, hash) {
+                probes += 1;
+                self.meta.record_read(!self.doesnt_hold(bucket_index, key));
+                match self.buckets[bucket_index] {
+                    BucketItem::Empty => {
+                        assert!(self.meta.bits() == 0 || self.max_runs);
+                        explain!("bucket {bucket_index}: read, empty, the key is absent");
+                        let (slot, run) = tombstone.unwrap_or((bucket_index, i));
+                        return (Some((slot, run, false)), probes);
+                    }
+                    BucketItem::Tombstone => {
+                        assert!(self.meta.bits() < 2 || self.max_runs);
+                        explain!("bucket {bucket_index}: read, tombstone, remember it for reuse");
+                        tombstone = tombstone.or(Some((bucket_index, i)));
+                    }
+                    BucketItem::Value(found_key) if key == found_key => {
+                        explain!("bucket {bucket_index}: read, already holds the key");
+                        return (Some((bucket_index, i, true)), probes);
+                    }
+                    BucketItem::Value(found_key) => {
+                        explain!("bucket {bucket_index}: read, holds {found_key}")
+                    }
+                };
+            } else {
+                check_hint(|| self.doesnt_hold(bucket_index, key), bucket_index);
+                explain!("bucket {bucket_index}: metadata rules the key out, skip");
+            }
+        }
+
+        (
+            tombstone.map(|(bucket_index, run)| (bucket_index, run, false)),
+            self.buckets.len(),
+        )
+    }
+
+    // whether the key is in the probe sequence from `home`, `from` steps on, before an
+    // empty bucket.
+    fn holds_from(&self, home: usize, stride: usize, from: usize, key: u64) -> bool {
+        for i in from..self.buckets.len() {
+            match self.buckets[self.bucket(home, stride, i)] {
+                BucketItem::Value(found_key) if found_key == key => return true,
+                BucketItem::Empty => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn is_empty(&self, bucket: usize) -> bool {
+        matches!(self.buckets[bucket], BucketItem::Empty)
+    }
+
+    fn doesnt_hold(&self, bucket: usize, key: u64) -> bool {
+        !matches!(self.buckets[bucket], BucketItem::Value(found_key) if found_key == key)
+    }
+
+    fn set_bucket(&mut self, bucket: usize, item: BucketItem) {
+        match item {
+            BucketItem::Value(key) => {
+                let hash = self.hasher.hash_one(key);
+                self.meta.set_full(bucket, Metadata::Hash(hash));
+            }
+            // compaction starts over from a table of empty buckets instead.
+            BucketItem::Empty => unreachable!(),
+            BucketItem::Tombstone => {
+                self.meta.set_tombstone(bucket);
+            }
+        }
+        self.buckets[bucket] = item;
+    }
+}
+
+impl Map for OpenAddressing {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        (self.hasher.hash_one(key) % (self.buckets.len() as u64)) as usize
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        [Some(&self.hasher), self.stride_hasher.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|h| h.stats())
+            .sum()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        [self.retired_hint_stats, self.meta.hint_stats()]
+            .into_iter()
+            .sum()
+    }
+
+    // triangular probing suffers secondary clustering only, for which Knuth gives
+    // 1 - ln(1-a) - a/2 probes for hits and 1/(1-a) - a - ln(1-a) for misses. double
+    // hashing behaves like uniform hashing: -ln(1-a)/a probes for hits and 1/(1-a) for
+    // misses. quadratic probing, which can't reach every bucket, and adaptive and
+    // max-run metadata follow no such analysis.
+    fn expected_probes(&self, load_factor: f64) -> ExpectedProbes {
+        if self.adaptive || self.max_runs {
+            return ExpectedProbes::default();
+        }
+        let ln = (1.0 - load_factor).ln();
+        let (present, absent) = match self.sequence {
+            ProbeSequence::Triangular => (
+                1.0 - ln - load_factor / 2.0,
+                1.0 / (1.0 - load_factor) - load_factor - ln,
+            ),
+            ProbeSequence::Double => (-ln / load_factor, 1.0 / (1.0 - load_factor)),
+            ProbeSequence::Quadratic => return ExpectedProbes::default(),
+        };
+        ExpectedProbes {
+            present: Some(self.meta.expected_reads(present, true)),
+            absent: Some(self.meta.expected_reads(absent, false)),
+        }
+    }
+
+    fn extra_meta_bits(&self) -> usize {
+        self.meta.extra_bits()
+    }
+
+    fn meta_upgrades(&self) -> usize {
+        self.upgrades
+    }
+
+    fn occupied(&self) -> Option<Vec<bool>> {
+        Some(
+            self.buckets
+                .iter()
+                .map(|item| !matches!(item, BucketItem::Empty))
+                .collect(),
+        )
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (probe_result, probes) = self.probe_search(key);
+
+        Probe {
+            contained: probe_result.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let mut update = Update {
+            total_probes: 0,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let (probe_result, total_probes) = self.probe_insert(key);
+        update.total_probes = total_probes;
+
+        let Some((bucket_index, run, contained)) = probe_result else {
+            update.completed = false;
+            return update;
+        };
+
+        if contained {
+            return update;
+        }
+
+        explain!("place the key in bucket {bucket_index}");
+        self.len += 1;
+        if matches!(self.buckets[bucket_index], BucketItem::Tombstone) {
+            self.tombstones -= 1;
+        }
+        self.set_bucket(bucket_index, BucketItem::Value(key));
+        if self.max_runs {
+            let home = self.home_bucket(key);
+            self.meta.raise_max_run(home, run);
+        }
+
+        if self.should_upgrade() {
+            self.upgrade(&mut update);
+        }
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let meta = self.meta.describe(bucket);
+                match self.buckets[bucket] {
+                    BucketItem::Value(key) => format!("{bucket} {key} meta={meta}"),
+                    BucketItem::Empty => format!("{bucket} - meta={meta}"),
+                    BucketItem::Tombstone => format!("{bucket} tombstone meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let mut update = Update {
+            total_probes: 0,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let (probe_result, total_probes) = self.probe_search(key);
+        let Some(bucket_index) = probe_result else {
+            update.completed = false;
+            update.total_probes = total_probes;
+            return update;
+        };
+
+        explain!("leave a tombstone in bucket {bucket_index}");
+        self.len -= 1;
+        self.tombstones += 1;
+        self.set_bucket(bucket_index, BucketItem::Tombstone);
+
+        if self.should_compact() {
+            self.compact(&mut update);
+        }
+        update
+    }
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
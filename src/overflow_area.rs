@@ -0,0 +1,275 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::candidates;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+use std::cell::Cell;
+
+// the most buckets of the main region a key may probe before it overflows.
+pub const MAX_PROBES: usize = 8;
+pub const OVERFLOW_SLOTS: usize = 8;
+
+// the fraction of buckets in the overflow region.
+const OVERFLOW_FACTOR: f64 = 0.05;
+
+// dummy hash-set for u64 keys.
+//
+// implements bounded linear probing with an overflow region: a key may sit in any of
+// the MAX_PROBES buckets from its home in the main region. keys which find them all
+// full go to a bucket of OVERFLOW_SLOTS slots in the small overflow region, chosen by
+// a second hash. every home bucket counts its keys in the overflow region, so that
+// lookups only search there when it holds some, and can be counted as spills.
+//
+// as removals leave plain holes, lookups read every bucket of the window which
+// metadata can't rule out. every bucket read counts as one probe.
+pub struct OverflowArea {
+    hashers: Vec<CountingHasher>,
+    buckets: BucketArray<Option<u64>>,
+    meta: MetaMap,
+    len: usize,
+    main_len: usize,
+    overflow_buckets: usize,
+    // per home bucket, how many of its keys are in the overflow region.
+    overflow: Vec<u32>,
+    overflow_len: usize,
+    spills: Cell<usize>,
+    spill_probes: Cell<usize>,
+}
+
+impl OverflowArea {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        let overflow_buckets = std::cmp::max(
+            1,
+            (capacity as f64 * OVERFLOW_FACTOR) as usize / OVERFLOW_SLOTS,
+        );
+        let main_len = std::cmp::max(
+            MAX_PROBES,
+            capacity.saturating_sub(overflow_buckets * OVERFLOW_SLOTS),
+        );
+        let capacity = main_len + overflow_buckets * OVERFLOW_SLOTS;
+        OverflowArea {
+            hashers: (0..2).map(|i| options.hasher(i)).collect(),
+            buckets: bucket_array(capacity, None, options),
+            meta: MetaMap::new(capacity, meta_bits),
+            len: 0,
+            main_len,
+            overflow_buckets,
+            overflow: vec![0; main_len],
+            overflow_len: 0,
+            spills: Cell::new(0),
+            spill_probes: Cell::new(0),
+        }
+    }
+
+    // (hash, home bucket)
+    fn home(&self, key: u64) -> (u64, usize) {
+        let hash = self.hashers[0].hash_one(key);
+        (hash, (hash % self.main_len as u64) as usize)
+    }
+
+    // the buckets of the main region a key with this home may use.
+    fn window(&self, home: usize) -> Vec<usize> {
+        (0..MAX_PROBES)
+            .map(|i| (home + i) % self.main_len)
+            .collect()
+    }
+
+    // the slots of the key's bucket in the overflow region, which follows the main one.
+    fn overflow_slots(&self, key: u64) -> std::ops::Range<usize> {
+        let bucket = (self.hashers[1].hash_one(key) % self.overflow_buckets as u64) as usize;
+        let first = self.main_len + bucket * OVERFLOW_SLOTS;
+        first..first + OVERFLOW_SLOTS
+    }
+
+    // look in the main region, then in the overflow region if any of the home
+    // bucket's keys are there.
+    //
+    // return a tuple containing:
+    //  + option containing the bucket holding the key
+    //  + option containing the probes made in the overflow region, if it was searched
+    //  + number of probes
+    fn search(&self, key: u64) -> (Option<usize>, Option<usize>, usize) {
+        let (hash, home) = self.home(key);
+        explain!("home bucket {home}");
+        let (found, mut probes) =
+            candidates::find(&self.meta, &self.buckets, &self.window(home), key, hash);
+        if found.is_some() || self.overflow[home] == 0 {
+            return (found, None, probes);
+        }
+
+        explain!(
+            "{} keys of the home are in the overflow region",
+            self.overflow[home]
+        );
+        let (found, overflow_probes) = candidates::find_range(
+            &self.meta,
+            &self.buckets,
+            self.overflow_slots(key),
+            key,
+            hash,
+        );
+        probes += overflow_probes;
+        (found, Some(overflow_probes), probes)
+    }
+
+    // the first empty bucket, reading buckets whose metadata can't tell.
+    fn first_empty(&self, buckets: &[usize], update: &mut Update) -> Option<usize> {
+        buckets.iter().copied().find(|&bucket| {
+            if self.meta.hint_empty(bucket) {
+                check_hint(|| self.buckets[bucket].is_none(), bucket);
+                true
+            } else if self.meta.bits() == 0 {
+                update.total_probes += 1;
+                self.buckets[bucket].is_none()
+            } else {
+                false
+            }
+        })
+    }
+
+    fn set_bucket(&mut self, bucket: usize, key: u64, hash: u64) {
+        self.buckets[bucket] = Some(key);
+        self.meta.set_full(bucket, Metadata::Hash(hash));
+    }
+}
+
+impl Map for OverflowArea {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.home(key).1
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hashers.iter().map(|h| h.stats()).sum()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: std::mem::size_of_val(&self.overflow[..]),
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        self.meta.hint_stats()
+    }
+
+    fn spills(&self) -> usize {
+        self.spills.get()
+    }
+
+    fn spill_probes(&self) -> usize {
+        self.spill_probes.get()
+    }
+
+    fn overflow_occupancy(&self) -> f64 {
+        self.overflow_len as f64 / (self.overflow_buckets * OVERFLOW_SLOTS) as f64
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, spilled, probes) = self.search(key);
+        if let Some(spill_probes) = spilled {
+            self.spills.set(self.spills.get() + 1);
+            self.spill_probes
+                .set(self.spill_probes.get() + spill_probes);
+        }
+
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let (found, _, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if found.is_some() {
+            return update;
+        }
+
+        let (hash, home) = self.home(key);
+        if let Some(bucket) = self.first_empty(&self.window(home), &mut update) {
+            explain!("place the key in bucket {bucket}");
+            self.set_bucket(bucket, key, hash);
+            self.len += 1;
+            return update;
+        }
+
+        let overflow_slots: Vec<usize> = self.overflow_slots(key).collect();
+        let Some(bucket) = self.first_empty(&overflow_slots, &mut update) else {
+            explain!("the window and the overflow bucket are full, give up");
+            update.completed = false;
+            return update;
+        };
+
+        explain!("the window is full, place the key in overflow slot {bucket}");
+        self.set_bucket(bucket, key, hash);
+        self.overflow[home] += 1;
+        self.overflow_len += 1;
+        self.len += 1;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let meta = self.meta.describe(bucket);
+                let region = if bucket < self.main_len {
+                    "main"
+                } else {
+                    "overflow"
+                };
+                match self.buckets[bucket] {
+                    Some(key) => format!("{bucket} ({region}) {key} meta={meta}"),
+                    None => format!("{bucket} ({region}) - meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, _, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some(bucket) = found else {
+            return update;
+        };
+
+        explain!("clear bucket {bucket}");
+        self.buckets[bucket] = None;
+        self.meta.set_empty(bucket);
+        if bucket >= self.main_len {
+            let home = self.home(key).1;
+            self.overflow[home] -= 1;
+            self.overflow_len -= 1;
+        }
+        self.len -= 1;
+        update.total_writes += 1;
+        update
+    }
+}
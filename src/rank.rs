@@ -0,0 +1,134 @@
+use crate::series_columns;
+use csv::{ReaderBuilder, Writer};
+use std::collections::BTreeMap;
+
+// weights of each kind of access, in whatever unit the deployment cares about.
+#[derive(Clone, Copy, Debug)]
+pub struct CostProfile {
+    pub meta_read: f64,
+    pub bucket_read: f64,
+    pub bucket_write: f64,
+    pub page_io: f64,
+}
+
+// the mean accesses of one operation, averaged over present lookups, absent lookups
+// and churn updates.
+//
+// the results don't record metadata reads or pages touched, so those are estimated:
+// one metadata read per bucket read when the scheme keeps metadata, and one page per
+// operation plus one per bucket written back.
+#[derive(Clone, Copy, Debug)]
+struct Accesses {
+    meta_reads: f64,
+    bucket_reads: f64,
+    bucket_writes: f64,
+    pages: f64,
+}
+
+impl Accesses {
+    fn cost(&self, profile: &CostProfile) -> f64 {
+        self.meta_reads * profile.meta_read
+            + self.bucket_reads * profile.bucket_read
+            + self.bucket_writes * profile.bucket_write
+            + self.pages * profile.page_io
+    }
+}
+
+// (load factor, meta bits) -> the mean of the first and second series.
+type Means = BTreeMap<(String, usize), (f64, f64)>;
+
+fn read_means(path: &str) -> Option<Means> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .ok()?;
+
+    let mut means = Means::new();
+    for record in reader.records() {
+        let record = record.unwrap();
+        let key = (record[0].to_string(), record[2].parse().unwrap());
+        means.insert(
+            key,
+            (
+                record[3].parse().unwrap(),
+                record[3 + series_columns()].parse().unwrap(),
+            ),
+        );
+    }
+
+    Some(means)
+}
+
+// rank every (scheme, meta bits) configuration in `dir` by its mean cost per operation
+// under `profile`, at each load factor both the probe and churn tests reached.
+//
+// writes `{dir}/rank_costs.csv` and prints the cheapest configuration per load factor.
+pub fn run(dir: &str, profile: CostProfile) {
+    // load factor -> [(cost, scheme, meta bits)]
+    let mut by_load: BTreeMap<String, Vec<(f64, String, usize)>> = BTreeMap::new();
+
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let file_name = entry.unwrap().file_name().into_string().unwrap();
+        let Some(scheme) = file_name
+            .strip_prefix("probe_")
+            .and_then(|rest| rest.strip_suffix(".csv"))
+        else {
+            continue;
+        };
+
+        let Some(probes) = read_means(&format!("{dir}/probe_{scheme}.csv")) else {
+            continue;
+        };
+        let Some(churn) = read_means(&format!("{dir}/churn_{scheme}.csv")) else {
+            continue;
+        };
+
+        for ((load_factor, meta_bits), (present, absent)) in probes {
+            let Some(&(update_probes, update_writes)) =
+                churn.get(&(load_factor.clone(), meta_bits))
+            else {
+                continue;
+            };
+
+            let bucket_reads = (present + absent + update_probes) / 3.0;
+            let bucket_writes = update_writes / 3.0;
+            let accesses = Accesses {
+                meta_reads: if meta_bits > 0 { bucket_reads } else { 0.0 },
+                bucket_reads,
+                bucket_writes,
+                pages: 1.0 + bucket_writes,
+            };
+
+            by_load.entry(load_factor).or_default().push((
+                accesses.cost(&profile),
+                scheme.to_string(),
+                meta_bits,
+            ));
+        }
+    }
+
+    let mut writer = Writer::from_path(format!("{dir}/rank_costs.csv")).unwrap();
+    for (load_factor, configs) in &mut by_load {
+        configs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (rank, (cost, scheme, meta_bits)) in configs.iter().enumerate() {
+            writer
+                .write_record([
+                    load_factor.clone(),
+                    format!("{}", rank + 1),
+                    scheme.clone(),
+                    format!("{meta_bits}"),
+                    format!("{cost:.2}"),
+                ])
+                .unwrap();
+        }
+
+        let (cost, scheme, meta_bits) = &configs[0];
+        println!("load {load_factor}: {scheme}:{meta_bits} at {cost:.2} per op");
+    }
+
+    writer.flush().unwrap();
+    if by_load.is_empty() {
+        println!("no probe and churn results found in {dir}");
+    }
+}
@@ -0,0 +1,184 @@
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use crate::meta_map::count_accesses;
+use crate::{explain, KeySet, Map, MapOptions, MapSpec, EXPLAIN_CAPACITY};
+
+const HELP: &str = "\
+new <spec> [capacity]        create a map, e.g. `new robinhood:4 64`
+insert|remove|probe <key>    run an op on the map
+fill <load>                  insert fresh keys until the load factor is reached
+dump                         show every bucket, with its metadata
+bucket <index>               show a single bucket
+stats                        show the size and hash counts of the map
+explain on|off               narrate every op step by step
+help                         show this
+quit";
+
+// the current map, with the keys handed out by `fill`.
+struct Session {
+    map: Box<dyn Map>,
+    map_spec: MapSpec,
+    keys: KeySet,
+}
+
+// read commands from stdin, one per line, until `quit` or the end of input. maps are
+// built with `options`.
+pub fn run(options: MapOptions) {
+    let mut session: Option<Session> = None;
+    let stdin = std::io::stdin();
+
+    println!("{HELP}");
+    loop {
+        print!("> ");
+        std::io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            [] => {}
+            ["quit"] | ["exit"] => break,
+            ["help"] => println!("{HELP}"),
+            ["new", spec, ref capacity @ ..] => {
+                let capacity = match capacity {
+                    [] => Ok(EXPLAIN_CAPACITY),
+                    [capacity] => capacity.parse().map_err(|_| "invalid capacity".to_string()),
+                    _ => Err(HELP.to_string()),
+                };
+                let built = capacity.and_then(|capacity| {
+                    let map_spec = MapSpec::from_str(spec)?;
+                    if capacity < map_spec.min_capacity() {
+                        return Err(format!(
+                            "{map_spec} needs a capacity of at least {}",
+                            map_spec.min_capacity()
+                        ));
+                    }
+                    Ok((map_spec, capacity))
+                });
+                match built {
+                    Ok((map_spec, capacity)) => {
+                        let map = map_spec.build_with_capacity(capacity, options);
+                        println!("{map_spec} with {} buckets", map.capacity());
+                        session = Some(Session {
+                            map,
+                            map_spec,
+                            keys: KeySet::new(options.hashes.key_rng()),
+                        });
+                    }
+                    Err(err) => println!("{err}"),
+                }
+            }
+            ["explain", "on"] => explain::set_enabled(true),
+            ["explain", "off"] => explain::set_enabled(false),
+            _ => match session.as_mut() {
+                Some(session) => session.command(&words),
+                None => println!("no map yet, create one with `new <spec>`"),
+            },
+        }
+    }
+}
+
+impl Session {
+    fn command(&mut self, words: &[&str]) {
+        let map = &mut self.map;
+        match *words {
+            [op @ ("insert" | "remove" | "probe"), key] => {
+                let Ok(key) = key.parse() else {
+                    println!("invalid key {key}");
+                    return;
+                };
+
+                if op == "probe" {
+                    let (probe, accesses) = count_accesses(|| map.probe(key));
+                    println!(
+                        "contained: {}, probes: {}, bucket lines: {}, meta lines: {}",
+                        probe.contained, probe.probes, accesses.bucket_lines, accesses.meta_lines
+                    );
+                    return;
+                }
+
+                let (update, accesses) = count_accesses(|| {
+                    if op == "insert" {
+                        map.insert(key)
+                    } else {
+                        map.remove(key)
+                    }
+                });
+                println!(
+                    "completed: {}, probes: {}, writes: {}, evictions: {}, splits: {}, \
+                     rebuilds: {}, rebuild moves: {}, bucket lines: {}, meta lines: {}",
+                    update.completed,
+                    update.total_probes,
+                    update.total_writes,
+                    update.evictions,
+                    update.splits,
+                    update.rebuilds,
+                    update.rebuild_moves,
+                    accesses.bucket_lines,
+                    accesses.meta_lines
+                );
+            }
+            ["fill", load_factor] => {
+                let Ok(load_factor) = load_factor.parse::<f64>() else {
+                    println!("invalid load factor {load_factor}");
+                    return;
+                };
+
+                let explaining = explain::enabled();
+                explain::set_enabled(false);
+                // an insert into a full map of a fixed size may never return.
+                while map.load_factor() < load_factor {
+                    if !map.resizable() && map.len() == map.capacity() {
+                        println!("the map is full");
+                        break;
+                    }
+                    let key = self.keys.push();
+                    if !map.insert(key).completed {
+                        println!("insert of {key} failed");
+                        break;
+                    }
+                }
+                explain::set_enabled(explaining);
+                println!("{} keys, load {:.2}", map.len(), map.load_factor());
+            }
+            ["dump"] => {
+                for line in map.dump() {
+                    println!("{line}");
+                }
+            }
+            ["bucket", index] => match index
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| map.dump().into_iter().nth(i))
+            {
+                Some(line) => println!("{line}"),
+                None => println!("no bucket {index}"),
+            },
+            ["stats"] => {
+                let hash_stats = map.hash_stats();
+                println!(
+                    "{}: {} keys in {} buckets, load {:.2}, {} hashes computed, {} served from cache",
+                    self.map_spec,
+                    map.len(),
+                    map.capacity(),
+                    map.load_factor(),
+                    hash_stats.invocations,
+                    hash_stats.cache_hits
+                );
+                let hint_stats = map.hint_stats();
+                println!(
+                    "metadata: {} buckets ruled out, {} read, of which {} false matches ({:.3} false positive rate)",
+                    hint_stats.rejections,
+                    hint_stats.matches,
+                    hint_stats.false_matches,
+                    hint_stats.false_positive_rate()
+                );
+            }
+            _ => println!("unknown command, try `help`"),
+        }
+    }
+}
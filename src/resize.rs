@@ -0,0 +1,166 @@
+use crate::{Map, MapOptions, MapSpec, MemoryUsage, Update};
+
+// how a growing map keeps to its target load.
+#[derive(Clone, Copy, Debug)]
+pub enum Rebuild {
+    // once past the target load, move every key into a map of twice the capacity at once.
+    Doubling,
+    // as with doubling, but the keys are moved MIGRATE_PER_INSERT at a time by the
+    // inserts which follow, and the old map lives on until they are all moved.
+    Incremental,
+    // allocate for the final number of keys up front. only rebuilds if an insert fails.
+    OverProvisioned,
+}
+
+const MIGRATE_PER_INSERT: usize = 4;
+
+// a map which rebuilds itself into a larger one as keys are added.
+//
+// none of the maps can iterate their keys, so every key inserted is kept aside
+// for the rebuilds. removals aren't supported.
+pub struct Resizing {
+    map_spec: MapSpec,
+    options: MapOptions,
+    rebuild: Rebuild,
+    target_load: f64,
+    map: Box<dyn Map>,
+    // the map being migrated from, and the keys still to be moved out of it.
+    old: Option<(Box<dyn Map>, Vec<u64>)>,
+    keys: Vec<u64>,
+    pub rebuilds: usize,
+}
+
+impl Resizing {
+    pub fn new(
+        map_spec: MapSpec,
+        options: MapOptions,
+        rebuild: Rebuild,
+        target_load: f64,
+        capacity: usize,
+    ) -> Self {
+        Resizing {
+            map_spec,
+            options,
+            rebuild,
+            target_load,
+            map: map_spec.build_with_capacity(capacity, options),
+            old: None,
+            keys: Vec::new(),
+            rebuilds: 0,
+        }
+    }
+
+    pub fn load_factor(&self) -> f64 {
+        self.map.load_factor()
+    }
+
+    // the bytes of the current map and the one being migrated from. the keys kept aside
+    // for rebuilds are auxiliary.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = self.map.memory_usage();
+        if let Some((old, pending)) = &self.old {
+            usage = usage + old.memory_usage();
+            usage.auxiliary += std::mem::size_of_val(&pending[..]);
+        }
+        usage.auxiliary += std::mem::size_of_val(&self.keys[..]);
+        usage
+    }
+
+    // insert a key, along with whatever migration or rebuild it triggers.
+    pub fn insert(&mut self, key: u64) -> Update {
+        let mut update = self.migrate();
+        let insertion = self.map.insert(key);
+        self.keys.push(key);
+        absorb(&mut update, insertion);
+
+        let over_target = match self.rebuild {
+            Rebuild::Doubling | Rebuild::Incremental => self.map.load_factor() > self.target_load,
+            Rebuild::OverProvisioned => false,
+        };
+        if !update.completed || over_target {
+            update.completed = true;
+            let growth = self.grow();
+            absorb(&mut update, growth);
+        }
+
+        update
+    }
+
+    // move up to MIGRATE_PER_INSERT keys out of the old map.
+    fn migrate(&mut self) -> Update {
+        let mut update = Update {
+            total_probes: 0,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some((old, pending)) = &mut self.old else {
+            return update;
+        };
+
+        for _ in 0..MIGRATE_PER_INSERT {
+            let Some(key) = pending.pop() else {
+                break;
+            };
+
+            absorb(&mut update, old.remove(key));
+            absorb(&mut update, self.map.insert(key));
+            update.rebuild_moves += 1;
+        }
+
+        if pending.is_empty() {
+            self.old = None;
+        }
+
+        update
+    }
+
+    // double the capacity, rebuilding all at once or starting a migration.
+    fn grow(&mut self) -> Update {
+        self.rebuilds += 1;
+
+        // a migration still in flight is finished first.
+        let mut update = self.migrate();
+        while self.old.is_some() {
+            let migration = self.migrate();
+            absorb(&mut update, migration);
+        }
+
+        let capacity = 2 * self.map.capacity();
+        let map = self.map_spec.build_with_capacity(capacity, self.options);
+        let old = std::mem::replace(&mut self.map, map);
+        update.rebuilds += 1;
+
+        match self.rebuild {
+            Rebuild::Doubling | Rebuild::OverProvisioned => {
+                for &key in &self.keys {
+                    absorb(&mut update, self.map.insert(key));
+                }
+                update.rebuild_moves += self.keys.len();
+            }
+            Rebuild::Incremental => self.old = Some((old, self.keys.clone())),
+        }
+
+        if !update.completed {
+            update.completed = true;
+            let growth = self.grow();
+            absorb(&mut update, growth);
+        }
+
+        update
+    }
+}
+
+fn absorb(total: &mut Update, update: Update) {
+    total.total_probes += update.total_probes;
+    total.total_writes += update.total_writes;
+    total.evictions += update.evictions;
+    total.splits += update.splits;
+    total.rebuilds += update.rebuilds;
+    total.rebuild_moves += update.rebuild_moves;
+    total.completed &= update.completed;
+}
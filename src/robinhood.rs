@@ -1,115 +1,242 @@
-use crate::meta_map::{MetaMap, Metadata, PslHint};
-use crate::{Map, Probe, Update};
-use ahash::RandomState;
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata, PslHint};
+use crate::{ExpectedProbes, Map, MapOptions, MemoryUsage, Probe, Update};
+
+// how the home bucket (and so the PSL) of a stored key is found.
+#[derive(Clone, Copy)]
+pub enum PslSource {
+    // rehash the stored key.
+    Rehash,
+    // use the PSL stored in the metamap when it is exact, rehashing otherwise.
+    Metadata,
+    // keep the home bucket of every stored key in an array beside the buckets.
+    CachedHome,
+    // like `Metadata`, with the metamap split between a PSL of this many bits and a
+    // hash fingerprint, so that buckets whose fingerprint differs can be skipped.
+    Split(usize),
+}
+
+// the most PSL bits of a split metamap.
+pub const MAX_SPLIT_PSL_BITS: usize = 4;
+
+// where lookups and removals start searching for a key.
+#[derive(Clone, Copy)]
+pub enum Lookup {
+    // at the home bucket, moving forwards.
+    Linear,
+    // at the mean PSL of the map, moving outwards in both directions (organ-pipe
+    // search). what is read narrows the PSLs the key could have, as keys in a
+    // robin-hood cluster are ordered by home bucket.
+    Smart,
+}
+
+// the buckets of a 4 KiB page, for maps whose buckets live on disk.
+pub const PAGE_SLOTS: usize = 512;
+
+// the pages of buckets an operation has read. with pages of one bucket every read is a
+// probe, otherwise only the first read of each page is.
+struct Pages {
+    page_slots: usize,
+    touched: Vec<usize>,
+}
+
+impl Pages {
+    fn read(&mut self, bucket: usize) -> usize {
+        if self.page_slots == 1 {
+            return 1;
+        }
+        let page = bucket / self.page_slots;
+        if self.touched.contains(&page) {
+            return 0;
+        }
+        explain!("page {page}: touched");
+        self.touched.push(page);
+        1
+    }
+}
 
 // dummy hash-set for u64 keys.
 //
-// implements robin-hood-hashing with backward-shift deletion
+// implements robin-hood-hashing with backward-shift deletion. buckets may be grouped
+// into pages of `page_slots`, modelling a table on disk with its metamap in memory,
+// where a probe is a page read rather than a bucket read.
 pub struct RobinHood {
-    hasher: RandomState,
-    buckets: Vec<Option<u64>>,
+    hasher: CountingHasher,
+    buckets: BucketArray<Option<u64>>,
     meta: MetaMap,
     len: usize,
+    psl_source: PslSource,
+    lookup: Lookup,
+    // only populated with `PslSource::CachedHome`.
+    homes: BucketArray<usize>,
+    // the sum of the PSLs of all keys, and an upper bound on them, for smart lookups.
+    psl_sum: usize,
+    max_psl: usize,
+    page_slots: usize,
 }
 
 impl RobinHood {
-    pub fn new(capacity: usize, meta_bits: usize) -> Self {
+    pub fn new(
+        capacity: usize,
+        meta_bits: usize,
+        psl_source: PslSource,
+        lookup: Lookup,
+        page_slots: usize,
+        options: MapOptions,
+    ) -> Self {
+        let homes = match psl_source {
+            PslSource::CachedHome => bucket_array(capacity, 0, options),
+            PslSource::Rehash | PslSource::Metadata | PslSource::Split(_) => {
+                bucket_array(0, 0, options)
+            }
+        };
+        let meta = match psl_source {
+            PslSource::Split(psl_bits) => MetaMap::split(capacity, meta_bits, psl_bits),
+            _ => MetaMap::new(capacity, meta_bits),
+        };
+
         RobinHood {
-            hasher: RandomState::new(),
-            buckets: vec![None; capacity],
-            meta: MetaMap::new(capacity, meta_bits),
+            hasher: options.hasher(0),
+            buckets: bucket_array(capacity, None, options),
+            meta,
             len: 0,
+            psl_source,
+            lookup,
+            homes,
+            psl_sum: 0,
+            max_psl: 0,
+            page_slots,
         }
     }
 
-    fn bucket_for(&self, key: u64) -> usize {
-        (self.hasher.hash_one(key) % (self.buckets.len() as u64)) as usize
+    fn pages(&self) -> Pages {
+        Pages {
+            page_slots: self.page_slots,
+            touched: Vec::new(),
+        }
     }
 
-    fn psl_of(&self, key: u64, bucket: usize) -> usize {
-        let home = self.bucket_for(key);
-        1 + if bucket < home {
-            (bucket + self.buckets.len()) - home
-        } else {
-            bucket - home
-        }
+    fn bucket_for(&self, key: u64) -> usize {
+        self.hash_and_bucket(key).1
     }
 
-    fn set_bucket(&mut self, bucket: usize, key: u64, psl: usize) {
-        self.buckets[bucket] = Some(key);
-        self.meta.set_full(bucket, Metadata::Psl(psl));
+    fn hash_and_bucket(&self, key: u64) -> (u64, usize) {
+        let hash = self.hasher.hash_one(key);
+        (hash, (hash % (self.buckets.len() as u64)) as usize)
     }
 
-    fn clear_bucket(&mut self, bucket: usize) {
-        self.buckets[bucket] = None;
-        self.meta.set_empty(bucket);
+    // true means the bucket definitely doesn't hold the key. only split metamaps keep a
+    // fingerprint. a bucket which matches is read right after.
+    fn hint_not_match(&self, bucket: usize, key: u64, hash: u64) -> bool {
+        if !matches!(self.psl_source, PslSource::Split(_)) {
+            return false;
+        }
+        if self.meta.hint_not_match(bucket, hash) {
+            return true;
+        }
+
+        self.meta.record_read(self.buckets[bucket] == Some(key));
+        false
     }
-}
 
-impl Map for RobinHood {
-    fn len(&self) -> usize {
-        self.len
+    // the home bucket of the key stored in `bucket`.
+    fn home_of(&self, key: u64, bucket: usize) -> usize {
+        match self.psl_source {
+            PslSource::Rehash => self.bucket_for(key),
+            PslSource::Metadata | PslSource::Split(_) => match self.meta.hint_psl(bucket) {
+                Some(PslHint::Exact(psl)) => {
+                    (bucket + self.buckets.len() - (psl - 1)) % self.buckets.len()
+                }
+                _ => self.bucket_for(key),
+            },
+            PslSource::CachedHome => self.homes[bucket],
+        }
     }
 
-    fn capacity(&self) -> usize {
-        self.buckets.len()
+    fn psl_of(&self, key: u64, bucket: usize) -> usize {
+        let home = self.home_of(key, bucket);
+        1 + if bucket < home {
+            (bucket + self.buckets.len()) - home
+        } else {
+            bucket - home
+        }
     }
 
-    fn probe(&self, key: u64) -> Probe {
+    // returns the bucket holding the key and its PSL, along with the number of probes
+    // made.
+    fn search(&self, key: u64, pages: &mut Pages) -> (Option<(usize, usize)>, usize) {
+        if let Lookup::Smart = self.lookup {
+            return self.smart_search(key, pages);
+        }
+
         let mut psl = 1;
         let mut probes = 0;
 
-        let mut bucket = self.bucket_for(key);
+        let (hash, mut bucket) = self.hash_and_bucket(key);
+        explain!("home bucket {bucket}");
         loop {
             match self.meta.hint_psl(bucket) {
                 None if self.meta.hint_empty(bucket) => {
-                    return Probe {
-                        contained: false,
-                        probes,
-                    }
+                    check_hint(|| self.buckets[bucket].is_none(), bucket);
+                    explain!("bucket {bucket}: metadata says empty, the key is absent");
+                    return (None, probes);
                 }
                 None => {}
                 Some(PslHint::Exact(bucket_psl)) => {
                     if bucket_psl < psl {
-                        return Probe {
-                            contained: false,
-                            probes,
-                        };
+                        check_hint(|| self.buckets[bucket] != Some(key), bucket);
+                        explain!(
+                            "bucket {bucket}: metadata psl {bucket_psl} < our psl {psl}, the key is absent"
+                        );
+                        return (None, probes);
                     } else if bucket_psl > psl {
+                        check_hint(|| self.buckets[bucket] != Some(key), bucket);
+                        explain!(
+                            "bucket {bucket}: metadata psl {bucket_psl} != our psl {psl}, skip"
+                        );
                         psl += 1;
                         bucket = (bucket + 1) % self.buckets.len();
                         continue;
-                    }
-                }
-                Some(PslHint::AtLeast(bucket_psl)) => {
-                    if bucket_psl > psl {
+                    } else if self.hint_not_match(bucket, key, hash) {
+                        check_hint(|| self.buckets[bucket] != Some(key), bucket);
+                        explain!("bucket {bucket}: metadata psl {psl}, fingerprint differs, skip");
                         psl += 1;
                         bucket = (bucket + 1) % self.buckets.len();
                         continue;
                     }
                 }
+                // a resident at least as poor as us which isn't the key can't end the
+                // search, so there's no need to read it.
+                Some(PslHint::AtLeast(bucket_psl))
+                    if bucket_psl > psl
+                        || (bucket_psl == psl && self.hint_not_match(bucket, key, hash)) =>
+                {
+                    check_hint(|| self.buckets[bucket] != Some(key), bucket);
+                    explain!("bucket {bucket}: metadata psl >= {bucket_psl}, not the key, skip");
+                    psl += 1;
+                    bucket = (bucket + 1) % self.buckets.len();
+                    continue;
+                }
+                Some(PslHint::AtLeast(_)) => {}
             }
 
-            probes += 1;
+            probes += pages.read(bucket);
             match self.buckets[bucket] {
                 None => {
-                    return Probe {
-                        contained: false,
-                        probes,
-                    }
+                    explain!("bucket {bucket}: read, empty, the key is absent");
+                    return (None, probes);
                 }
                 Some(k) if k == key => {
-                    return Probe {
-                        contained: true,
-                        probes,
-                    }
+                    explain!("bucket {bucket}: read, holds the key");
+                    return (Some((bucket, psl)), probes);
                 }
                 Some(k) => {
-                    if self.psl_of(k, bucket) < psl {
-                        return Probe {
-                            contained: false,
-                            probes,
-                        };
+                    let k_psl = self.psl_of(k, bucket);
+                    explain!("bucket {bucket}: read, holds {k} with psl {k_psl}");
+                    if k_psl < psl {
+                        explain!("{k} is richer than our psl {psl}, the key is absent");
+                        return (None, probes);
                     }
                 }
             }
@@ -119,54 +246,239 @@ impl Map for RobinHood {
         }
     }
 
+    // search the PSLs the key could have, `lo..=hi`, starting from the mean PSL and
+    // moving outwards. a bucket which is empty, or holds a richer key, bounds the key's
+    // PSL from above, and one holding a poorer key bounds it from below.
+    fn smart_search(&self, key: u64, pages: &mut Pages) -> (Option<(usize, usize)>, usize) {
+        let home = self.bucket_for(key);
+        let mut probes = 0;
+        let (mut lo, mut hi) = (1, self.max_psl);
+        if self.len == 0 {
+            explain!("the map is empty, the key is absent");
+            return (None, probes);
+        }
+
+        let mean = ((self.psl_sum + self.len / 2) / self.len).clamp(lo, hi);
+        explain!("home bucket {home}, start at the mean psl {mean}, psl at most {hi}");
+
+        for step in 0.. {
+            if lo > hi || (mean + step > hi && mean < lo + step) {
+                break;
+            }
+
+            let below = (step > 0 && mean > step).then(|| mean - step);
+            for psl in std::iter::once(mean + step).chain(below) {
+                if psl < lo || psl > hi {
+                    continue;
+                }
+
+                let bucket = (home + psl - 1) % self.buckets.len();
+                let bucket_psl = match self.meta.hint_psl(bucket) {
+                    None if self.meta.hint_empty(bucket) => {
+                        check_hint(|| self.buckets[bucket].is_none(), bucket);
+                        explain!("bucket {bucket}: metadata says empty, psl < {psl}");
+                        hi = psl - 1;
+                        continue;
+                    }
+                    Some(PslHint::Exact(bucket_psl)) if bucket_psl != psl => bucket_psl,
+                    Some(PslHint::AtLeast(bucket_psl)) if bucket_psl > psl => bucket_psl,
+                    _ => {
+                        probes += pages.read(bucket);
+                        match self.buckets[bucket] {
+                            None => {
+                                explain!("bucket {bucket}: read, empty, psl < {psl}");
+                                hi = psl - 1;
+                                continue;
+                            }
+                            Some(k) if k == key => {
+                                explain!("bucket {bucket}: read, holds the key");
+                                return (Some((bucket, psl)), probes);
+                            }
+                            Some(k) => {
+                                let k_psl = self.psl_of(k, bucket);
+                                explain!("bucket {bucket}: read, holds {k} with psl {k_psl}");
+                                k_psl
+                            }
+                        }
+                    }
+                };
+
+                if bucket_psl < psl {
+                    check_hint(|| self.buckets[bucket] != Some(key), bucket);
+                    explain!("bucket {bucket}: holds a richer key, psl < {psl}");
+                    hi = psl - 1;
+                } else if bucket_psl > psl {
+                    check_hint(|| self.buckets[bucket] != Some(key), bucket);
+                    explain!("bucket {bucket}: holds a poorer key, psl > {psl}");
+                    lo = psl + 1;
+                }
+            }
+        }
+
+        explain!("no psl is left, the key is absent");
+        (None, probes)
+    }
+
+    // adds to the PSL sum. callers overwriting or clearing a key subtract its PSL.
+    fn set_bucket(&mut self, bucket: usize, key: u64, psl: usize) {
+        self.psl_sum += psl;
+        self.max_psl = std::cmp::max(self.max_psl, psl);
+        self.buckets[bucket] = Some(key);
+        let metadata = match self.psl_source {
+            // the fingerprint needs the key's hash, which is recomputed.
+            PslSource::Split(_) => Metadata::PslAndHash {
+                psl,
+                hash: self.hasher.hash_one(key),
+            },
+            _ => Metadata::Psl(psl),
+        };
+        self.meta.set_full(bucket, metadata);
+        if let PslSource::CachedHome = self.psl_source {
+            self.homes[bucket] = (bucket + self.buckets.len() - (psl - 1)) % self.buckets.len();
+        }
+    }
+
+    fn clear_bucket(&mut self, bucket: usize) {
+        self.buckets[bucket] = None;
+        self.meta.set_empty(bucket);
+    }
+}
+
+impl Map for RobinHood {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.bucket_for(key)
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: std::mem::size_of_val(&self.homes[..]),
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        self.meta.hint_stats()
+    }
+
+    // robin hood reorders keys but not their total displacement, so hits cost the same
+    // as under linear probing: (1 + 1/(1-a)) / 2. misses stop early on PSLs, and
+    // metadata holds PSLs rather than fingerprints, so only plain hits are predicted.
+    fn expected_probes(&self, load_factor: f64) -> ExpectedProbes {
+        let plain = self.meta.bits() == 0
+            && matches!(self.psl_source, PslSource::Rehash)
+            && matches!(self.lookup, Lookup::Linear);
+        ExpectedProbes {
+            present: plain.then(|| (1.0 + 1.0 / (1.0 - load_factor)) / 2.0),
+            absent: None,
+        }
+    }
+
+    fn occupied(&self) -> Option<Vec<bool>> {
+        Some(self.buckets.iter().map(Option::is_some).collect())
+    }
+
+    fn displacements(&self) -> Option<Vec<usize>> {
+        Some(
+            (0..self.buckets.len())
+                .filter_map(|bucket| Some(self.psl_of(self.buckets[bucket]?, bucket) - 1))
+                .collect(),
+        )
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, probes) = self.search(key, &mut self.pages());
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
     fn insert(&mut self, key: u64) -> Update {
         let mut update = Update {
             total_probes: 0,
             total_writes: 1,
             completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
         };
 
-        let mut home_bucket = self.bucket_for(key);
+        let mut pages = self.pages();
+        let (hash, mut home_bucket) = self.hash_and_bucket(key);
         let mut active_key = key;
         let mut psl = 1;
         self.len += 1;
+        explain!("home bucket {home_bucket}");
 
         loop {
             let bucket = (home_bucket + psl - 1) % self.buckets.len();
 
             let skip = match self.meta.hint_psl(bucket) {
                 None if self.meta.hint_empty(bucket) => {
+                    check_hint(|| self.buckets[bucket].is_none(), bucket);
+                    explain!(
+                        "bucket {bucket}: metadata says empty, place {active_key} with psl {psl}"
+                    );
                     self.set_bucket(bucket, active_key, psl);
                     return update;
                 }
                 None => false,
-                Some(PslHint::Exact(bucket_psl)) => bucket_psl >= psl,
-                Some(PslHint::AtLeast(bucket_psl)) => bucket_psl >= psl,
+                // a resident with our PSL might be the key itself, unless we're
+                // already placing a displaced key or its fingerprint differs.
+                Some(PslHint::Exact(bucket_psl)) => {
+                    bucket_psl > psl
+                        || (bucket_psl == psl
+                            && (active_key != key || self.hint_not_match(bucket, key, hash)))
+                }
+                Some(PslHint::AtLeast(bucket_psl)) => bucket_psl > psl,
             };
 
             if skip {
+                check_hint(|| self.buckets[bucket] != Some(active_key), bucket);
+                explain!("bucket {bucket}: metadata psl is at least our psl {psl}, skip");
                 psl += 1;
                 continue;
             }
 
-            update.total_probes += 1;
+            update.total_probes += pages.read(bucket);
             if self.buckets[bucket].is_none() {
+                explain!("bucket {bucket}: read, empty, place {active_key} with psl {psl}");
                 self.set_bucket(bucket, active_key, psl);
                 return update;
             }
 
             let contained_key = self.buckets[bucket].unwrap();
             if contained_key == active_key {
+                explain!("bucket {bucket}: read, already holds the key");
                 if active_key == key {
                     self.len -= 1;
                 }
                 return update;
             }
 
-            let contained_home = self.bucket_for(contained_key);
+            let contained_home = self.home_of(contained_key, bucket);
             let contained_psl = self.psl_of(contained_key, bucket);
+            explain!("bucket {bucket}: read, holds {contained_key} with psl {contained_psl}");
 
             if contained_psl < psl {
+                explain!(
+                    "{contained_key} is richer than {active_key} (psl {psl}), displace it and carry it on"
+                );
+                self.psl_sum -= contained_psl;
                 self.set_bucket(bucket, active_key, psl);
 
                 home_bucket = contained_home;
@@ -179,21 +491,42 @@ impl Map for RobinHood {
         }
     }
 
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let meta = self.meta.describe(bucket);
+                match self.buckets[bucket] {
+                    Some(key) => format!(
+                        "{bucket} {key} psl={} meta={meta}",
+                        self.psl_of(key, bucket)
+                    ),
+                    None => format!("{bucket} - meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
     fn remove(&mut self, key: u64) -> Update {
-        let probe = self.probe(key);
+        let mut pages = self.pages();
+        let (found, total_probes) = self.search(key, &mut pages);
         let mut update = Update {
-            total_probes: probe.probes,
+            total_probes,
             total_writes: 0,
             completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
         };
 
-        if !probe.contained {
+        let Some((mut bucket, psl)) = found else {
             return update;
-        }
+        };
 
         self.len -= 1;
+        self.psl_sum -= psl;
 
-        let mut bucket = (self.bucket_for(key) + probe.probes - 1) % self.buckets.len();
+        explain!("clear bucket {bucket}, then shift back the keys after it");
         self.clear_bucket(bucket);
         update.total_writes += 1;
 
@@ -201,19 +534,30 @@ impl Map for RobinHood {
             let next_bucket = (bucket + 1) % self.buckets.len();
 
             if let Some(PslHint::Exact(1)) = self.meta.hint_psl(next_bucket) {
+                check_hint(
+                    || self.buckets[next_bucket].is_some_and(|k| self.psl_of(k, next_bucket) == 1),
+                    next_bucket,
+                );
+                explain!("bucket {next_bucket}: metadata says its key is home, stop");
                 return update;
             }
 
-            update.total_probes += 1;
+            update.total_probes += pages.read(next_bucket);
             let (shift_key, shift_psl) = match self.buckets[next_bucket] {
-                None => return update,
+                None => {
+                    explain!("bucket {next_bucket}: read, empty, stop");
+                    return update;
+                }
                 Some(k) => {
                     let shift_psl = self.psl_of(k, next_bucket);
                     if shift_psl == 1 {
+                        explain!("bucket {next_bucket}: read, {k} is home, stop");
                         return update;
                     }
 
+                    explain!("bucket {next_bucket}: read, shift {k} back to bucket {bucket}");
                     self.clear_bucket(next_bucket);
+                    self.psl_sum -= shift_psl;
                     (k, shift_psl - 1)
                 }
             };
@@ -0,0 +1,133 @@
+use crate::{percentiles, stats, MapSpec, Record};
+use csv::Writer;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write as _;
+use std::sync::{Mutex, OnceLock};
+
+// where every record a test takes goes.
+pub trait OutputSink {
+    fn write(&mut self, record: &Record, map_spec: MapSpec);
+}
+
+// a row of the output's CSV.
+impl OutputSink for Writer<File> {
+    fn write(&mut self, record: &Record, map_spec: MapSpec) {
+        record.write(self, map_spec);
+    }
+}
+
+pub const JSON_LINES_PATH: &str = "out/results.jsonl";
+
+// the file every JSON lines sink appends to. opened once, before any task runs.
+static JSON_LINES: OnceLock<Mutex<File>> = OnceLock::new();
+
+pub fn open_json_lines() {
+    let file = File::create(JSON_LINES_PATH).unwrap();
+    JSON_LINES.set(Mutex::new(file)).unwrap();
+}
+
+// a line of `out/results.jsonl` per record, written as soon as it's taken, so that long
+// runs can be followed and read before they end.
+pub struct JsonLines {
+    pub output: &'static str,
+}
+
+impl OutputSink for JsonLines {
+    fn write(&mut self, record: &Record, map_spec: MapSpec) {
+        let line = json_line(self.output, record, map_spec);
+        // a single write per line, so that lines of concurrent tasks don't interleave.
+        let mut file = JSON_LINES.get().unwrap().lock().unwrap();
+        file.write_all(line.as_bytes()).unwrap();
+    }
+}
+
+// non-finite values, such as scalars a map doesn't report, are null.
+fn number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{value}")
+    } else {
+        "null".to_string()
+    }
+}
+
+fn array(values: impl IntoIterator<Item = f64>) -> String {
+    let values: Vec<String> = values.into_iter().map(number).collect();
+    format!("[{}]", values.join(","))
+}
+
+// a record as a single line of JSON, naming every value. series and scalars are in the
+// order of the CSV's columns. the bootstrap intervals of the CSV are left out.
+fn json_line(output: &str, record: &Record, map_spec: MapSpec) -> String {
+    let mut line = format!(
+        "{{\"output\":\"{output}\",\"map\":\"{}\",\"size\":{},\"meta_bits\":{},\"load_factor\":{}",
+        map_spec.name(),
+        map_spec.size(),
+        map_spec.meta_bits(),
+        number(record.load_factor),
+    );
+
+    let series: Vec<String> = record
+        .series
+        .iter()
+        .map(|series| {
+            let h = &series.histogram;
+            let percentiles: Vec<String> = percentiles()
+                .iter()
+                .map(|&p| format!("\"{p}\":{}", h.value_at_percentile(p)))
+                .collect();
+            let trials = stats::trial_interval(&series.trial_means)
+                .map_or("null".to_string(), |(mean, lo, hi)| array([mean, lo, hi]));
+            format!(
+                "{{\"count\":{},\"mean\":{},\"percentiles\":{{{}}},\"max\":{},\"stdev\":{},\"trials\":{trials}}}",
+                h.len(),
+                number(h.mean()),
+                percentiles.join(","),
+                h.max(),
+                number(h.stdev()),
+            )
+        })
+        .collect();
+    write!(line, ",\"series\":[{}]", series.join(",")).unwrap();
+
+    let [buckets, metadata, auxiliary, total] = record.bytes_per_key.map(number);
+    write!(
+        line,
+        ",\"scalars\":{},\"bytes_per_key\":{{\"buckets\":{buckets},\"metadata\":{metadata},\"auxiliary\":{auxiliary},\"total\":{total}}}}}",
+        array(record.scalars.iter().copied()),
+    )
+    .unwrap();
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PslSource, Series};
+    use hdrhistogram::Histogram;
+
+    #[test]
+    fn records_are_single_lines_naming_every_value() {
+        let mut series = Series::new(Histogram::new(3).unwrap());
+        series.record(2, 1);
+        series.record(4, 1);
+        let record = Record {
+            load_factor: 0.5,
+            series: vec![series],
+            scalars: vec![1.5, f64::NAN],
+            bytes_per_key: [8.0, 1.0, 0.0, 9.0],
+        };
+        let map_spec = MapSpec::RobinHood(8, PslSource::Rehash, crate::Lookup::Linear);
+
+        let line = json_line("probe", &record, map_spec);
+        assert_eq!(line.matches('\n').count(), 1);
+        assert!(line.starts_with(
+            "{\"output\":\"probe\",\"map\":\"robinhood\",\"size\":1048576,\"meta_bits\":8,\"load_factor\":0.5,"
+        ));
+        assert!(line.contains("\"count\":2,\"mean\":3,"));
+        assert!(line.contains("\"trials\":null}"));
+        assert!(line.contains("\"scalars\":[1.5,null]"));
+        assert!(line.ends_with("\"total\":9}}\n"));
+    }
+}
@@ -0,0 +1,199 @@
+use rand::Rng;
+
+pub fn mean(values: &[u64]) -> f64 {
+    values.iter().sum::<u64>() as f64 / values.len() as f64
+}
+
+// the nearest-rank percentile. sorts the values.
+pub fn percentile(values: &mut [u64], percentile: f64) -> f64 {
+    values.sort_unstable();
+    let rank = (percentile / 100.0 * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1] as f64
+}
+
+// percentile bootstrap interval of `statistic` over the samples, at the given
+// confidence level. None if there are no samples.
+pub fn bootstrap(
+    samples: &[u64],
+    resamples: usize,
+    confidence: f64,
+    statistic: impl Fn(&mut [u64]) -> f64,
+    rng: &mut impl Rng,
+) -> Option<(f64, f64)> {
+    if samples.is_empty() || resamples == 0 {
+        return None;
+    }
+
+    let mut resample = vec![0; samples.len()];
+    let mut estimates: Vec<f64> = (0..resamples)
+        .map(|_| {
+            for value in resample.iter_mut() {
+                *value = samples[rng.gen_range(0..samples.len())];
+            }
+            statistic(&mut resample)
+        })
+        .collect();
+    estimates.sort_unstable_by(f64::total_cmp);
+
+    let tail = (1.0 - confidence) / 2.0;
+    let index = |q: f64| ((q * resamples as f64) as usize).min(resamples - 1);
+    Some((estimates[index(tail)], estimates[index(1.0 - tail)]))
+}
+
+// student's t at 97.5% for 1 to 30 degrees of freedom. past them the normal
+// distribution's 1.96 is close enough.
+const T_975: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+// the mean of the results of independent trials, and its 95% t interval. None for
+// fewer than two trials.
+//
+// return a tuple containing:
+//  + the mean
+//  + the low end of the interval
+//  + the high end of the interval
+pub fn trial_interval(results: &[f64]) -> Option<(f64, f64, f64)> {
+    let n = results.len();
+    if n < 2 {
+        return None;
+    }
+
+    let mean = results.iter().sum::<f64>() / n as f64;
+    let variance = results.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+    let t = T_975.get(n - 2).copied().unwrap_or(1.96);
+    let half_width = t * (variance / n as f64).sqrt();
+    Some((mean, mean - half_width, mean + half_width))
+}
+
+// two-sided Mann-Whitney U test of whether samples `a` and `b` come from the
+// same distribution, using the normal approximation with a tie correction.
+//
+// return a tuple containing:
+//  + U statistic of `a`
+//  + p-value
+pub fn mann_whitney(a: &[u64], b: &[u64]) -> (f64, f64) {
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let n = n_a + n_b;
+    if a.is_empty() || b.is_empty() {
+        return (0.0, 1.0);
+    }
+
+    // (value, from a)
+    let mut all: Vec<(u64, bool)> = a
+        .iter()
+        .map(|&v| (v, true))
+        .chain(b.iter().map(|&v| (v, false)))
+        .collect();
+    all.sort_unstable();
+
+    // tied values all take the average of the ranks they span.
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0;
+    let mut start = 0;
+    while start < all.len() {
+        let end = start
+            + all[start..]
+                .iter()
+                .take_while(|x| x.0 == all[start].0)
+                .count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        let in_a = all[start..end].iter().filter(|x| x.1).count();
+        rank_sum_a += rank * in_a as f64;
+
+        let t = (end - start) as f64;
+        ties += t * t * t - t;
+        start = end;
+    }
+
+    let u = rank_sum_a - n_a * (n_a + 1.0) / 2.0;
+    let mean = n_a * n_b / 2.0;
+    let variance = n_a * n_b / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return (u, 1.0);
+    }
+
+    // with a continuity correction.
+    let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    (u, erfc(z / std::f64::consts::SQRT_2))
+}
+
+// complementary error function, accurate to ~1.2e-7 (Numerical Recipes' erfcc).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    #[test]
+    fn same_samples_are_not_significant() {
+        let a: Vec<u64> = (0..100).map(|i| i % 7).collect();
+        let (u, p) = mann_whitney(&a, &a);
+        assert_eq!(u, 100.0 * 100.0 / 2.0);
+        assert!(p > 0.99);
+    }
+
+    #[test]
+    fn shifted_samples_are_significant() {
+        let a: Vec<u64> = (0..100).map(|i| i % 10).collect();
+        let b: Vec<u64> = (0..100).map(|i| i % 10 + 3).collect();
+        assert!(mann_whitney(&a, &b).1 < 1e-6);
+    }
+
+    #[test]
+    fn bootstrap_interval_covers_the_statistic() {
+        let samples: Vec<u64> = (0..1000).map(|i| i % 10).collect();
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        let (lo, hi) = bootstrap(&samples, 500, 0.95, |s| mean(s), &mut rng).unwrap();
+        assert!(lo <= 4.5 && 4.5 <= hi);
+        assert!(hi - lo < 1.0);
+
+        let (lo, hi) = bootstrap(&samples, 500, 0.95, |s| percentile(s, 99.0), &mut rng).unwrap();
+        assert!(lo <= 9.0 && 9.0 <= hi);
+        assert!(bootstrap(&[], 500, 0.95, |s| mean(s), &mut rng).is_none());
+    }
+
+    #[test]
+    fn trial_interval_widens_with_fewer_trials() {
+        assert_eq!(trial_interval(&[3.0]), None);
+        assert_eq!(trial_interval(&[2.0, 2.0, 2.0]), Some((2.0, 2.0, 2.0)));
+
+        // a standard deviation of 1: 12.706 / sqrt(2) either side with two trials.
+        let (mean, lo, hi) = trial_interval(&[1.0, 1.0 + 2f64.sqrt()]).unwrap();
+        assert!((hi - mean - 12.706 / 2f64.sqrt()).abs() < 1e-9);
+        assert!((mean - lo - (hi - mean)).abs() < 1e-9);
+
+        let results: Vec<f64> = (0..100).map(|i| (i % 2) as f64).collect();
+        let (mean, lo, hi) = trial_interval(&results).unwrap();
+        assert_eq!(mean, 0.5);
+        assert!(hi - lo < 0.25);
+    }
+
+    #[test]
+    fn erfc_known_values() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-6);
+        assert!((erfc(1.0) - 0.157_299_2).abs() < 1e-6);
+        assert!((erfc(-1.0) - 1.842_700_8).abs() < 1e-6);
+    }
+}
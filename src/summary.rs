@@ -0,0 +1,114 @@
+use crate::{series_columns, MapSpec};
+use csv::{ReaderBuilder, StringRecord};
+use std::collections::BTreeMap;
+
+// the load factor probes and churn writes are compared at.
+const SUMMARY_LOAD: f64 = 0.9;
+
+// the rows of `out/{output}_{name}.csv`, or none if the test didn't run.
+fn read_rows(output: &str, name: &str) -> Vec<StringRecord> {
+    let Ok(mut reader) = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(format!("out/{output}_{name}.csv"))
+    else {
+        return Vec::new();
+    };
+
+    reader.records().map(Result::unwrap).collect()
+}
+
+// the rows written for `meta_bits` at `size`, with their load factors.
+fn spec_rows(
+    rows: &[StringRecord],
+    meta_bits: usize,
+    size: usize,
+) -> impl Iterator<Item = (f64, &StringRecord)> {
+    rows.iter()
+        .filter(move |row| row[1].parse() == Ok(size) && row[2].parse() == Ok(meta_bits))
+        .map(|row| (row[0].parse().unwrap(), row))
+}
+
+// the mean of `column` over the rows at SUMMARY_LOAD, e.g. over the windows of a churn run.
+fn mean_at_load(
+    rows: &[StringRecord],
+    meta_bits: usize,
+    size: usize,
+    column: usize,
+) -> Option<f64> {
+    let values: Vec<f64> = spec_rows(rows, meta_bits, size)
+        .filter(|(load_factor, _)| (load_factor - SUMMARY_LOAD).abs() < 0.005)
+        .filter_map(|(_, row)| row.get(column)?.parse().ok())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    Some(values.iter().sum::<f64>() / values.len() as f64)
+}
+
+fn cell(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |value| format!("{value:.2}"))
+}
+
+// a table comparing every map simulated, read back from the results in `out/`: the
+// highest load the grow test reached, the mean probes of present and absent lookups
+// at SUMMARY_LOAD, and the mean writes of a churn update there. cells of tests which
+// didn't run, or didn't reach the load, are left as `-`. one row per map and size.
+pub fn print(map_specs: &[MapSpec], sizes: &[usize]) {
+    // name -> (grow, probe, churn) rows.
+    let mut results = BTreeMap::new();
+
+    println!(
+        "{:<20} {:>4} {:>4} {:>8} {:>10} {:>11} {:>12}",
+        "map", "size", "bits", "max load", "hit probes", "miss probes", "churn writes"
+    );
+    for &size in sizes {
+        for map_spec in map_specs {
+            let (name, meta_bits) = (map_spec.name(), map_spec.meta_bits());
+            let (grow, probe, churn) = results.entry(name).or_insert_with(|| {
+                (
+                    read_rows("grow", name),
+                    read_rows("probe", name),
+                    read_rows("churn", name),
+                )
+            });
+
+            let max_load = spec_rows(grow, meta_bits, size)
+                .map(|(load_factor, _)| load_factor)
+                .reduce(f64::max);
+            println!(
+                "{:<20} {:>4} {:>4} {:>8} {:>10} {:>11} {:>12}",
+                name,
+                size.ilog2(),
+                meta_bits,
+                cell(max_load),
+                cell(mean_at_load(probe, meta_bits, size, 3)),
+                cell(mean_at_load(probe, meta_bits, size, 3 + series_columns())),
+                cell(mean_at_load(churn, meta_bits, size, 3 + series_columns())),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn means_are_taken_at_the_summary_load() {
+        let rows = [
+            StringRecord::from(vec!["0.88", "1024", "0", "1.00"]),
+            StringRecord::from(vec!["0.90", "1024", "0", "2.00"]),
+            StringRecord::from(vec!["0.90", "1024", "0", "4.00"]),
+            StringRecord::from(vec!["0.90", "1024", "8", "9.00"]),
+            StringRecord::from(vec!["0.90", "4096", "0", "7.00"]),
+        ];
+
+        assert_eq!(mean_at_load(&rows, 0, 1024, 3), Some(3.0));
+        assert_eq!(mean_at_load(&rows, 8, 1024, 3), Some(9.0));
+        assert_eq!(mean_at_load(&rows, 0, 4096, 3), Some(7.0));
+        assert_eq!(mean_at_load(&rows, 4, 1024, 3), None);
+        assert_eq!(mean_at_load(&rows, 0, 1024, 4), None);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::hasher::{CountingHasher, HashStats};
+use crate::meta_map::{check_hint, HintStats, MetaMap, Metadata, GROUP_SLOTS};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+#[derive(Clone, Debug)]
+enum BucketItem {
+    Value(u64),
+    Empty,
+    Tombstone,
+}
+
+// dummy hash-set for u64 keys.
+//
+// implements swiss-table style group probing: buckets are probed in aligned groups of
+// GROUP_SLOTS, and the groups in triangular order. each group's metadata is scanned
+// at once, and only the slots it matches are read. reading any slots of a group
+// counts as a single probe, while a group ruled out by its metadata alone costs none.
+pub struct SwissTable {
+    hasher: CountingHasher,
+    buckets: BucketArray<BucketItem>,
+    meta: MetaMap,
+    len: usize,
+}
+
+// the outcome of walking a key's probe sequence.
+struct Search {
+    found: Option<usize>,
+    // the first empty or tombstone slot passed, where the key would be inserted.
+    free: Option<usize>,
+    probes: usize,
+}
+
+impl SwissTable {
+    pub fn new(capacity: usize, meta_bits: usize, options: MapOptions) -> Self {
+        let capacity = std::cmp::max(1, capacity / GROUP_SLOTS) * GROUP_SLOTS;
+        SwissTable {
+            hasher: options.hasher(0),
+            buckets: bucket_array(capacity, BucketItem::Empty, options),
+            meta: MetaMap::new(capacity, meta_bits),
+            len: 0,
+        }
+    }
+
+    fn groups(&self) -> usize {
+        self.buckets.len() / GROUP_SLOTS
+    }
+
+    fn home_group(&self, hash: u64) -> usize {
+        (hash % self.groups() as u64) as usize
+    }
+
+    // walk the groups until the key is found or a group with an empty slot is reached,
+    // which an insertion of the key would have used.
+    fn search(&self, key: u64) -> Search {
+        let hash = self.hasher.hash_one(key);
+        let home = self.home_group(hash);
+        explain!("home group {home}");
+        let mut search = Search {
+            found: None,
+            free: None,
+            probes: 0,
+        };
+
+        let mut offset = 0;
+        for i in 0..self.groups() {
+            offset += i;
+            let group = (home + offset) % self.groups();
+            let first = group * GROUP_SLOTS;
+
+            let matches = self.meta.hint_match_group(group, hash);
+            explain!("group {group}: metadata matches slots {matches:016b}");
+            if matches != 0 {
+                search.probes += 1;
+            }
+
+            for slot in 0..GROUP_SLOTS {
+                let bucket = first + slot;
+                if matches & (1 << slot) == 0 {
+                    check_hint(|| self.doesnt_hold(bucket, key), bucket);
+                    continue;
+                }
+
+                self.meta.record_read(!self.doesnt_hold(bucket, key));
+                if let BucketItem::Value(found_key) = self.buckets[bucket] {
+                    if found_key == key {
+                        explain!("bucket {bucket}: read, holds the key");
+                        search.found = Some(bucket);
+                        return search;
+                    }
+                }
+            }
+
+            // metadata tells empty and tombstone slots apart from two bits up. below
+            // that, the slots it can't tell apart were read above.
+            search.free = search.free.or_else(|| {
+                (first..first + GROUP_SLOTS)
+                    .find(|&bucket| !matches!(self.buckets[bucket], BucketItem::Value(_)))
+            });
+
+            if self.group_has_empty(group) {
+                explain!("group {group}: has an empty slot, the key is absent");
+                return search;
+            }
+        }
+
+        search
+    }
+
+    // with metadata, empty slots are always marked as such. without it, the whole group
+    // has been read.
+    fn group_has_empty(&self, group: usize) -> bool {
+        let first = group * GROUP_SLOTS;
+        let has_empty = (first..first + GROUP_SLOTS)
+            .any(|bucket| matches!(self.buckets[bucket], BucketItem::Empty));
+
+        if self.meta.bits() == 0 {
+            return has_empty;
+        }
+
+        let empties = self.meta.hint_empty_group(group);
+        check_hint(|| (empties != 0) == has_empty, first);
+        empties != 0
+    }
+
+    fn doesnt_hold(&self, bucket: usize, key: u64) -> bool {
+        !matches!(self.buckets[bucket], BucketItem::Value(found_key) if found_key == key)
+    }
+
+    fn set_bucket(&mut self, bucket: usize, item: BucketItem) {
+        match item {
+            BucketItem::Value(key) => {
+                let hash = self.hasher.hash_one(key);
+                self.meta.set_full(bucket, Metadata::Hash(hash));
+            }
+            BucketItem::Empty => self.meta.set_empty(bucket),
+            BucketItem::Tombstone => self.meta.set_tombstone(bucket),
+        }
+        self.buckets[bucket] = item;
+    }
+}
+
+impl Map for SwissTable {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        self.home_group(self.hasher.hash_one(key)) * GROUP_SLOTS
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hasher.stats()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buckets: std::mem::size_of_val(&self.buckets[..]),
+            metadata: self.meta.memory_usage(),
+            auxiliary: 0,
+        }
+    }
+
+    fn hint_stats(&self) -> HintStats {
+        self.meta.hint_stats()
+    }
+
+    fn occupied(&self) -> Option<Vec<bool>> {
+        Some(
+            self.buckets
+                .iter()
+                .map(|item| !matches!(item, BucketItem::Empty))
+                .collect(),
+        )
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let search = self.search(key);
+        Probe {
+            contained: search.found.is_some(),
+            probes: search.probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let search = self.search(key);
+        let mut update = Update {
+            total_probes: search.probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if search.found.is_some() {
+            return update;
+        }
+
+        let Some(bucket) = search.free else {
+            update.completed = false;
+            return update;
+        };
+
+        explain!("place the key in bucket {bucket}");
+        self.len += 1;
+        self.set_bucket(bucket, BucketItem::Value(key));
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        (0..self.buckets.len())
+            .map(|bucket| {
+                let meta = self.meta.describe(bucket);
+                match self.buckets[bucket] {
+                    BucketItem::Value(key) => format!("{bucket} {key} meta={meta}"),
+                    BucketItem::Empty => format!("{bucket} - meta={meta}"),
+                    BucketItem::Tombstone => format!("{bucket} tombstone meta={meta}"),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let search = self.search(key);
+        let mut update = Update {
+            total_probes: search.probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some(bucket) = search.found else {
+            return update;
+        };
+
+        // a search stops at a group with an empty slot, so a slot in such a group can
+        // be emptied outright rather than left as a tombstone.
+        self.len -= 1;
+        update.total_writes += 1;
+        if self.group_has_empty(bucket / GROUP_SLOTS) {
+            explain!("clear bucket {bucket}, its group has an empty slot");
+            self.set_bucket(bucket, BucketItem::Empty);
+        } else {
+            explain!("leave a tombstone in bucket {bucket}");
+            self.set_bucket(bucket, BucketItem::Tombstone);
+        }
+
+        update
+    }
+}
@@ -0,0 +1,98 @@
+use csv::{ReaderBuilder, Writer};
+use std::io::Read;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+    Insert(u64),
+    Remove(u64),
+    Probe(u64),
+}
+
+impl Op {
+    // the op named `name`, the inverse of `Op::name`.
+    pub fn new(name: &str, key: u64) -> Option<Op> {
+        match name {
+            "insert" => Some(Op::Insert(key)),
+            "remove" => Some(Op::Remove(key)),
+            "probe" => Some(Op::Probe(key)),
+            _ => None,
+        }
+    }
+
+    pub fn key(&self) -> u64 {
+        match *self {
+            Op::Insert(key) | Op::Remove(key) | Op::Probe(key) => key,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Insert(_) => "insert",
+            Op::Remove(_) => "remove",
+            Op::Probe(_) => "probe",
+        }
+    }
+}
+
+// traces are CSV files with one `op,key` row per operation, where op is one of
+// `insert`, `remove` or `probe`. errors name the line they're on.
+pub fn read(path: &str) -> Result<Vec<Op>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("can't read trace {path}: {e}"))?;
+    parse(file).map_err(|e| format!("{path}:{e}"))
+}
+
+fn parse(trace: impl Read) -> Result<Vec<Op>, String> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(trace);
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| {
+                let line = e.position().map_or(0, |position| position.line());
+                format!("{line}: {e}")
+            })?;
+            let line = record.position().map_or(0, |position| position.line());
+            let (Some(op), Some(key), 2) = (record.get(0), record.get(1), record.len()) else {
+                return Err(format!("{line}: expected an `op,key` row"));
+            };
+            let key = key
+                .parse()
+                .map_err(|_| format!("{line}: invalid key {key}"))?;
+            Op::new(op, key).ok_or_else(|| format!("{line}: unknown op {op}"))
+        })
+        .collect()
+}
+
+pub fn write(path: &str, ops: &[Op]) {
+    let mut writer = Writer::from_path(path).unwrap();
+    for op in ops {
+        writer
+            .write_record([op.name().to_string(), op.key().to_string()])
+            .unwrap();
+    }
+
+    writer.flush().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_rows_are_reported_by_line() {
+        let ops = parse("insert,1\nprobe,1\nremove,1\n".as_bytes()).unwrap();
+        assert_eq!(ops.len(), 3);
+
+        let error = |trace: &str| parse(trace.as_bytes()).unwrap_err();
+        assert_eq!(error("insert,1\nprobe\n"), "2: expected an `op,key` row");
+        assert_eq!(
+            error("insert,1\nprobe,1,2\n"),
+            "2: expected an `op,key` row"
+        );
+        assert_eq!(error("insert,1\nprobe,1\ninsert,x\n"), "3: invalid key x");
+        assert_eq!(error("find,1\n"), "1: unknown op find");
+    }
+}
@@ -0,0 +1,155 @@
+use crate::allocation::{bucket_array, BucketArray};
+use crate::chains::Chains;
+use crate::hasher::{CountingHasher, HashStats};
+use crate::{Map, MapOptions, MemoryUsage, Probe, Update};
+
+// dummy hash-set for u64 keys.
+//
+// implements two-choice chaining: every key hashes to two buckets, and is pushed to the
+// front of whichever chain is shorter, ties going to the first. chains live in one
+// arena, as in separate chaining, and each bucket keeps its chain's length alongside its
+// head.
+//
+// every node visited counts as a probe. reading a bucket's head and length is free, so
+// a lookup skips empty chains, and searches the shorter chain first.
+pub struct TwoChoice {
+    hashers: [CountingHasher; 2],
+    chains: Chains,
+    lengths: BucketArray<usize>,
+    len: usize,
+}
+
+impl TwoChoice {
+    pub fn new(capacity: usize, options: MapOptions) -> Self {
+        TwoChoice {
+            hashers: [options.hasher(0), options.hasher(1)],
+            chains: Chains::new(capacity, options),
+            lengths: bucket_array(capacity, 0, options),
+            len: 0,
+        }
+    }
+
+    // the key's two buckets, shorter chain first.
+    fn choices(&self, key: u64) -> [usize; 2] {
+        let [a, b] = self
+            .hashers
+            .each_ref()
+            .map(|hasher| (hasher.hash_one(key) % self.chains.buckets() as u64) as usize);
+        if self.lengths[b] < self.lengths[a] {
+            [b, a]
+        } else {
+            [a, b]
+        }
+    }
+
+    // walk both of the key's chains.
+    //
+    // return a tuple containing:
+    //  + option containing the bucket, the node holding the key, and the node before it
+    //  + number of probes
+    fn search(&self, key: u64) -> (Option<(usize, usize, Option<usize>)>, usize) {
+        let mut probes = 0;
+        let choices = self.choices(key);
+        for (i, &bucket) in choices.iter().enumerate() {
+            if i == 1 && bucket == choices[0] {
+                break;
+            }
+            explain!("bucket {bucket}, chain of {}", self.lengths[bucket]);
+            if let Some((node, prev)) = self.chains.find(bucket, key, &mut probes) {
+                return (Some((bucket, node, prev)), probes);
+            }
+        }
+
+        explain!("end of both chains, the key is absent");
+        (None, probes)
+    }
+}
+
+impl Map for TwoChoice {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.chains.buckets()
+    }
+
+    fn home_bucket(&self, key: u64) -> usize {
+        (self.hashers[0].hash_one(key) % self.chains.buckets() as u64) as usize
+    }
+
+    fn hash_stats(&self) -> HashStats {
+        self.hashers.iter().map(|h| h.stats()).sum()
+    }
+
+    // the chain lengths only steer keys, so are auxiliary.
+    fn memory_usage(&self) -> MemoryUsage {
+        let chains = self.chains.memory_usage();
+        MemoryUsage {
+            auxiliary: chains.auxiliary + std::mem::size_of_val(&self.lengths[..]),
+            ..chains
+        }
+    }
+
+    fn probe(&self, key: u64) -> Probe {
+        let (found, probes) = self.search(key);
+        Probe {
+            contained: found.is_some(),
+            probes,
+        }
+    }
+
+    fn insert(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 1,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        if found.is_some() {
+            return update;
+        }
+
+        // the node, then the shorter bucket's head.
+        let bucket = self.choices(key)[0];
+        let index = self.chains.push(bucket, key);
+        explain!("push node {index} to the front of bucket {bucket}, the shorter chain");
+        self.lengths[bucket] += 1;
+        self.len += 1;
+        update.total_writes += 1;
+        update
+    }
+
+    fn dump(&self) -> Vec<String> {
+        self.chains.dump()
+    }
+
+    fn remove(&mut self, key: u64) -> Update {
+        let (found, total_probes) = self.search(key);
+        let mut update = Update {
+            total_probes,
+            total_writes: 0,
+            completed: true,
+            evictions: 0,
+            splits: 0,
+            rebuilds: 0,
+            rebuild_moves: 0,
+        };
+
+        let Some((bucket, node, prev)) = found else {
+            return update;
+        };
+
+        explain!("unlink node {node} from bucket {bucket}");
+        self.chains.unlink(bucket, node, prev);
+        self.lengths[bucket] -= 1;
+        self.len -= 1;
+        update.total_writes += 1;
+        update
+    }
+}
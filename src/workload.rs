@@ -0,0 +1,792 @@
+use crate::config::{self, Config, HotSet, Mix, Sweep, Test, Trials};
+use crate::hasher::HashProvider;
+use crate::resize::{Rebuild, Resizing};
+use crate::{
+    batch, bulk_load, churn, clusters, displacements, drain, failure, fill, grow, hot,
+    mean_displacement, merge_trials, mixed, probe, size, tombstone_fraction, trial_options, trials,
+    Failure, KeySet, Map, MapOptions, MapSpec, Record, Series, Writers, LOAD_SCALE, LOAD_SIGFIGS,
+};
+use hdrhistogram::Histogram;
+use rand::prelude::*;
+
+// a test measured on a map: the operations it makes and the records it takes of them.
+// the driver repeats it over every trial and writes its records.
+pub trait Workload {
+    // the output the workload's records are written to, and its operations logged as.
+    fn name(&self) -> &'static str;
+
+    // the output the `index`th record is written to, for workloads writing to several.
+    fn output(&self, _index: usize) -> &'static str {
+        self.name()
+    }
+
+    fn trials(&self) -> usize {
+        trials()
+    }
+
+    // the load factors the workload is run at, each on a fresh map filled to it. none
+    // runs it once, on a single map from empty.
+    fn sweep(&self) -> Option<Sweep> {
+        None
+    }
+
+    // a trial's records, on maps built with `options`. workloads building maps of their
+    // own, rather than one at each load factor of the sweep, make their trials here.
+    fn trial(
+        &mut self,
+        map_spec: MapSpec,
+        options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let Some(sweep) = self.sweep() else {
+            let mut map = map_spec.build(options);
+            let mut key_set = KeySet::new(options.hashes.key_rng());
+            return self.run(&mut *map, &mut key_set, options, writers);
+        };
+
+        let fill = format!("{}_fill", self.name());
+        let mut records = Vec::new();
+        let mut load = sweep.start;
+        while load <= sweep.max_load {
+            let mut map = map_spec.build(options);
+            let mut key_set = KeySet::new(options.hashes.key_rng());
+            let mut log = writers.op_log(&fill);
+            if grow(&mut *map, &mut key_set, load, &mut log).is_none() {
+                break;
+            };
+
+            records.extend(self.run(&mut *map, &mut key_set, options, writers));
+            load += sweep.increment;
+        }
+        records
+    }
+
+    // the records of a map given to the workload, unused by those making their own trials.
+    fn run(
+        &mut self,
+        _map: &mut dyn Map,
+        _keys: &mut KeySet,
+        _options: MapOptions,
+        _writers: &mut Writers,
+    ) -> Vec<Record> {
+        Vec::new()
+    }
+
+    // the name the `index`th record's histograms are written under.
+    fn histograms(&self, index: usize) -> String {
+        self.output(index).to_string()
+    }
+}
+
+// the workload run for `test`. a new workload takes a test and an entry here.
+pub fn for_test(test: Test, config: &Config) -> Box<dyn Workload> {
+    match test {
+        Test::Grow => Box::new(Grow { sweep: config.grow }),
+        Test::Probe => Box::new(Probe {
+            sweep: config.probe,
+            misses: config.misses,
+        }),
+        Test::Churn => Box::new(Churn {
+            sweep: config.churn,
+        }),
+        Test::Bulk => Box::new(Bulk { sweep: config.bulk }),
+        Test::Amortized => Box::new(Amortized),
+        Test::Analyze => Box::new(Analyze {
+            sweep: config.analyze,
+            reports: [false; 2],
+        }),
+        Test::Failures => Box::new(Failures {
+            trials: config.failures,
+        }),
+        Test::Mixed => Box::new(Mixed {
+            sweep: config.mixed,
+            mix: config.mix,
+        }),
+        Test::Drain => Box::new(Drain {
+            sweep: config.drain,
+        }),
+        Test::Hot => Box::new(Hot {
+            sweep: config.hot,
+            hot_set: config.hot_set,
+        }),
+        Test::Soak => Box::new(Soak { sweep: config.soak }),
+        Test::Batch => Box::new(Batch {
+            sweep: config.batch,
+            batch_size: config.batch_size,
+        }),
+        Test::Sawtooth => Box::new(Sawtooth {
+            sawtooth: config.sawtooth,
+        }),
+    }
+}
+
+// run `workload` for every trial and write its records, merged across the trials.
+pub fn run(
+    writers: &mut Writers,
+    map_spec: MapSpec,
+    options: MapOptions,
+    workload: &mut dyn Workload,
+) {
+    let runs = (0..workload.trials())
+        .map(|trial| workload.trial(map_spec, trial_options(options, trial), writers))
+        .collect();
+
+    for (i, record) in merge_trials(runs).into_iter().enumerate() {
+        let output = workload.output(i);
+        writers.sink(output).write(&record, map_spec);
+        record.write_samples(&mut writers.samples, output, map_spec);
+        record.write_histograms(&workload.histograms(i), map_spec);
+    }
+}
+
+// the ops of each record of a windowed sweep, all of them when no window is set.
+fn window(sweep: Sweep) -> usize {
+    let window = if sweep.window == 0 {
+        sweep.ops
+    } else {
+        sweep.window
+    };
+    std::cmp::max(1, window)
+}
+
+// how far past their initial capacity resizable maps are grown.
+const DYNAMIC_GROWTH: f64 = 4.0;
+
+// a single map grown from empty, a step of `increment` at a time, recording every insert.
+// resizable maps are grown past a load of 1.
+struct Grow {
+    sweep: Sweep,
+}
+
+impl Workload for Grow {
+    fn name(&self) -> &'static str {
+        "grow"
+    }
+
+    fn run(
+        &mut self,
+        map: &mut dyn Map,
+        keys: &mut KeySet,
+        _options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let max_load = if map.resizable() {
+            self.sweep.max_load * DYNAMIC_GROWTH
+        } else {
+            self.sweep.max_load
+        };
+
+        let mut records = Vec::new();
+        while map.load_factor() + self.sweep.increment < max_load {
+            match grow(map, keys, self.sweep.increment, &mut writers.op_log("grow")) {
+                Some(record) => records.push(record),
+                None => break,
+            }
+        }
+        records
+    }
+}
+
+// lookups of present and absent keys, `misses` of them absent.
+struct Probe {
+    sweep: Sweep,
+    misses: f64,
+}
+
+impl Workload for Probe {
+    fn name(&self) -> &'static str {
+        "probe"
+    }
+
+    fn sweep(&self) -> Option<Sweep> {
+        Some(self.sweep)
+    }
+
+    fn run(
+        &mut self,
+        map: &mut dyn Map,
+        keys: &mut KeySet,
+        _options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let mut log = writers.op_log("probe");
+        vec![probe(map, keys, self.sweep.ops, self.misses, &mut log)]
+    }
+}
+
+// remove/insert pairs, a record per window of them, so that drift over the run shows.
+struct Churn {
+    sweep: Sweep,
+}
+
+impl Workload for Churn {
+    fn name(&self) -> &'static str {
+        "churn"
+    }
+
+    fn sweep(&self) -> Option<Sweep> {
+        Some(self.sweep)
+    }
+
+    fn run(
+        &mut self,
+        map: &mut dyn Map,
+        keys: &mut KeySet,
+        _options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let (ops, window) = (self.sweep.ops, window(self.sweep));
+        let mut log = writers.op_log("churn");
+        (0..ops)
+            .step_by(window)
+            .map(|start| {
+                churn(
+                    map,
+                    keys,
+                    start,
+                    std::cmp::min(window, ops - start),
+                    &mut log,
+                )
+            })
+            .collect()
+    }
+
+    // windows share a load factor, so their histograms are told apart by start.
+    fn histograms(&self, index: usize) -> String {
+        let (ops, window) = (self.sweep.ops, window(self.sweep));
+        let windows = ops.div_ceil(window);
+        if windows == 1 {
+            "churn".to_string()
+        } else {
+            format!("churn{}", index % windows * window)
+        }
+    }
+}
+
+// the options of a pair of maps which must hash alike. random hashers are pinned to a
+// seed first.
+fn pinned(options: MapOptions) -> MapOptions {
+    let hashes = match options.hashes {
+        HashProvider::Random => HashProvider::Seeded(thread_rng().gen()),
+        hashes => hashes,
+    };
+    MapOptions { hashes, ..options }
+}
+
+// a fresh pair of maps per load factor, one built in a batch and the other a key at a
+// time.
+struct Bulk {
+    sweep: Sweep,
+}
+
+impl Workload for Bulk {
+    fn name(&self) -> &'static str {
+        "bulk"
+    }
+
+    fn trial(&mut self, map_spec: MapSpec, options: MapOptions, _: &mut Writers) -> Vec<Record> {
+        let options = pinned(options);
+        let mut records = Vec::new();
+        let mut load = self.sweep.start;
+        while load <= self.sweep.max_load {
+            let (mut map, mut incremental) = (map_spec.build(options), map_spec.build(options));
+            let mut key_set = KeySet::new(options.hashes.key_rng());
+            let Some(record) = bulk_load(&mut *map, &mut *incremental, &mut key_set, load) else {
+                break;
+            };
+
+            records.push(record);
+            load += self.sweep.increment;
+        }
+        records
+    }
+}
+
+// grow a map 100x while keeping it at a target load, once per rebuild strategy. made
+// once, whatever the trials.
+//
+// one row per strategy, holding the per-insert probes and writes, so that the means are
+// the amortized costs, followed by the strategy (0: doubling, 1: incremental,
+// 2: over-provisioned) and the number of rebuilds.
+struct Amortized;
+
+impl Amortized {
+    const TARGET_LOAD: f64 = 0.8;
+    const GROWTH: usize = 100;
+}
+
+impl Workload for Amortized {
+    fn name(&self) -> &'static str {
+        "amortized"
+    }
+
+    fn trials(&self) -> usize {
+        1
+    }
+
+    fn trial(&mut self, map_spec: MapSpec, options: MapOptions, _: &mut Writers) -> Vec<Record> {
+        let start_keys = size() / Self::GROWTH;
+        [
+            Rebuild::Doubling,
+            Rebuild::Incremental,
+            Rebuild::OverProvisioned,
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, rebuild)| {
+            let capacity = match rebuild {
+                Rebuild::Doubling | Rebuild::Incremental => {
+                    ((start_keys as f64 / Self::TARGET_LOAD) as usize).next_power_of_two()
+                }
+                Rebuild::OverProvisioned => (size() as f64 / Self::TARGET_LOAD).ceil() as usize,
+            };
+            let mut map = Resizing::new(map_spec, options, rebuild, Self::TARGET_LOAD, capacity);
+            let mut keys = KeySet::new(options.hashes.key_rng());
+            for _ in 0..start_keys {
+                map.insert(keys.push());
+            }
+
+            let rebuilds = map.rebuilds;
+            let mut probes = Series::new(Histogram::new(3).unwrap());
+            let mut writes = Series::new(Histogram::new(3).unwrap());
+            for _ in start_keys..size() {
+                let update = map.insert(keys.push());
+                probes.record(update.total_probes as u64, 1);
+                writes.record(update.total_writes as u64, 1);
+            }
+
+            Record {
+                load_factor: map.load_factor(),
+                series: vec![probes, writes],
+                scalars: vec![i as f64, (map.rebuilds - rebuilds) as f64],
+                bytes_per_key: map.memory_usage().per_key(size()),
+            }
+        })
+        .collect()
+    }
+}
+
+// a single map, grown from one load factor to the next, is analyzed at each. maps
+// report clusters, displacements, both or neither, written to outputs of their own.
+struct Analyze {
+    sweep: Sweep,
+    // whether the map reports clusters and displacements. a load factor's records are
+    // its clusters, followed by its displacements.
+    reports: [bool; 2],
+}
+
+impl Workload for Analyze {
+    fn name(&self) -> &'static str {
+        "analyze"
+    }
+
+    fn output(&self, index: usize) -> &'static str {
+        match self.reports {
+            [true, true] if index % 2 == 1 => "displacements",
+            [true, _] => "clusters",
+            _ => "displacements",
+        }
+    }
+
+    fn trial(
+        &mut self,
+        map_spec: MapSpec,
+        options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let mut map = map_spec.build(options);
+        let mut key_set = KeySet::new(options.hashes.key_rng());
+        let mut records = Vec::new();
+        let mut load = self.sweep.start;
+        while load <= self.sweep.max_load {
+            let increment = load - map.load_factor();
+            let mut log = writers.op_log("analyze_fill");
+            if grow(&mut *map, &mut key_set, increment, &mut log).is_none() {
+                break;
+            }
+            let (clusters, displacements) = (clusters(&*map), displacements(&*map));
+            self.reports = [clusters.is_some(), displacements.is_some()];
+            if self.reports == [false; 2] {
+                break;
+            }
+
+            records.extend(clusters);
+            records.extend(displacements);
+            load += self.sweep.increment;
+        }
+        records
+    }
+}
+
+// fill fresh copies of a map until inserts keep failing, once per trial, with fresh
+// hashers and keys each time. resizable maps don't fail, so are skipped. the trials are
+// the test's own, pooled into a single record.
+//
+// one row per map, at the mean of the highest loads reached, holding the load of the
+// first failed insert, that insert's probes and evictions, and the highest load
+// reached, followed by the number of trials, the fraction of them whose first failure
+// was given up on rather than past the probe limit, and the fraction which filled
+// every bucket without failing.
+struct Failures {
+    trials: Trials,
+}
+
+impl Workload for Failures {
+    fn name(&self) -> &'static str {
+        "failures"
+    }
+
+    fn trials(&self) -> usize {
+        1
+    }
+
+    fn trial(&mut self, map_spec: MapSpec, options: MapOptions, _: &mut Writers) -> Vec<Record> {
+        let mut first_loads = Series::new(Histogram::new(LOAD_SIGFIGS).unwrap());
+        let mut probes = Series::new(Histogram::new(3).unwrap());
+        let mut evictions = Series::new(Histogram::new(3).unwrap());
+        let mut max_loads = Series::new(Histogram::new(LOAD_SIGFIGS).unwrap());
+
+        let trials = self.trials.trials;
+        let (mut incomplete, mut filled) = (0, 0);
+        let mut bytes_per_key = [0.0; 4];
+        for trial in 0..trials {
+            let options = trial_options(options, trial);
+            let mut map = map_spec.build(options);
+            if map.resizable() {
+                return Vec::new();
+            }
+
+            let (first_failure, max_load) =
+                fill(&mut *map, &mut KeySet::new(options.hashes.key_rng()));
+            match first_failure {
+                Some(first) => {
+                    first_loads.record((first.load_factor * LOAD_SCALE).round() as u64, 1);
+                    probes.record(first.probes as u64, 1);
+                    evictions.record(first.evictions as u64, 1);
+                    incomplete += usize::from(first.failure == Failure::Incomplete);
+                }
+                None => filled += 1,
+            }
+            max_loads.record((max_load * LOAD_SCALE).round() as u64, 1);
+            bytes_per_key = map.memory_usage().per_key(map.len());
+        }
+
+        vec![Record {
+            load_factor: max_loads.histogram.mean() / LOAD_SCALE,
+            series: vec![first_loads, probes, evictions, max_loads],
+            scalars: vec![
+                trials as f64,
+                incomplete as f64 / trials as f64,
+                filled as f64 / trials as f64,
+            ],
+            bytes_per_key,
+        }]
+    }
+}
+
+// a mixed workload, on a map filled to each load factor.
+struct Mixed {
+    sweep: Sweep,
+    mix: Mix,
+}
+
+impl Workload for Mixed {
+    fn name(&self) -> &'static str {
+        "mixed"
+    }
+
+    fn sweep(&self) -> Option<Sweep> {
+        Some(self.sweep)
+    }
+
+    fn run(
+        &mut self,
+        map: &mut dyn Map,
+        keys: &mut KeySet,
+        options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let mut rng = options.hashes.op_rng();
+        let mut log = writers.op_log("mixed");
+        vec![mixed(
+            map,
+            keys,
+            &mut rng,
+            self.mix,
+            self.sweep.ops,
+            &mut log,
+        )]
+    }
+}
+
+// a single map, filled to the sweep's max load, or as far as it gets, and then drained
+// back down to its start, a step at a time.
+struct Drain {
+    sweep: Sweep,
+}
+
+impl Workload for Drain {
+    fn name(&self) -> &'static str {
+        "drain"
+    }
+
+    fn run(
+        &mut self,
+        map: &mut dyn Map,
+        keys: &mut KeySet,
+        _options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let sweep = self.sweep;
+        while map.load_factor() + sweep.increment <= sweep.max_load {
+            let mut log = writers.op_log("drain_fill");
+            if grow(map, keys, sweep.increment, &mut log).is_none() {
+                break;
+            }
+        }
+
+        let mut records = Vec::new();
+        while map.load_factor() > sweep.start && !keys.is_empty() {
+            let mut log = writers.op_log("drain");
+            records.push(drain(map, keys, sweep.increment, &mut log));
+        }
+        records
+    }
+}
+
+// a hot-set workload, on a map filled to each load factor.
+struct Hot {
+    sweep: Sweep,
+    hot_set: HotSet,
+}
+
+impl Workload for Hot {
+    fn name(&self) -> &'static str {
+        "hot"
+    }
+
+    fn sweep(&self) -> Option<Sweep> {
+        Some(self.sweep)
+    }
+
+    fn run(
+        &mut self,
+        map: &mut dyn Map,
+        keys: &mut KeySet,
+        options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let mut rng = options.hashes.op_rng();
+        let mut log = writers.op_log("hot");
+        vec![hot(
+            map,
+            keys,
+            &mut rng,
+            self.hot_set,
+            self.sweep.ops,
+            &mut log,
+        )]
+    }
+}
+
+// lookups of present and then absent keys made after every soak window.
+const SOAK_LOOKUPS: usize = 10_000;
+
+// a churn run long enough to show slow degradation, on a map filled to each load
+// factor. each window is written as the churn test's record, followed by the present
+// and absent probes of lookups made after it, and then by the fraction of buckets
+// holding tombstones and the mean displacement of the keys, blank for maps reporting
+// neither.
+struct Soak {
+    sweep: Sweep,
+}
+
+impl Workload for Soak {
+    fn name(&self) -> &'static str {
+        "soak"
+    }
+
+    fn sweep(&self) -> Option<Sweep> {
+        Some(self.sweep)
+    }
+
+    fn run(
+        &mut self,
+        map: &mut dyn Map,
+        keys: &mut KeySet,
+        _options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let (ops, window) = (self.sweep.ops, window(self.sweep));
+        (0..ops)
+            .step_by(window)
+            .map(|start| {
+                let count = std::cmp::min(window, ops - start);
+                let mut record = churn(map, keys, start, count, &mut writers.op_log("soak"));
+                let mut log = writers.op_log("soak_probe");
+                let lookups = probe(map, keys, SOAK_LOOKUPS / 2, 0.5, &mut log);
+                record.series.extend(lookups.series.into_iter().take(2));
+                record
+                    .scalars
+                    .extend([tombstone_fraction(map), mean_displacement(map)]);
+                record
+            })
+            .collect()
+    }
+
+    // windows share a load factor, so their histograms are told apart by start.
+    fn histograms(&self, index: usize) -> String {
+        let (ops, window) = (self.sweep.ops, window(self.sweep));
+        format!("soak{}", index % ops.div_ceil(window) * window)
+    }
+}
+
+// a fresh pair of maps per load factor, filled alike, the one updated and looked up in
+// batches and the other a key at a time.
+struct Batch {
+    sweep: Sweep,
+    batch_size: usize,
+}
+
+impl Workload for Batch {
+    fn name(&self) -> &'static str {
+        "batch"
+    }
+
+    fn trial(&mut self, map_spec: MapSpec, options: MapOptions, _: &mut Writers) -> Vec<Record> {
+        let options = pinned(options);
+        let mut records = Vec::new();
+        let mut load = self.sweep.start;
+        'loads: while load <= self.sweep.max_load {
+            let (mut batched, mut single) = (map_spec.build(options), map_spec.build(options));
+            let mut key_set = KeySet::new(options.hashes.key_rng());
+            while batched.load_factor() < load {
+                let key = key_set.push();
+                let updates = [batched.insert(key), single.insert(key)];
+                if updates.iter().any(|update| failure(update).is_some()) {
+                    break 'loads;
+                }
+            }
+
+            let (batched, single) = (&mut *batched, &mut *single);
+            let (ops, max_load) = (self.sweep.ops, self.sweep.max_load);
+            records.push(batch(
+                batched,
+                single,
+                &mut key_set,
+                self.batch_size,
+                ops,
+                max_load,
+            ));
+            load += self.sweep.increment;
+        }
+        records
+    }
+}
+
+// the lookups made at the bottom of every sawtooth cycle.
+const CYCLE_LOOKUPS: usize = 10_000;
+
+// a map grown to `high` and drained back to `low`, over and over, to show the debt, such
+// as tombstones, which every cycle leaves to the next. each cycle is written as a record
+// of its growth, followed by the series of its drain and of the present and absent
+// lookups made after it. its scalars are the cycle's number, the fraction of buckets
+// holding tombstones at the top and bottom of the cycle and the mean displacement of the
+// keys at the bottom, then those of the growth and of the drain.
+struct Sawtooth {
+    sawtooth: config::Sawtooth,
+}
+
+impl Workload for Sawtooth {
+    fn name(&self) -> &'static str {
+        "sawtooth"
+    }
+
+    fn run(
+        &mut self,
+        map: &mut dyn Map,
+        keys: &mut KeySet,
+        _options: MapOptions,
+        writers: &mut Writers,
+    ) -> Vec<Record> {
+        let config::Sawtooth { low, high, cycles } = self.sawtooth;
+        let mut log = writers.op_log("sawtooth_fill");
+        if grow(map, keys, low, &mut log).is_none() {
+            return Vec::new();
+        }
+
+        let mut records = Vec::new();
+        for cycle in 0..cycles {
+            let mut log = writers.op_log("sawtooth_grow");
+            let rise = high - map.load_factor();
+            let Some(mut record) = grow(map, keys, rise, &mut log) else {
+                break;
+            };
+            let top_tombstones = tombstone_fraction(map);
+
+            let mut log = writers.op_log("sawtooth_drain");
+            let fall = map.load_factor() - low;
+            let drained = drain(map, keys, fall, &mut log);
+            let mut log = writers.op_log("sawtooth_probe");
+            let lookups = probe(map, keys, CYCLE_LOOKUPS / 2, 0.5, &mut log);
+
+            record.series.extend(drained.series);
+            record.series.extend(lookups.series.into_iter().take(2));
+            record.scalars = [
+                cycle as f64,
+                top_tombstones,
+                tombstone_fraction(map),
+                mean_displacement(map),
+            ]
+            .into_iter()
+            .chain(record.scalars)
+            .chain(drained.scalars)
+            .collect();
+            record.bytes_per_key = drained.bytes_per_key;
+            records.push(record);
+        }
+        records
+    }
+
+    // cycles share a load factor, so their histograms are told apart by number.
+    fn histograms(&self, index: usize) -> String {
+        format!("sawtooth{index}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn churn_windows_are_told_apart_by_start() {
+        let sweep = |window| Sweep {
+            ops: 250,
+            window,
+            ..Sweep::default()
+        };
+
+        let whole = Churn { sweep: sweep(0) };
+        assert_eq!(whole.histograms(3), "churn");
+
+        let windowed = Churn { sweep: sweep(100) };
+        let names: Vec<String> = (0..4).map(|i| windowed.histograms(i)).collect();
+        assert_eq!(names, ["churn0", "churn100", "churn200", "churn0"]);
+    }
+
+    #[test]
+    fn analyzed_records_go_to_the_outputs_reported() {
+        let analyze = |reports| Analyze {
+            sweep: Sweep::default(),
+            reports,
+        };
+        let outputs = |analyze: Analyze| (0..3).map(|i| analyze.output(i)).collect::<Vec<_>>();
+
+        assert_eq!(
+            outputs(analyze([true, true])),
+            ["clusters", "displacements", "clusters"]
+        );
+        assert_eq!(outputs(analyze([true, false])), ["clusters"; 3]);
+        assert_eq!(outputs(analyze([false, true])), ["displacements"; 3]);
+    }
+}